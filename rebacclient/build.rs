@@ -0,0 +1,9 @@
+fn main() {
+    tonic_build::configure()
+        .build_server(false)
+        .compile(
+            &["../rebacserver/proto/rebacs.proto"],
+            &["../rebacserver/proto"],
+        )
+        .unwrap();
+}
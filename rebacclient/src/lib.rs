@@ -0,0 +1,240 @@
+//! client for the rebacs gRPC server
+// `tonic::Status` is large, but it is the error type of every request anyway
+#![allow(clippy::result_large_err)]
+use std::sync::Arc;
+
+use rebacdb::{Object as DbObject, ObjectOrSet, Set as DbSet};
+use tonic::{
+    codegen::InterceptedService,
+    service::Interceptor,
+    transport::{Channel, Endpoint},
+    Request, Status,
+};
+use tonic_types::StatusExt;
+
+use crate::rebacs_proto::{
+    error_details::Reason, exists_req, grant_req, is_permitted_req,
+    rebac_service_client::RebacServiceClient, revoke_req, ErrorDetails, ExistsReq, GrantReq,
+    IsMemberReq, IsPermittedReq, Object, RevokeReq, Set,
+};
+
+#[cfg(test)]
+mod tests;
+
+/// domain of the `ErrorInfo` in the details of a status of the server
+pub const ERROR_DOMAIN: &str = "rebacs.zettoit.eu";
+
+pub mod rebacs_proto {
+
+    tonic::include_proto!("eu.zettoit.rebacs");
+}
+
+/// adds the token returned by the provider as `authorization` header to every request
+#[derive(Clone)]
+pub struct TokenInterceptor {
+    provider: Arc<dyn Fn() -> String + Send + Sync>,
+}
+
+impl TokenInterceptor {
+    pub fn new(provider: impl Fn() -> String + Send + Sync + 'static) -> Self {
+        Self {
+            provider: Arc::new(provider),
+        }
+    }
+}
+
+impl Interceptor for TokenInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        let token = (self.provider)()
+            .parse()
+            .map_err(|_| Status::unauthenticated("token is not a valid header value"))?;
+        request.metadata_mut().insert("authorization", token);
+        Ok(request)
+    }
+}
+
+/// client of the rebacs server that authenticates every request with a token
+///
+/// The token is requested from the provider for every request, so a provider can refresh
+/// expired tokens. Requests without a convenience method are available through
+/// [`RebacClient::inner`].
+#[derive(Clone)]
+pub struct RebacClient {
+    inner: RebacServiceClient<InterceptedService<Channel, TokenInterceptor>>,
+}
+
+impl RebacClient {
+    /// creates a client that sends its requests over `channel`
+    pub fn new(channel: Channel, token: impl Fn() -> String + Send + Sync + 'static) -> Self {
+        Self {
+            inner: RebacServiceClient::with_interceptor(channel, TokenInterceptor::new(token)),
+        }
+    }
+
+    /// connects to the server at `dst` (e.g. `http://localhost:50051`)
+    pub async fn connect(
+        dst: impl Into<String>,
+        token: impl Fn() -> String + Send + Sync + 'static,
+    ) -> Result<Self, tonic::transport::Error> {
+        let channel = Endpoint::from_shared(dst.into())?.connect().await?;
+        Ok(Self::new(channel, token))
+    }
+
+    /// returns the generated client for requests without a convenience method
+    pub fn inner(
+        &mut self,
+    ) -> &mut RebacServiceClient<InterceptedService<Channel, TokenInterceptor>> {
+        &mut self.inner
+    }
+
+    /// creates the relation between `src` and `dst` and returns whether it didn't exist before
+    pub async fn grant(
+        &mut self,
+        src: impl Into<ObjectOrSet<'_>>,
+        dst: &DbSet,
+    ) -> Result<bool, Status> {
+        let request = GrantReq {
+            src: Some(ProtoSrc::from(src.into()).into()),
+            dst: Some(set(dst)),
+            dry_run: false,
+        };
+        Ok(self.inner.grant(request).await?.into_inner().created)
+    }
+
+    /// removes the relation between `src` and `dst` and returns whether it existed
+    pub async fn revoke(
+        &mut self,
+        src: impl Into<ObjectOrSet<'_>>,
+        dst: &DbSet,
+    ) -> Result<bool, Status> {
+        let request = RevokeReq {
+            src: Some(ProtoSrc::from(src.into()).into()),
+            dst: Some(set(dst)),
+            dry_run: false,
+        };
+        Ok(self.inner.revoke(request).await?.into_inner().removed)
+    }
+
+    /// checks if there is a *direct* relation between `src` and `dst`
+    pub async fn exists(
+        &mut self,
+        src: impl Into<ObjectOrSet<'_>>,
+        dst: &DbSet,
+    ) -> Result<bool, Status> {
+        let request = ExistsReq {
+            src: Some(ProtoSrc::from(src.into()).into()),
+            dst: Some(set(dst)),
+        };
+        Ok(self.inner.exists(request).await?.into_inner().exists)
+    }
+
+    /// checks if there is a *path* between `src` and `dst`
+    pub async fn is_permitted(
+        &mut self,
+        src: impl Into<ObjectOrSet<'_>>,
+        dst: &DbSet,
+    ) -> Result<bool, Status> {
+        let request = IsPermittedReq {
+            src: Some(ProtoSrc::from(src.into()).into()),
+            dst: Some(set(dst)),
+            min_revision: 0,
+        };
+        Ok(self
+            .inner
+            .is_permitted(request)
+            .await?
+            .into_inner()
+            .permitted)
+    }
+
+    /// checks if `subject` is a member of `group` with the relation `member_relation`
+    pub async fn is_member(
+        &mut self,
+        subject: &DbObject,
+        group: &DbObject,
+        member_relation: &str,
+    ) -> Result<bool, Status> {
+        let request = IsMemberReq {
+            subject: Some(object(subject)),
+            group: Some(object(group)),
+            member_relation: member_relation.to_string(),
+        };
+        Ok(self.inner.is_member(request).await?.into_inner().member)
+    }
+}
+
+/// decodes the [`ErrorDetails`] the server attaches to the status of a failed request
+///
+/// The server sends them as `ErrorInfo` and `BadRequest` of the standard `google.rpc.Status`
+/// details. Returns `None` if the status has no details of the server, e.g. because it was
+/// created by the client or the transport.
+pub fn error_details(status: &Status) -> Option<ErrorDetails> {
+    let details = status.get_error_details();
+    let reason = details
+        .error_info()
+        .filter(|info| info.domain == ERROR_DOMAIN)
+        .and_then(|info| Reason::from_str_name(&info.reason))?;
+    let field = details
+        .bad_request()
+        .and_then(|x| x.field_violations.first())
+        .map(|x| x.field.clone())
+        .unwrap_or_default();
+    Some(ErrorDetails {
+        reason: reason.into(),
+        field,
+    })
+}
+
+/// source of a request, intersections are sent as a set with the relations joined by `&`
+#[derive(Debug, PartialEq)]
+enum ProtoSrc {
+    Obj(Object),
+    Set(Set),
+}
+
+impl From<ObjectOrSet<'_>> for ProtoSrc {
+    fn from(value: ObjectOrSet<'_>) -> Self {
+        match value {
+            ObjectOrSet::Object(obj) => Self::Obj(object(&obj)),
+            ObjectOrSet::Set(dst) => Self::Set(set(&dst)),
+            ObjectOrSet::Intersection(intersection) => Self::Set(Set {
+                namespace: intersection.namespace().to_string(),
+                id: intersection.id().to_string(),
+                relation: intersection.relations().collect::<Vec<_>>().join("&"),
+            }),
+        }
+    }
+}
+
+macro_rules! from_src {
+    ($src:path) => {
+        impl From<ProtoSrc> for $src {
+            fn from(value: ProtoSrc) -> Self {
+                use $src;
+                match value {
+                    ProtoSrc::Obj(obj) => Src::SrcObj(obj),
+                    ProtoSrc::Set(set) => Src::SrcSet(set),
+                }
+            }
+        }
+    };
+}
+from_src!(grant_req::Src);
+from_src!(revoke_req::Src);
+from_src!(exists_req::Src);
+from_src!(is_permitted_req::Src);
+
+fn object(obj: &DbObject) -> Object {
+    Object {
+        namespace: obj.namespace().to_string(),
+        id: obj.id().to_string(),
+    }
+}
+
+fn set(set: &DbSet) -> Set {
+    Set {
+        namespace: set.namespace().to_string(),
+        id: set.id().to_string(),
+        relation: set.relation().to_string(),
+    }
+}
@@ -0,0 +1,99 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use rebacdb::{Intersection, Object as DbObject, ObjectOrSet, Set as DbSet};
+use tonic::{service::Interceptor, Code, Request, Status};
+use tonic_types::{ErrorDetails as StatusDetails, StatusExt};
+
+use crate::{
+    error_details,
+    rebacs_proto::{error_details::Reason, grant_req, ErrorDetails, Object, Set},
+    ProtoSrc, TokenInterceptor, ERROR_DOMAIN,
+};
+
+#[test]
+fn token_interceptor() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let mut interceptor = TokenInterceptor::new({
+        let calls = calls.clone();
+        move || format!("token{}", calls.fetch_add(1, Ordering::Relaxed))
+    });
+
+    for expected in ["token0", "token1"] {
+        let request = interceptor.call(Request::new(())).unwrap();
+        assert_eq!(request.metadata().get("authorization").unwrap(), expected);
+    }
+
+    let mut interceptor = TokenInterceptor::new(|| "invalid\ntoken".to_string());
+    let err = interceptor.call(Request::new(())).unwrap_err();
+    assert_eq!(err.code(), Code::Unauthenticated);
+}
+
+#[test]
+fn proto_src() {
+    let alice: DbObject = ("user", "alice").into();
+    let eng_member: DbSet = ("group", "eng", "member").into();
+
+    let src = grant_req::Src::from(ProtoSrc::from(ObjectOrSet::from(&alice)));
+    assert_eq!(
+        src,
+        grant_req::Src::SrcObj(Object {
+            namespace: "user".to_string(),
+            id: "alice".to_string(),
+        })
+    );
+
+    let src = grant_req::Src::from(ProtoSrc::from(ObjectOrSet::from(&eng_member)));
+    assert_eq!(
+        src,
+        grant_req::Src::SrcSet(Set {
+            namespace: "group".to_string(),
+            id: "eng".to_string(),
+            relation: "member".to_string(),
+        })
+    );
+
+    let intersection = Intersection::new(
+        "group".to_string(),
+        "eng".to_string(),
+        vec!["admin".to_string(), "member".to_string()],
+    );
+    let src = grant_req::Src::from(ProtoSrc::from(ObjectOrSet::from(&intersection)));
+    assert_eq!(
+        src,
+        grant_req::Src::SrcSet(Set {
+            namespace: "group".to_string(),
+            id: "eng".to_string(),
+            relation: "admin&member".to_string(),
+        })
+    );
+}
+
+#[test]
+fn decode_error_details() {
+    let details = ErrorDetails {
+        reason: Reason::FieldMissing.into(),
+        field: "dst.id".to_string(),
+    };
+    let mut status_details =
+        StatusDetails::with_error_info("FIELD_MISSING", ERROR_DOMAIN, HashMap::new());
+    status_details.add_bad_request_violation("dst.id", "dst.id must be set");
+    let status =
+        Status::with_error_details(Code::InvalidArgument, "dst.id must be set", status_details);
+    assert_eq!(error_details(&status), Some(details));
+
+    // reasons of other domains are not the ones of the server
+    let status = Status::with_error_details(
+        Code::InvalidArgument,
+        "dst.id must be set",
+        StatusDetails::with_error_info("FIELD_MISSING", "example.com", HashMap::new()),
+    );
+    assert_eq!(error_details(&status), None);
+
+    assert_eq!(error_details(&Status::internal("transport error")), None);
+}
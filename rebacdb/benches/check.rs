@@ -0,0 +1,174 @@
+//! concurrent `check` throughput, `check` time of missing relations and `expand` traversal time
+//! on a synthetic graph
+//!
+//! The graph consists of `BENCH_USERS` users (default 1000) that are members of groups. Every
+//! group is a member of `BENCH_FANOUT` (default 4) groups of the next level, `BENCH_LEVELS`
+//! (default 4) levels deep. The last level has the permission that is checked.
+//!
+//! `star_check` uses a separate graph of a user that is a member of `BENCH_STAR` (default 5000)
+//! groups with one file each.
+
+use std::{env, sync::Arc, time::Instant};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rebacdb::{Object, RelationGraph, Set};
+
+const CHECKS_PER_TASK: u64 = 100;
+
+fn env_or(name: &str, default: usize) -> usize {
+    env::var(name)
+        .ok()
+        .and_then(|x| x.parse().ok())
+        .unwrap_or(default)
+}
+
+fn group(level: usize, i: usize) -> Set {
+    ("group", format!("l{level}g{i}").as_str(), "member").into()
+}
+
+fn user(i: usize) -> Object {
+    ("user", format!("u{i}").as_str()).into()
+}
+
+async fn build_graph(users: usize, fanout: usize, levels: usize) -> RelationGraph {
+    let graph = RelationGraph::default();
+    let resource: Set = ("file", "foo", "read").into();
+
+    let mut width = fanout.pow(levels as u32);
+    for i in 0..users {
+        graph.insert(&user(i), &group(levels, i % width)).await;
+    }
+    for level in (1..=levels).rev() {
+        for i in 0..width {
+            graph
+                .insert(&group(level, i), &group(level - 1, i / fanout))
+                .await;
+        }
+        width /= fanout;
+    }
+    graph.insert(&group(0, 0), &resource).await;
+    graph
+}
+
+fn concurrent_check(c: &mut Criterion) {
+    let users = env_or("BENCH_USERS", 1000);
+    let fanout = env_or("BENCH_FANOUT", 4);
+    let levels = env_or("BENCH_LEVELS", 4);
+    let max_threads = env_or(
+        "BENCH_THREADS",
+        std::thread::available_parallelism().map_or(4, |x| x.get()),
+    );
+
+    let graph = Arc::new(
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(build_graph(users, fanout, levels)),
+    );
+    let resource: Set = ("file", "foo", "read").into();
+
+    let mut group = c.benchmark_group("concurrent_check");
+    let mut threads = 1;
+    while threads <= max_threads {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(threads)
+            .build()
+            .unwrap();
+
+        group.throughput(Throughput::Elements(threads as u64 * CHECKS_PER_TASK));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(threads),
+            &threads,
+            |b, &threads| {
+                b.iter_custom(|iters| {
+                    let start = Instant::now();
+                    for _ in 0..iters {
+                        runtime.block_on(async {
+                            let tasks = (0..threads)
+                                .map(|task| {
+                                    let graph = graph.clone();
+                                    let resource = resource.clone();
+                                    tokio::spawn(async move {
+                                        for i in 0..CHECKS_PER_TASK as usize {
+                                            let user = user((task * 7919 + i) % users);
+                                            assert!(graph.check(&user, &resource, None).await);
+                                        }
+                                    })
+                                })
+                                .collect::<Vec<_>>();
+                            for task in tasks {
+                                task.await.unwrap();
+                            }
+                        });
+                    }
+                    start.elapsed()
+                });
+            },
+        );
+        threads *= 2;
+    }
+    group.finish();
+}
+
+fn denied_check(c: &mut Criterion) {
+    let users = env_or("BENCH_USERS", 1000);
+    let fanout = env_or("BENCH_FANOUT", 4);
+    let levels = env_or("BENCH_LEVELS", 4);
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let graph = runtime.block_on(build_graph(users, fanout, levels));
+    // an admin that is related to all groups checked against a resource of another user
+    let admin = user(users);
+    let resource: Set = ("file", "bar", "read").into();
+    runtime.block_on(async {
+        for i in 0..fanout.pow(levels as u32) {
+            graph.insert(&admin, &group(levels, i)).await;
+        }
+        graph.insert(&user(users + 1), &resource).await;
+    });
+
+    c.bench_function("denied_check", |b| {
+        b.iter(|| assert!(!runtime.block_on(graph.check(&admin, &resource, None))))
+    });
+}
+
+fn star_check(c: &mut Criterion) {
+    let spokes = env_or("BENCH_STAR", 5000);
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let graph = RelationGraph::default();
+    let center = user(0);
+    let file = |i: usize| -> Set { ("file", format!("f{i}").as_str(), "read").into() };
+    runtime.block_on(async {
+        for i in 0..spokes {
+            graph.insert(&center, &group(0, i)).await;
+            graph.insert(&group(0, i), &file(i)).await;
+        }
+        // a deny relation anywhere in the graph makes `check` search all relations of the user
+        graph.insert_deny(&user(1), &file(0)).await;
+    });
+    let resource = file(spokes - 1);
+
+    c.bench_function("star_check", |b| {
+        b.iter(|| assert!(runtime.block_on(graph.check(&center, &resource, None))))
+    });
+}
+
+fn expand(c: &mut Criterion) {
+    let users = env_or("BENCH_USERS", 1000);
+    let fanout = env_or("BENCH_FANOUT", 4);
+    let levels = env_or("BENCH_LEVELS", 4);
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let graph = runtime.block_on(build_graph(users, fanout, levels));
+    let resource: Set = ("file", "foo", "read").into();
+
+    c.bench_function("expand", |b| {
+        b.iter(|| {
+            let expanded = runtime.block_on(graph.expand(&resource));
+            assert_eq!(expanded.len(), users);
+        })
+    });
+}
+
+criterion_group!(benches, concurrent_check, denied_check, star_check, expand);
+criterion_main!(benches);
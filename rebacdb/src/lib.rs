@@ -1,22 +1,56 @@
-#![doc = include_str!("../README.md")]
+#![doc = include_str!("../../README.md")]
+// `Vertex` is hashed and ordered by its immutable `id` only, the interior mutability of the edge
+// sets does not affect the key.
+#![allow(clippy::mutable_key_type)]
 use std::{
     borrow::{Borrow, Cow},
     cmp::Ordering,
-    collections::{BTreeSet, HashSet},
+    collections::{hash_map::Entry, BTreeSet, HashMap, HashSet},
     fmt::Debug,
     hash::Hash,
-    sync::Arc,
+    io,
+    num::NonZeroUsize,
+    path::Path,
+    sync::{
+        atomic::{self, AtomicU64, AtomicUsize},
+        Arc, Mutex,
+    },
 };
 
+use async_compression::tokio::{bufread::GzipDecoder, write::GzipEncoder};
+use futures::future::join_all;
+use lru::LruCache;
+use rustc_hash::{FxHashMap, FxHashSet};
 use tokio::{
-    io::{AsyncBufReadExt, AsyncWriteExt},
-    sync::RwLock,
+    fs::{self, File},
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    sync::{RwLock, RwLockWriteGuard},
 };
 
 #[cfg(test)]
 mod tests;
 
-const WILDCARD_ID: &str = "*";
+/// id that relates to every other id in the namespace (e.g. (`file`, `*`, `read`))
+pub const WILDCARD_ID: &str = "*";
+/// relation that matches every relation of an object, e.g. (`file`, `foo`, `*`) grants every
+/// relation on (`file`, `foo`), see [`RelationGraph::with_wildcards`]
+pub const WILDCARD_RELATION: &str = "*";
+/// relation used to model that an object contains another object, see
+/// [`RelationGraph::insert_contains`]
+pub const CONTAINS_RELATION: &str = "contains";
+/// version of the savefile format written by [`RelationGraph::write_savefile`]
+const SAVEFILE_VERSION: u32 = 1;
+/// source in the savefile that references the object of the current section
+const SELF_REF: &str = "self";
+/// prefix of the relation lines in the savefile that contain deny relations
+const DENY_PREFIX: &str = "!";
+/// characters with a meaning in the savefile or string format, not allowed in namespaces, ids
+/// and relations
+const RESERVED_CHARS: &[char] = &['[', ']', ':', '#', ',', '=', INTERSECTION_SEPARATOR];
+/// separator between the relations of an [`Intersection`]
+const INTERSECTION_SEPARATOR: char = '&';
+/// escapes the next character in the savefile, see [`escape`]
+const ESCAPE_CHAR: char = '\\';
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
 struct VertexId {
@@ -24,46 +58,215 @@ struct VertexId {
     id: String,
     relation: Option<String>,
 }
-/// shared version of [`VertexId`]
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
-struct VertexIdRef<'a> {
-    namespace: &'a str,
-    id: &'a str,
-    relation: Option<&'a str>,
-}
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ObjectOrSet<'a> {
     Object(Cow<'a, Object>),
     Set(Cow<'a, Set>),
+    Intersection(Cow<'a, Intersection>),
 }
 
 /// representation of a an object and a relation (e.g. (`file`, `foo.pdf`, `read`))
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Set(VertexId);
 
 /// representation of an object (e.g. (`user`, `alice`))
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Object(VertexId);
 
+/// representation of an object and multiple relations (e.g. (`group`, `eng`, [`admin`, `member`]))
+///
+/// As a source of a relation it only relates the objects that are related to *all* of its sets at
+/// the same time, e.g. the members of a group that are also admins.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Intersection(VertexId);
+
+/// error returned when parsing an [`Object`], [`Set`] or [`ObjectOrSet`] from a string like
+/// `user:alice` or `group:eng#member`
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ParseError {
+    #[error("missing `:` between namespace and id")]
+    MissingSeparator,
+    #[error("namespace is empty")]
+    EmptyNamespace,
+    #[error("id is empty")]
+    EmptyId,
+    #[error("relation is empty")]
+    EmptyRelation,
+    #[error("expected a set with a relation")]
+    MissingRelation,
+    #[error("expected an object without a relation")]
+    UnexpectedRelation,
+    #[error("invalid character {0:?}")]
+    InvalidCharacter(char),
+    #[error("invalid escape sequence")]
+    InvalidEscape,
+}
+
+/// error returned when reading a savefile with [`RelationGraph::read_savefile`]
+///
+/// The line numbers start at 1.
+#[derive(Debug, thiserror::Error)]
+pub enum SavefileError {
+    #[error("failed to read savefile: {0}")]
+    Io(#[from] io::Error),
+    #[error("line {line}: expected a section, a relation or a comment")]
+    MalformedLine { line: usize },
+    #[error("line {line}: relation outside of a section")]
+    MissingSection { line: usize },
+    #[error("line {line}: invalid section: {source}")]
+    InvalidSection { line: usize, source: ParseError },
+    #[error("line {line}: invalid relation: {source}")]
+    InvalidRelation { line: usize, source: ParseError },
+    #[error("line {line}: invalid source `{src}`: {source}")]
+    InvalidSource {
+        line: usize,
+        src: String,
+        source: ParseError,
+    },
+}
+
+/// error returned when importing a graph with [`RelationGraph::import_json`]
+///
+/// The indices of the relations start at 0.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum JsonError {
+    #[error("expected an object with a `relations` array")]
+    MalformedGraph,
+    #[error("relations[{index}]: expected an object with `src` and `dst`")]
+    MalformedRelation { index: usize },
+    #[error("relations[{index}]: invalid src: {source}")]
+    InvalidSource { index: usize, source: ParseError },
+    #[error("relations[{index}]: invalid dst: {source}")]
+    InvalidDestination { index: usize, source: ParseError },
+}
+
+/// violation of an invariant of the graph found by [`RelationGraph::validate`]
+///
+/// Relations are reported as (`src`, `dst`), `deny` is `true` for deny relations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GraphInconsistency {
+    /// `dst` is an outgoing edge of `src`, but `src` is not an incoming edge of `dst`
+    MissingEdgeIn {
+        src: ObjectOrSet<'static>,
+        dst: ObjectOrSet<'static>,
+        deny: bool,
+    },
+    /// `src` is an incoming edge of `dst`, but `dst` is not an outgoing edge of `src`
+    MissingEdgeOut {
+        src: ObjectOrSet<'static>,
+        dst: ObjectOrSet<'static>,
+        deny: bool,
+    },
+    /// `vertex` has an edge to `unknown`, which is not part of the graph
+    UnknownVertex {
+        vertex: ObjectOrSet<'static>,
+        unknown: ObjectOrSet<'static>,
+    },
+    /// `vertex` has no edges at all
+    Orphaned { vertex: ObjectOrSet<'static> },
+    /// `vertex` is not connected to `wildcard` like [`RelationGraph::insert`] connects them
+    MissingWildcardEdge {
+        vertex: ObjectOrSet<'static>,
+        wildcard: ObjectOrSet<'static>,
+    },
+}
+
+/// error returned by [`RelationGraph::try_check`] if the search reached its depth limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("depth limit reached")]
+pub struct LimitReached;
+
+/// result of the bidirectional search of [`RelationGraph::check`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Meeting {
+    /// there is no path, even if intersections and deny relations are ignored
+    Disjoint,
+    /// there is a path that neither intersections nor deny relations can invalidate
+    Related,
+    /// there is a path, but intersections or deny relations might invalidate it
+    Unconfirmed,
+    /// there is no path within the limit, but the search stopped at the limit
+    Limited,
+}
+
+/// owned copy of the relations of a [`RelationGraph`] at one point in time
+///
+/// Created by [`RelationGraph::snapshot`]. Writing a snapshot doesn't lock the graph, so slow
+/// writes don't delay changes of the graph.
+pub struct GraphSnapshot {
+    revision: u64,
+    /// the sets with relations or deny relations, ordered by their id
+    sets: Vec<SnapshotSet>,
+}
+
+/// a set of a [`GraphSnapshot`] with the sources of its relations and deny relations
+struct SnapshotSet {
+    id: VertexId,
+    srcs: Vec<VertexId>,
+    denied_srcs: Vec<VertexId>,
+}
+
+/// a vertex of the [`RelationGraph`]
+///
+/// an operation that holds edge locks of two verticies at once must acquire them ordered by
+/// [`VertexId`]. for a single vertex the incoming set is locked before the outgoing set.
 struct Vertex {
     id: VertexId,
     edges_in: RwLock<HashSet<Arc<Vertex>>>,
     edges_out: RwLock<HashSet<Arc<Vertex>>>,
+    /// deny relations created with [`RelationGraph::insert_deny`]
+    denies_in: RwLock<HashSet<Arc<Vertex>>>,
+    denies_out: RwLock<HashSet<Arc<Vertex>>>,
 }
 
 /// graph-based database implementation
-#[derive(Default)]
 pub struct RelationGraph {
     /// all verticies of the graph
     verticies: RwLock<BTreeSet<Arc<Vertex>>>,
+    /// whether [`WILDCARD_ID`] vertices are wired up and resolved automatically
+    wildcards: bool,
+    /// namespaces whose sets also match the sets of the namespace used as key
+    namespace_aliases: HashMap<String, Vec<String>>,
+    /// relations implied by the (namespace, relation) used as key, see
+    /// [`RelationGraph::with_schema`]
+    schema: HashMap<(String, String), Vec<String>>,
+    /// number of deny relations, used to skip the deny check if there are none
+    denies: AtomicUsize,
+    /// incremented on every change of the relations
+    revision: AtomicU64,
+    /// results of [`RelationGraph::check`], see [`RelationGraph::with_check_cache`]
+    check_cache: Option<CheckCache>,
 }
 
-trait VertexIdentifier {
-    fn namespace(&self) -> &str;
-    fn id(&self) -> &str;
-    fn relation(&self) -> Option<&str>;
-    fn vertex_id(&self) -> &VertexId;
+/// `src`, `dst` and `limit` of a [`RelationGraph::check`]
+type CheckKey = (VertexId, VertexId, Option<u32>);
+
+/// cache of [`RelationGraph::check`] results
+struct CheckCache {
+    /// the result with the revision of the graph it was computed at
+    results: Mutex<LruCache<CheckKey, (bool, u64)>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// number of [`RelationGraph::check`] calls answered by the cache and calls that were computed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CheckCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// node of the tree returned by [`RelationGraph::expand_tree`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpandNode {
+    /// the object, set or intersection of the node
+    pub value: ObjectOrSet<'static>,
+    /// the nodes that are directly related to `value`, sorted by namespace, id and relation
+    pub children: Vec<ExpandNode>,
+    /// `true` if `value` is a set whose children are omitted, because they are already part of
+    /// the node of the set that comes first in the tree
+    pub reference: bool,
 }
 
 impl Object {
@@ -75,6 +278,20 @@ impl Object {
         })
     }
 
+    /// creates an [`Object`], rejecting empty parts and characters that are reserved by the
+    /// savefile format or control characters
+    pub fn try_new(namespace: String, id: String) -> Result<Self, ParseError> {
+        if namespace.is_empty() {
+            return Err(ParseError::EmptyNamespace);
+        }
+        if id.is_empty() {
+            return Err(ParseError::EmptyId);
+        }
+        validate_part(&namespace)?;
+        validate_part(&id)?;
+        Ok(Self::new(namespace, id))
+    }
+
     pub fn namespace(&self) -> &str {
         &self.0.namespace
     }
@@ -97,6 +314,14 @@ impl Set {
         })
     }
 
+    /// creates a [`Set`], rejecting empty parts and characters that are reserved by the savefile
+    /// format or control characters
+    pub fn try_new(namespace: String, id: String, relation: String) -> Result<Self, ParseError> {
+        let object = Object::try_new(namespace, id)?;
+        validate_relation(&relation)?;
+        Ok(Self::new(object.0.namespace, object.0.id, relation))
+    }
+
     pub fn namespace(&self) -> &str {
         &self.0.namespace
     }
@@ -109,6 +334,68 @@ impl Set {
         self.0.relation.as_deref().unwrap_or("")
     }
 
+    /// returns the [`Object`] of the set without the relation, e.g. to group sets by object
+    pub fn object(&self) -> Object {
+        (self.namespace(), self.id()).into()
+    }
+
+    fn vertex_id(&self) -> &VertexId {
+        &self.0
+    }
+}
+
+impl Intersection {
+    /// creates an [`Intersection`] of the `relations` of the object (`namespace`, `id`)
+    pub fn new(namespace: String, id: String, relations: Vec<String>) -> Self {
+        let mut relations = relations;
+        relations.sort();
+        relations.dedup();
+        Self(VertexId {
+            namespace,
+            id,
+            relation: Some(relations.join(&INTERSECTION_SEPARATOR.to_string())),
+        })
+    }
+
+    /// creates an [`Intersection`], rejecting empty parts and characters that are reserved by the
+    /// savefile format or control characters
+    pub fn try_new(
+        namespace: String,
+        id: String,
+        relations: Vec<String>,
+    ) -> Result<Self, ParseError> {
+        let object = Object::try_new(namespace, id)?;
+        if relations.is_empty() {
+            return Err(ParseError::EmptyRelation);
+        }
+        for relation in &relations {
+            validate_relation(relation)?;
+        }
+        Ok(Self::new(object.0.namespace, object.0.id, relations))
+    }
+
+    pub fn namespace(&self) -> &str {
+        &self.0.namespace
+    }
+
+    pub fn id(&self) -> &str {
+        &self.0.id
+    }
+
+    /// returns the relations in sorted order
+    pub fn relations(&self) -> impl Iterator<Item = &str> {
+        self.0
+            .relation
+            .as_deref()
+            .unwrap_or_default()
+            .split(INTERSECTION_SEPARATOR)
+    }
+
+    /// returns the sets that an object needs to be related to
+    pub fn sets(&self) -> Vec<Set> {
+        self.0.intersection_sets()
+    }
+
     fn vertex_id(&self) -> &VertexId {
         &self.0
     }
@@ -119,362 +406,2868 @@ impl<'a> ObjectOrSet<'a> {
         match self {
             Self::Object(obj) => obj.namespace(),
             Self::Set(set) => set.namespace(),
+            Self::Intersection(intersection) => intersection.namespace(),
         }
     }
     pub fn id(&self) -> &str {
         match self {
             Self::Object(obj) => obj.id(),
             Self::Set(set) => set.id(),
+            Self::Intersection(intersection) => intersection.id(),
         }
     }
 
+    /// returns the relation of a [`Set`] or the relations of an [`Intersection`] joined by `&`
     pub fn relation(&self) -> Option<&str> {
-        match self {
-            Self::Object(_) => None,
-            Self::Set(set) => set.0.relation.as_deref(),
-        }
+        self.vertex_id().relation.as_deref()
     }
 
     fn vertex_id(&self) -> &VertexId {
         match self {
             Self::Object(obj) => obj.vertex_id(),
             Self::Set(set) => set.vertex_id(),
+            Self::Intersection(intersection) => intersection.vertex_id(),
+        }
+    }
+}
+
+impl VertexId {
+    /// checks if the vertex represents an [`Intersection`]
+    fn is_intersection(&self) -> bool {
+        self.relation
+            .as_deref()
+            .is_some_and(|x| x.contains(INTERSECTION_SEPARATOR))
+    }
+
+    /// returns the sets of an [`Intersection`] vertex
+    fn intersection_sets(&self) -> Vec<Set> {
+        self.relation
+            .as_deref()
+            .unwrap_or_default()
+            .split(INTERSECTION_SEPARATOR)
+            .map(|relation| (self.namespace.as_str(), self.id.as_str(), relation).into())
+            .collect()
+    }
+
+    /// converts the id into an [`Object`], [`Set`] or [`Intersection`]
+    fn into_object_or_set(self) -> ObjectOrSet<'static> {
+        if self.is_intersection() {
+            ObjectOrSet::Intersection(Cow::Owned(Intersection(self)))
+        } else if self.relation.is_some() {
+            ObjectOrSet::Set(Cow::Owned(Set(self)))
+        } else {
+            ObjectOrSet::Object(Cow::Owned(Object(self)))
+        }
+    }
+}
+
+impl Default for RelationGraph {
+    fn default() -> Self {
+        Self {
+            verticies: RwLock::default(),
+            wildcards: true,
+            namespace_aliases: HashMap::new(),
+            schema: HashMap::new(),
+            denies: AtomicUsize::new(0),
+            revision: AtomicU64::new(0),
+            check_cache: None,
+        }
+    }
+}
+
+impl RelationGraph {
+    /// enables or disables the automatic handling of [`WILDCARD_ID`] (enabled by default)
+    ///
+    /// If disabled, [`RelationGraph::insert`] only creates the literal relation between `src`
+    /// and `dst`, and neither [`RelationGraph::check`] nor [`RelationGraph::expand`] fall back
+    /// to the wildcard of a namespace. A `*` id is then treated like any other id.
+    ///
+    /// The setting also applies to [`WILDCARD_RELATION`]: if enabled, a relation to
+    /// (`file`, `foo`, `*`) satisfies a check for every relation of (`file`, `foo`). Both
+    /// wildcards combine, (`file`, `*`, `*`) matches every relation of every file. Wildcard
+    /// relations are only resolved by checks, [`RelationGraph::expand`] of (`file`, `foo`,
+    /// `read`) doesn't include the members of (`file`, `foo`, `*`).
+    pub fn with_wildcards(mut self, enabled: bool) -> Self {
+        self.wildcards = enabled;
+        self
+    }
+
+    /// makes the sets of the namespace `alias` match the sets of `namespace` (disabled by default)
+    ///
+    /// A check for a set in `namespace` (e.g. (`product_a`, `eng`, `member`)) then also succeeds
+    /// for the set with the same id and relation in `alias` (e.g. (`shared`, `eng`, `member`)).
+    /// The alias only applies in this direction and is not transitive.
+    pub fn with_namespace_alias(mut self, namespace: &str, alias: &str) -> Self {
+        self.namespace_aliases
+            .entry(namespace.to_string())
+            .or_default()
+            .push(alias.to_string());
+        self
+    }
+
+    /// declares computed relations: the relation of a namespace used as key implies the relations
+    /// in its value (none by default)
+    ///
+    /// With (`doc`, `editor`) -> [`viewer`], every object related to (`doc`, `foo`, `editor`) is
+    /// also related to (`doc`, `foo`, `viewer`) without an edge between the sets, and
+    /// [`RelationGraph::check`] follows the implication. Implications are transitive, e.g. with
+    /// an additional (`doc`, `owner`) -> [`editor`] an owner is a viewer as well. A deny relation
+    /// of an implying relation also denies the implied ones.
+    pub fn with_schema(mut self, schema: HashMap<(String, String), Vec<String>>) -> Self {
+        self.schema = schema;
+        self
+    }
+
+    /// caches the results of up to `capacity` [`RelationGraph::check`] calls, evicting the least
+    /// recently used ones (disabled by default, `0` disables it)
+    ///
+    /// A cached result is only used while the revision of the graph is the one it was computed
+    /// at, so every change of the relations invalidates all results.
+    pub fn with_check_cache(mut self, capacity: usize) -> Self {
+        self.check_cache = NonZeroUsize::new(capacity).map(|capacity| CheckCache {
+            results: Mutex::new(LruCache::new(capacity)),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        });
+        self
+    }
+
+    /// returns the hits and misses of the cache of [`RelationGraph::with_check_cache`], `None` if
+    /// the cache is disabled
+    pub fn check_cache_stats(&self) -> Option<CheckCacheStats> {
+        self.check_cache.as_ref().map(|cache| CheckCacheStats {
+            hits: cache.hits.load(atomic::Ordering::Relaxed),
+            misses: cache.misses.load(atomic::Ordering::Relaxed),
+        })
+    }
+
+    /// create a new graph containing the relations from `src` to `dst`
+    ///
+    /// This is equivalent to calling [`RelationGraph::insert`] for every relation.
+    pub async fn from_relations<'a>(
+        relations: impl IntoIterator<Item = (impl Into<ObjectOrSet<'a>>, &'a Set)>,
+    ) -> Self {
+        let graph = Self::default();
+        graph.insert_many(relations).await;
+        graph
+    }
+
+    /// create a new relation between from a [`Object`] or [`Set`] to a [`Set`]
+    ///
+    /// returns `true` if the relation was newly created and `false` if it already existed
+    pub async fn insert(&self, src: impl Into<ObjectOrSet<'_>>, dst: &Set) -> bool {
+        self.insert_edge(src.into(), dst, false).await.is_some()
+    }
+
+    /// [`RelationGraph::insert`] that returns the revision of the graph that contains the new
+    /// relation, or `None` if it already existed
+    ///
+    /// Unlike [`RelationGraph::current_revision`] after the insert, the revision can't include
+    /// concurrent changes.
+    pub async fn insert_revision(&self, src: impl Into<ObjectOrSet<'_>>, dst: &Set) -> Option<u64> {
+        self.insert_edge(src.into(), dst, false).await
+    }
+
+    /// create a new deny relation between from a [`Object`] or [`Set`] to a [`Set`]
+    ///
+    /// Deny relations take precedence over all other relations: [`RelationGraph::check`] returns
+    /// `false` if there is a path from `src` to `dst` that contains a deny relation, regardless of
+    /// any other path. E.g. denying (`user`, `alice`) -> (`project`, `x`, `access`) revokes the
+    /// access of alice even if she is a member of a group that has access to the project.
+    ///
+    /// returns `true` if the deny relation was newly created and `false` if it already existed
+    pub async fn insert_deny(&self, src: impl Into<ObjectOrSet<'_>>, dst: &Set) -> bool {
+        self.insert_edge(src.into(), dst, true).await.is_some()
+    }
+
+    /// create the relations from `src` to `dst`
+    ///
+    /// This is equivalent to calling [`RelationGraph::insert`] for every relation, but the graph is
+    /// only locked once for all of them. Returns the number of newly created relations.
+    pub async fn insert_many<'a>(
+        &self,
+        relations: impl IntoIterator<Item = (impl Into<ObjectOrSet<'a>>, &'a Set)>,
+    ) -> usize {
+        self.insert_each(relations)
+            .await
+            .into_iter()
+            .filter(|created| *created)
+            .count()
+    }
+
+    /// [`RelationGraph::insert_many`] that returns whether each relation was newly created, in
+    /// the order of `relations`
+    ///
+    /// No other change of the graph is applied between the relations.
+    pub async fn insert_each<'a>(
+        &self,
+        relations: impl IntoIterator<Item = (impl Into<ObjectOrSet<'a>>, &'a Set)>,
+    ) -> Vec<bool> {
+        self.insert_each_revision(relations)
+            .await
+            .into_iter()
+            .map(|revision| revision.is_some())
+            .collect()
+    }
+
+    /// [`RelationGraph::insert_each`] that returns the revision of the graph that contains each
+    /// newly created relation, see [`RelationGraph::insert_revision`]
+    pub async fn insert_each_revision<'a>(
+        &self,
+        relations: impl IntoIterator<Item = (impl Into<ObjectOrSet<'a>>, &'a Set)>,
+    ) -> Vec<Option<u64>> {
+        let mut verticies = self.verticies.write().await;
+        let mut created = vec![];
+        for (src, dst) in relations {
+            created.push(
+                self.insert_edge_locked(&mut verticies, src.into(), dst, false)
+                    .await,
+            );
+        }
+        created
+    }
+
+    async fn insert_edge(&self, src: ObjectOrSet<'_>, dst: &Set, deny: bool) -> Option<u64> {
+        let mut verticies = self.verticies.write().await;
+        self.insert_edge_locked(&mut verticies, src, dst, deny)
+            .await
+    }
+
+    /// [`RelationGraph::insert_edge`] with the already locked `verticies`
+    ///
+    /// returns the revision of the graph with the new relation, or `None` if it already existed
+    async fn insert_edge_locked(
+        &self,
+        verticies: &mut BTreeSet<Arc<Vertex>>,
+        src: ObjectOrSet<'_>,
+        dst: &Set,
+        deny: bool,
+    ) -> Option<u64> {
+        let mut get_or_create = |vertex: &VertexId| match verticies.get(vertex) {
+            Some(vertex) => vertex.clone(),
+            None => {
+                let vertex = Arc::new(Vertex::new(vertex.clone()));
+                verticies.insert(vertex.clone());
+                vertex
+            }
+        };
+
+        let src_vertex = get_or_create(src.vertex_id());
+        let dst_vertex = get_or_create(dst.vertex_id());
+
+        // the sets of an intersection are connected to the intersection, which is only traversed
+        // if all of them are related to the source of the traversal
+        let mut sets = vec![];
+        if let ObjectOrSet::Intersection(intersection) = &src {
+            for set in intersection.sets() {
+                let set_vertex = get_or_create(set.vertex_id());
+                add_edge(set_vertex.clone(), src_vertex.clone()).await;
+                sets.push(set_vertex);
+            }
+        }
+
+        if self.wildcards {
+            if src_vertex.id.id != WILDCARD_ID && !src_vertex.id.is_intersection() {
+                let src_wildcard: ObjectOrSet =
+                    (src.namespace(), WILDCARD_ID, src.relation()).into();
+                let src_wildcard = get_or_create(src_wildcard.vertex_id());
+
+                if src.relation().is_none() {
+                    add_edge(src_vertex.clone(), src_wildcard).await;
+                } else {
+                    add_edge(src_wildcard, src_vertex.clone()).await;
+                }
+            }
+
+            for set_vertex in sets.into_iter().chain([dst_vertex.clone()]) {
+                if set_vertex.id.id != WILDCARD_ID {
+                    let set_wildcard: Set = (
+                        set_vertex.id.namespace.as_str(),
+                        WILDCARD_ID,
+                        set_vertex.id.relation.as_deref().unwrap_or_default(),
+                    )
+                        .into();
+                    let set_wildcard = get_or_create(set_wildcard.vertex_id());
+                    add_edge(set_wildcard, set_vertex).await;
+                }
+            }
+        }
+
+        let created = if deny {
+            add_deny_edge(src_vertex, dst_vertex).await
+        } else {
+            add_edge(src_vertex, dst_vertex).await
+        };
+
+        if !created {
+            return None;
+        }
+        if deny {
+            self.denies.fetch_add(1, atomic::Ordering::Relaxed);
+        }
+        Some(self.revision.fetch_add(1, atomic::Ordering::Relaxed) + 1)
+    }
+
+    /// create a containment relation between two objects (e.g. a directory containing a file)
+    ///
+    /// Objects can't be the destination of a relation, so the containment is stored as the
+    /// relation (`child`, [`CONTAINS_RELATION`]) -> (`parent`, [`CONTAINS_RELATION`]). This makes
+    /// it transitive: everything contained in `child` is also contained in `parent`. Use
+    /// [`RelationGraph::is_contained`] to check it.
+    ///
+    /// returns `true` if the relation was newly created and `false` if it already existed
+    pub async fn insert_contains(&self, parent: &Object, child: &Object) -> bool {
+        let (parent, child) = contains_sets(parent, child);
+        self.insert(&child, &parent).await
+    }
+
+    /// remove a containment relation created with [`RelationGraph::insert_contains`]
+    ///
+    /// returns `true` if the relation existed and `false` if there was nothing to remove
+    pub async fn remove_contains(&self, parent: &Object, child: &Object) -> bool {
+        let (parent, child) = contains_sets(parent, child);
+        self.remove(&child, &parent).await
+    }
+
+    /// checks if `parent` contains `child`, either directly or through other objects
+    pub async fn is_contained(&self, child: &Object, parent: &Object, limit: Option<u32>) -> bool {
+        let (parent, child) = contains_sets(parent, child);
+        self.check(&child, &parent, limit).await
+    }
+
+    /// remove a relation
+    ///
+    /// returns `true` if the relation existed and `false` if there was nothing to remove
+    pub async fn remove(&self, src: impl Into<ObjectOrSet<'_>>, dst: &Set) -> bool {
+        self.remove_edge(src.into(), dst, false).await.is_some()
+    }
+
+    /// [`RelationGraph::remove`] that returns the revision of the graph without the relation, or
+    /// `None` if there was nothing to remove, see [`RelationGraph::insert_revision`]
+    pub async fn remove_revision(&self, src: impl Into<ObjectOrSet<'_>>, dst: &Set) -> Option<u64> {
+        self.remove_edge(src.into(), dst, false).await
+    }
+
+    /// remove a deny relation created with [`RelationGraph::insert_deny`]
+    ///
+    /// returns `true` if the deny relation existed and `false` if there was nothing to remove
+    pub async fn remove_deny(&self, src: impl Into<ObjectOrSet<'_>>, dst: &Set) -> bool {
+        self.remove_edge(src.into(), dst, true).await.is_some()
+    }
+
+    async fn remove_edge(&self, src: ObjectOrSet<'_>, dst: &Set, deny: bool) -> Option<u64> {
+        let mut verticies = self.verticies.write().await;
+
+        let src = verticies.get(src.vertex_id()).cloned();
+        let dst = verticies.get(dst.vertex_id()).cloned();
+
+        if let (Some(src), Some(dst)) = (src, dst) {
+            let removed = if deny {
+                dst.denies_in.write().await.remove(&src);
+                src.denies_out.write().await.remove(&dst)
+            } else {
+                dst.edges_in.write().await.remove(&src);
+                src.edges_out.write().await.remove(&dst)
+            };
+
+            let revision = removed.then(|| {
+                if deny {
+                    self.denies.fetch_sub(1, atomic::Ordering::Relaxed);
+                }
+                self.revision.fetch_add(1, atomic::Ordering::Relaxed) + 1
+            });
+
+            remove_unused_intersection(&mut verticies, &src, self.wildcards).await;
+            remove_if_unused(&mut verticies, &src, self.wildcards).await;
+            remove_if_unused(&mut verticies, &dst, self.wildcards).await;
+            revision
+        } else {
+            None
+        }
+    }
+
+    /// remove every relation and deny relation from or to a vertex of `namespace`
+    ///
+    /// All verticies of `namespace` are removed, including its wildcards. Verticies of other
+    /// namespaces are removed like in [`RelationGraph::remove`] once they no longer take part in
+    /// any relation. Returns the number of removed relations and deny relations.
+    pub async fn remove_namespace(&self, namespace: &str) -> usize {
+        let mut verticies = self.verticies.write().await;
+        let removed_verticies = namespace_verticies(&verticies, namespace)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut relations = vec![];
+        let mut neighbors = vec![];
+        for vertex in &removed_verticies {
+            drain_relations(vertex, &mut relations, &mut neighbors, self.wildcards).await;
+        }
+        for vertex in &removed_verticies {
+            verticies.remove(&vertex.id);
+        }
+
+        for neighbor in neighbors.iter().filter(|x| x.id.namespace != namespace) {
+            remove_unused_intersection(&mut verticies, neighbor, self.wildcards).await;
+            remove_if_unused(&mut verticies, neighbor, self.wildcards).await;
+        }
+
+        let denies = relations.iter().filter(|(_, _, deny)| *deny).count();
+        self.denies.fetch_sub(denies, atomic::Ordering::Relaxed);
+        self.revision
+            .fetch_add(relations.len() as u64, atomic::Ordering::Relaxed);
+        relations.len()
+    }
+
+    /// move all relations and deny relations of the object (`namespace`, `old_id`) and its sets
+    /// to (`namespace`, `new_id`)
+    ///
+    /// The relations are moved at once, a check never sees the object with only a part of its
+    /// relations moved. If `new_id` already has relations, the moved relations are added to
+    /// them. The wildcards are wired up like in [`RelationGraph::insert`]. Returns the number of
+    /// moved relations and deny relations.
+    pub async fn rename_object(&self, namespace: &str, old_id: &str, new_id: &str) -> usize {
+        if old_id == new_id {
+            return 0;
+        }
+
+        let mut verticies = self.verticies.write().await;
+        let renamed_verticies = object_verticies(&verticies, namespace, old_id)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut relations = vec![];
+        let mut neighbors = vec![];
+        for vertex in &renamed_verticies {
+            drain_relations(vertex, &mut relations, &mut neighbors, self.wildcards).await;
+        }
+        for vertex in &renamed_verticies {
+            verticies.remove(&vertex.id);
+        }
+        let denies = relations.iter().filter(|(_, _, deny)| *deny).count();
+        self.denies.fetch_sub(denies, atomic::Ordering::Relaxed);
+        self.revision
+            .fetch_add(relations.len() as u64, atomic::Ordering::Relaxed);
+
+        let rename = |mut id: VertexId| {
+            if id.namespace == namespace && id.id == old_id {
+                id.id = new_id.to_string();
+            }
+            id
+        };
+        for (src, dst, deny) in &relations {
+            let src = rename(src.clone()).into_object_or_set();
+            let dst = Set(rename(dst.clone()));
+            self.insert_edge_locked(&mut verticies, src, &dst, *deny)
+                .await;
+        }
+
+        // the verticies that were only related to the old object are removed, the others are
+        // related to the new one now
+        for neighbor in neighbors
+            .iter()
+            .filter(|x| !(x.id.namespace == namespace && x.id.id == old_id))
+        {
+            remove_unused_intersection(&mut verticies, neighbor, self.wildcards).await;
+            remove_if_unused(&mut verticies, neighbor, self.wildcards).await;
+        }
+        relations.len()
+    }
+
+    /// removes verticies that are only connected to wildcards and wildcards without any relations
+    ///
+    /// A vertex whose only edges are the ones [`RelationGraph::insert`] creates between a vertex
+    /// and its wildcard doesn't take part in any relation and is treated like a missing vertex by
+    /// [`RelationGraph::check`] and [`RelationGraph::expand`]. Returns the number of removed
+    /// verticies.
+    pub async fn compact(&self) -> usize {
+        let mut verticies = self.verticies.write().await;
+
+        let mut orphans = vec![];
+        for vertex in verticies.iter().filter(|x| x.id.id != WILDCARD_ID) {
+            if vertex.has_only_wildcard_connectors(self.wildcards).await {
+                orphans.push(vertex.clone());
+            }
+        }
+
+        for orphan in &orphans {
+            detach(orphan).await;
+            verticies.remove(&orphan.id);
+        }
+
+        let mut wildcards = vec![];
+        for vertex in verticies.iter().filter(|x| x.id.id == WILDCARD_ID) {
+            if vertex.is_isolated().await {
+                wildcards.push(vertex.clone());
+            }
+        }
+        for wildcard in &wildcards {
+            verticies.remove(&wildcard.id);
+        }
+
+        orphans.len() + wildcards.len()
+    }
+
+    /// checks the invariants of the graph, e.g. after it was read from a savefile
+    ///
+    /// Every edge has to be recorded by both of its verticies and point to a vertex of the graph,
+    /// no vertex may be without edges and, if wildcards are enabled, every object has to be
+    /// connected to the wildcard of its namespace and every set to the wildcard of its relation.
+    /// All violations are returned, ordered by the vertex they were found at.
+    pub async fn validate(&self) -> Result<(), Vec<GraphInconsistency>> {
+        let verticies = self.verticies.read().await;
+        let id = |vertex: &Vertex| vertex.id.clone().into_object_or_set();
+        let mut inconsistencies = vec![];
+
+        for vertex in verticies.iter() {
+            if vertex.is_isolated().await {
+                inconsistencies.push(GraphInconsistency::Orphaned { vertex: id(vertex) });
+                continue;
+            }
+
+            for deny in [false, true] {
+                let (edges_out, edges_in) = match deny {
+                    false => (&vertex.edges_out, &vertex.edges_in),
+                    true => (&vertex.denies_out, &vertex.denies_in),
+                };
+                // every lock is released before the one of the other vertex is taken
+                let mut dsts = edges_out.read().await.iter().cloned().collect::<Vec<_>>();
+                dsts.sort();
+                let mut srcs = edges_in.read().await.iter().cloned().collect::<Vec<_>>();
+                srcs.sort();
+
+                for other in dsts.iter().chain(&srcs) {
+                    if !verticies
+                        .get(&other.id)
+                        .is_some_and(|x| Arc::ptr_eq(x, other))
+                    {
+                        inconsistencies.push(GraphInconsistency::UnknownVertex {
+                            vertex: id(vertex),
+                            unknown: id(other),
+                        });
+                    }
+                }
+                for dst in dsts {
+                    let dst_edges_in = match deny {
+                        false => &dst.edges_in,
+                        true => &dst.denies_in,
+                    };
+                    if !dst_edges_in.read().await.contains(vertex) {
+                        inconsistencies.push(GraphInconsistency::MissingEdgeIn {
+                            src: id(vertex),
+                            dst: id(&dst),
+                            deny,
+                        });
+                    }
+                }
+                for src in srcs {
+                    let src_edges_out = match deny {
+                        false => &src.edges_out,
+                        true => &src.denies_out,
+                    };
+                    if !src_edges_out.read().await.contains(vertex) {
+                        inconsistencies.push(GraphInconsistency::MissingEdgeOut {
+                            src: id(&src),
+                            dst: id(vertex),
+                            deny,
+                        });
+                    }
+                }
+            }
+
+            if self.wildcards && vertex.id.id != WILDCARD_ID && !vertex.id.is_intersection() {
+                let wildcard = VertexId {
+                    namespace: vertex.id.namespace.clone(),
+                    id: WILDCARD_ID.to_string(),
+                    relation: vertex.id.relation.clone(),
+                };
+                let connected = match vertex.id.relation {
+                    None => vertex.edges_out.read().await.contains(&wildcard),
+                    Some(_) => vertex.edges_in.read().await.contains(&wildcard),
+                };
+                if !connected {
+                    inconsistencies.push(GraphInconsistency::MissingWildcardEdge {
+                        vertex: id(vertex),
+                        wildcard: wildcard.into_object_or_set(),
+                    });
+                }
+            }
+        }
+
+        match inconsistencies.is_empty() {
+            true => Ok(()),
+            false => Err(inconsistencies),
+        }
+    }
+
+    /// returns the revision of the graph, which is incremented on every change of the relations
+    pub fn current_revision(&self) -> u64 {
+        self.revision.load(atomic::Ordering::Relaxed)
+    }
+
+    /// checks if the [`Object`] or [`Set`] is part of any relation
+    pub async fn contains(&self, obj: impl Into<ObjectOrSet<'_>>) -> bool {
+        let obj: ObjectOrSet<'_> = obj.into();
+        self.verticies.read().await.contains(obj.vertex_id())
+    }
+
+    /// returns the number of verticies, including wildcards and intersections
+    pub async fn vertex_count(&self) -> usize {
+        self.verticies.read().await.len()
+    }
+
+    /// returns the number of relations, deny relations are not counted
+    ///
+    /// The edges [`RelationGraph::insert`] creates between a vertex and its wildcard are only
+    /// counted if `include_wildcard_connectors` is set.
+    pub async fn edge_count(&self, include_wildcard_connectors: bool) -> usize {
+        let verticies = self.verticies.read().await;
+        let mut count = 0;
+        for vertex in verticies.iter() {
+            let edges_out = vertex.edges_out.read().await;
+            count += match include_wildcard_connectors {
+                true => edges_out.len(),
+                false => edges_out
+                    .iter()
+                    .filter(|dst| !is_wildcard_connector(&vertex.id, &dst.id))
+                    .count(),
+            };
+        }
+        count
+    }
+
+    /// counts the objects of `namespace` that have at least one relation with `relation`
+    ///
+    /// Wildcard objects and the relations to the wildcard of the namespace are not counted.
+    pub async fn count_objects_with_relation(&self, namespace: &str, relation: &str) -> usize {
+        let verticies = self.verticies.read().await;
+        let mut count = 0;
+        for vertex in namespace_verticies(&verticies, namespace)
+            .filter(|x| x.id.id != WILDCARD_ID && x.id.relation.as_deref() == Some(relation))
+        {
+            let related = vertex
+                .edges_in
+                .read()
+                .await
+                .iter()
+                .any(|src| !is_set_wildcard_connector(&src.id, &vertex.id));
+            if related {
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// get the names of all relations that exist on `obj`, sorted by name
+    pub async fn relations_on(&self, obj: &Object) -> Vec<String> {
+        let verticies = self.verticies.read().await;
+        object_verticies(&verticies, obj.namespace(), obj.id())
+            .filter(|x| !x.id.is_intersection())
+            .filter_map(|x| x.id.relation.clone())
+            .collect()
+    }
+
+    /// checks if there is a *direct* relation between `src` and `dst`
+    pub async fn has(&self, src: impl Into<ObjectOrSet<'_>>, dst: &Set) -> bool {
+        let src: ObjectOrSet<'_> = src.into();
+        let (src, dst) = {
+            let verticies = self.verticies.read().await;
+            (
+                verticies.get(src.vertex_id()).cloned(),
+                verticies.get(dst.vertex_id()).cloned(),
+            )
+        };
+
+        if let (Some(src), Some(dst)) = (src, dst) {
+            src.edges_out.read().await.contains(&dst)
+        } else {
+            false
+        }
+    }
+
+    /// checks if there is a *path* between src and dst using a bidirectional
+    /// [BFS](https://en.wikipedia.org/wiki/Breadth-first_search)
+    ///
+    /// The search alternates between the relations of `src` and the members of `dst` and stops
+    /// as soon as they meet, so a missing relation doesn't explore everything `src` is related
+    /// to. Paths through intersections or in graphs with deny relations are confirmed like in
+    /// [`RelationGraph::check_path`].
+    ///
+    /// # Arguments
+    /// * `src` - start of the path
+    /// * `dst` - end of the path
+    /// * `limit` - optional maximum search depth of the search before returing false, shared by
+    ///   both directions
+    pub async fn check<'a>(
+        &self,
+        src: impl Into<ObjectOrSet<'_>>,
+        dst: &Set,
+        limit: Option<u32>,
+    ) -> bool {
+        let src: ObjectOrSet<'_> = src.into();
+        let Some(cache) = &self.check_cache else {
+            return self.check_uncached(src, dst, limit).await;
+        };
+
+        // read before the check, a change during the check makes the result outdated
+        let revision = self.current_revision();
+        let key = (src.vertex_id().clone(), dst.0.clone(), limit);
+        if let Some((related, _)) = cache
+            .results
+            .lock()
+            .unwrap()
+            .get(&key)
+            .filter(|(_, cached)| *cached == revision)
+        {
+            cache.hits.fetch_add(1, atomic::Ordering::Relaxed);
+            return *related;
+        }
+
+        cache.misses.fetch_add(1, atomic::Ordering::Relaxed);
+        let related = self.check_uncached(src, dst, limit).await;
+        cache.results.lock().unwrap().put(key, (related, revision));
+        related
+    }
+
+    /// [`RelationGraph::check`] without the cache
+    async fn check_uncached(&self, src: ObjectOrSet<'_>, dst: &Set, limit: Option<u32>) -> bool {
+        match self.meet(&src, dst, limit).await.0 {
+            Meeting::Disjoint | Meeting::Limited => false,
+            Meeting::Related => true,
+            Meeting::Unconfirmed => self.check_path(src, dst, limit).await.is_some(),
+        }
+    }
+
+    /// checks if `subject` is a member of `group` with the relation `member_relation`
+    ///
+    /// This is [`RelationGraph::check`] of `subject` and the set (`group`, `member_relation`),
+    /// e.g. `is_member(alice, (group, eng), "member", None)`. An invalid `member_relation` is
+    /// never a member relation.
+    pub async fn is_member(
+        &self,
+        subject: &Object,
+        group: &Object,
+        member_relation: &str,
+        limit: Option<u32>,
+    ) -> bool {
+        let Ok(members) = Set::try_new(
+            group.namespace().to_string(),
+            group.id().to_string(),
+            member_relation.to_string(),
+        ) else {
+            return false;
+        };
+        self.check(subject, &members, limit).await
+    }
+
+    /// [`RelationGraph::check`] that returns [`LimitReached`] instead of `false` if the search
+    /// stopped at `limit` before finding a path
+    ///
+    /// This allows distinguishing a missing relation from a relation chain that is too deep. The
+    /// bidirectional search decides the check unless it is unconfirmed or stopped at `limit`.
+    pub async fn try_check(
+        &self,
+        src: impl Into<ObjectOrSet<'_>>,
+        dst: &Set,
+        limit: Option<u32>,
+    ) -> Result<bool, LimitReached> {
+        let src: ObjectOrSet<'_> = src.into();
+        match self.meet(&src, dst, limit).await.0 {
+            Meeting::Disjoint => Ok(false),
+            Meeting::Related => Ok(true),
+            Meeting::Unconfirmed | Meeting::Limited => {
+                Ok(self.try_check_path(src, dst, limit).await?.is_some())
+            }
+        }
+    }
+
+    /// returns the sets that connect `src` to `dst` if there is a *path* between them
+    ///
+    /// The sets are ordered from `src` to `dst` and contain neither of them, the path of a direct
+    /// relation is empty. If `dst` is only reached through the wildcard of its namespace, the
+    /// wildcard set (e.g. (`file`, `*`, `read`)) is the last set of the path. `None` has the same
+    /// meaning as `false` returned by [`RelationGraph::check`].
+    pub async fn check_path<'a>(
+        &self,
+        src: impl Into<ObjectOrSet<'a>>,
+        dst: &Set,
+        limit: Option<u32>,
+    ) -> Option<Vec<Set>> {
+        self.try_check_path(src, dst, limit).await.unwrap_or(None)
+    }
+
+    /// [`RelationGraph::check_path`] that returns [`LimitReached`] like
+    /// [`RelationGraph::try_check`]
+    async fn try_check_path(
+        &self,
+        src: impl Into<ObjectOrSet<'_>>,
+        dst: &Set,
+        limit: Option<u32>,
+    ) -> Result<Option<Vec<Set>>, LimitReached> {
+        self.search(src.into(), limit, |vertex| {
+            self.is_target(vertex, dst).then(|| dst.clone())
+        })
+        .await
+    }
+
+    /// checks if there is a *path* between `src` and `dst` that passes through `via`
+    ///
+    /// The `limit` applies to both parts of the path, `src` -> `via` and `via` -> `dst`. Deny
+    /// relations of `src` are honored like in [`RelationGraph::check`].
+    pub async fn check_via<'a>(
+        &self,
+        src: impl Into<ObjectOrSet<'a>>,
+        dst: &Set,
+        via: &Set,
+        limit: Option<u32>,
+    ) -> bool {
+        let src: ObjectOrSet<'_> = src.into();
+        if !self.check(&src, via, limit).await || !self.check(via, dst, limit).await {
+            return false;
+        }
+        match self.start_vertex(&src).await {
+            Some(start) => {
+                !self
+                    .is_denied(&src, start, dst, limit, &HashSet::new())
+                    .await
+            }
+            None => false,
+        }
+    }
+
+    /// returns all wildcard sets on the paths that relate `src` to `dst`
+    ///
+    /// Unlike [`RelationGraph::check`], the search doesn't stop at the first match, every
+    /// wildcard set (e.g. (`file`, `*`, `read`)) that `src` reaches and that leads to `dst` is
+    /// returned. The `limit` applies to both parts of the path like in
+    /// [`RelationGraph::check_via`]. The result is empty if `src` is not related to `dst` and
+    /// sorted by namespace, id and relation.
+    pub async fn audit_check<'a>(
+        &self,
+        src: impl Into<ObjectOrSet<'a>>,
+        dst: &Set,
+        limit: Option<u32>,
+    ) -> Vec<Set> {
+        let src: ObjectOrSet<'_> = src.into();
+        if !self.check(&src, dst, limit).await {
+            return vec![];
+        }
+
+        let mut wildcards = vec![];
+        for set in self.reachable_sets(&src, limit).await {
+            if set.id() != WILDCARD_ID {
+                continue;
+            }
+            if self.is_target(set.vertex_id(), dst) || self.check(&set, dst, limit).await {
+                wildcards.push(set);
+            }
+        }
+        wildcards
+    }
+
+    /// returns every set that lies on any path from `src` to `dst`, including `dst` itself
+    ///
+    /// The sets are the intersection of the sets reachable from `src` and the sets that reach
+    /// `dst`, where the distances from `src` and to `dst` together are within `limit` like the
+    /// paths of [`RelationGraph::check`]. The result is empty if `src` is not related to `dst`
+    /// and sorted by namespace, id and relation.
+    pub async fn ancestor_sets<'a>(
+        &self,
+        src: impl Into<ObjectOrSet<'a>>,
+        dst: &Set,
+        limit: Option<u32>,
+    ) -> Vec<Set> {
+        let src: ObjectOrSet<'_> = src.into();
+        if !self.check(&src, dst, limit).await {
+            return vec![];
+        }
+
+        let mut neighbors: Vec<Arc<Vertex>> = {
+            let verticies = self.verticies.read().await;
+            let wildcard_dst: Set = (dst.namespace(), WILDCARD_ID, dst.relation()).into();
+            [Some(dst), self.wildcards.then_some(&wildcard_dst)]
+                .into_iter()
+                .flatten()
+                .filter_map(|x| verticies.get(x.vertex_id()).cloned())
+                .collect()
+        };
+        // the ancestors with their distance to `dst`
+        let mut ancestors: FxHashMap<VertexId, u32> = FxHashMap::default();
+        let mut distance = 0;
+        while !neighbors.is_empty() && limit.map(|limit| distance <= limit).unwrap_or(true) {
+            let mut next_neighbors = vec![];
+            for neighbor in neighbors {
+                if let Entry::Vacant(entry) = ancestors.entry(neighbor.id.clone()) {
+                    entry.insert(distance);
+                    next_neighbors.extend(neighbor.edges_in.read().await.iter().cloned());
+                }
+            }
+            neighbors = next_neighbors;
+            distance += 1;
+        }
+
+        let mut sets: Vec<Set> = self
+            .reachable_set_distances(&src, limit)
+            .await
+            .into_iter()
+            .filter(|(set, forward)| {
+                set != dst
+                    && ancestors.get(set.vertex_id()).is_some_and(|backward| {
+                        limit
+                            .map(|limit| forward + backward <= limit)
+                            .unwrap_or(true)
+                    })
+            })
+            .map(|(set, _)| set)
+            .collect();
+        sets.push(dst.clone());
+        sets.sort_by(|a, b| a.0.cmp(&b.0));
+        sets
+    }
+
+    /// checks if there is a *path* between src and any relation of the object (`namespace`, `id`)
+    /// that starts with `relation_prefix`
+    ///
+    /// This allows checking for hierarchical relation names, e.g. the prefix `repo.` matches the
+    /// relations `repo.read` and `repo.write`.
+    pub async fn check_relation_prefix(
+        &self,
+        src: impl Into<ObjectOrSet<'_>>,
+        namespace: &str,
+        id: &str,
+        relation_prefix: &str,
+        limit: Option<u32>,
+    ) -> bool {
+        self.search(src.into(), limit, |vertex| {
+            let relation = vertex.relation.as_deref()?;
+            (vertex.namespace == namespace
+                && (vertex.id == id || (self.wildcards && vertex.id == WILDCARD_ID))
+                && relation.starts_with(relation_prefix))
+            .then(|| (namespace, id, relation).into())
+        })
+        .await
+        .is_ok_and(|path| path.is_some())
+    }
+
+    /// searches a path from `src` to a vertex for which `target` returns the matched [`Set`]
+    /// using [BFS](https://en.wikipedia.org/wiki/Breadth-first_search)
+    ///
+    /// A match only counts if `src` is not denied access to the matched [`Set`]. Returns the sets
+    /// between `src` and the matched [`Set`], see [`RelationGraph::check_path`].
+    async fn search(
+        &self,
+        src: ObjectOrSet<'_>,
+        limit: Option<u32>,
+        target: impl Fn(&VertexId) -> Option<Set> + Sync,
+    ) -> Result<Option<Vec<Set>>, LimitReached> {
+        self.search_excluding(src, limit, &target, &HashSet::new())
+            .await
+    }
+
+    /// [`RelationGraph::search`] that doesn't traverse the intersections in `excluded`
+    async fn search_excluding(
+        &self,
+        src: ObjectOrSet<'_>,
+        limit: Option<u32>,
+        target: &(dyn Fn(&VertexId) -> Option<Set> + Sync),
+        excluded: &HashSet<VertexId>,
+    ) -> Result<Option<Vec<Set>>, LimitReached> {
+        let mut distance = 1;
+
+        let Some(start) = self.start_vertex(&src).await else {
+            return Ok(None);
+        };
+
+        let mut neighbors: Vec<(Arc<Vertex>, Option<Arc<Vertex>>)> = self
+            .start_frontier(&start)
+            .await
+            .into_iter()
+            .map(|v| (v, None))
+            .collect();
+
+        // the visited vertices with the vertex they were reached from, the path is reconstructed
+        // from them
+        let mut visited: FxHashMap<Arc<Vertex>, Option<Arc<Vertex>>> = FxHashMap::default();
+
+        while !neighbors.is_empty() {
+            if let Some(limit) = limit {
+                if distance > limit {
+                    return Err(LimitReached);
+                }
+            }
+
+            let mut expanded = vec![];
+            for (neighbor, predecessor) in neighbors {
+                if distance > 1 && visited.contains_key(&neighbor) {
+                    continue;
+                }
+
+                if neighbor.id.is_intersection()
+                    && !self
+                        .intersection_satisfied(&src, &neighbor, limit, excluded)
+                        .await
+                {
+                    visited.insert(neighbor, predecessor);
+                    continue;
+                }
+
+                if let Some(dst) = target(&neighbor.id) {
+                    if !self
+                        .is_denied(&src, start.clone(), &dst, limit, excluded)
+                        .await
+                    {
+                        return Ok(Some(search_path(&visited, predecessor, &neighbor, &dst)));
+                    }
+                }
+
+                visited.insert(neighbor.clone(), predecessor);
+                expanded.push(neighbor);
+            }
+
+            // the edges of the whole frontier are read concurrently instead of waiting for the
+            // lock of one vertex after the other
+            neighbors = join_all(expanded.into_iter().map(|neighbor| async move {
+                // looked up before the edges are locked, the graph is never locked while holding
+                // the edge lock of a vertex
+                let implied = self.implied_verticies(&neighbor.id).await;
+                let edges = neighbor.edges_out.read().await;
+                edges
+                    .iter()
+                    .chain(implied.iter())
+                    .map(|v| (v.clone(), Some(neighbor.clone())))
+                    .collect::<Vec<_>>()
+            }))
+            .await
+            .into_iter()
+            .flatten()
+            .collect();
+            distance += 1;
+        }
+        Ok(None)
+    }
+
+    /// checks if `src` is related to all sets of the intersection `vertex`
+    ///
+    /// Intersections in `excluded` are already being checked and are not traversed again, which
+    /// would never terminate.
+    async fn intersection_satisfied(
+        &self,
+        src: &ObjectOrSet<'_>,
+        vertex: &Vertex,
+        limit: Option<u32>,
+        excluded: &HashSet<VertexId>,
+    ) -> bool {
+        if excluded.contains(&vertex.id) {
+            return false;
+        }
+        let mut excluded = excluded.clone();
+        excluded.insert(vertex.id.clone());
+
+        for set in vertex.id.intersection_sets() {
+            let target = |vertex: &VertexId| self.is_target(vertex, &set).then(|| set.clone());
+            let related = Box::pin(self.search_excluding(src.clone(), limit, &target, &excluded));
+            if !matches!(related.await, Ok(Some(_))) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// get all sets that `src` is related to, either directly or through other sets
+    ///
+    /// Wildcard sets are returned as they are, e.g. (`file`, `*`, `read`) if `src` can read every
+    /// file. Sets `src` is denied access to are omitted. The sets are sorted by namespace, id and
+    /// relation.
+    pub async fn reachable_sets(
+        &self,
+        src: impl Into<ObjectOrSet<'_>>,
+        limit: Option<u32>,
+    ) -> Vec<Set> {
+        let mut sets = self
+            .reachable_set_distances(&src.into(), limit)
+            .await
+            .into_iter()
+            .map(|(set, _)| set)
+            .collect::<Vec<_>>();
+        sets.sort_by(|a, b| a.0.cmp(&b.0));
+        sets
+    }
+
+    /// [`RelationGraph::reachable_sets`] with the number of relations on the shortest path from
+    /// `src` to each set, unsorted
+    async fn reachable_set_distances(
+        &self,
+        src: &ObjectOrSet<'_>,
+        limit: Option<u32>,
+    ) -> Vec<(Set, u32)> {
+        let Some(start) = self.start_vertex(src).await else {
+            return vec![];
+        };
+
+        let mut distance = 1;
+        let mut neighbors = self.start_frontier(&start).await;
+        let mut visited: FxHashSet<Arc<Vertex>> = FxHashSet::default();
+        let mut sets = vec![];
+
+        while !neighbors.is_empty() && limit.map(|limit| distance <= limit).unwrap_or(true) {
+            let mut next_neighbors = vec![];
+            for neighbor in neighbors {
+                if visited.contains(&neighbor) {
+                    continue;
+                }
+
+                if neighbor.id.is_intersection() {
+                    let satisfied = self
+                        .intersection_satisfied(src, &neighbor, limit, &HashSet::new())
+                        .await;
+                    if !satisfied {
+                        visited.insert(neighbor);
+                        continue;
+                    }
+                } else if neighbor.id.relation.is_some() {
+                    let set = Set(neighbor.id.clone());
+                    if !self
+                        .is_denied(src, start.clone(), &set, limit, &HashSet::new())
+                        .await
+                    {
+                        sets.push((set, distance));
+                    }
+                }
+
+                next_neighbors.extend(neighbor.edges_out.read().await.iter().cloned());
+                visited.insert(neighbor);
+            }
+            neighbors = next_neighbors;
+            distance += 1;
+        }
+        sets
+    }
+
+    /// searches from the relations of `src` and the members of `dst` until they meet and returns
+    /// the number of visited verticies
+    ///
+    /// Intersections and deny relations are ignored, so [`Meeting::Disjoint`] is final while a
+    /// path found through an intersection or in a graph with deny relations is
+    /// [`Meeting::Unconfirmed`]. The frontier with fewer edges is expanded first, and the distances
+    /// of both directions together never exceed `limit`. If that stopped the search before the
+    /// directions met, the result is [`Meeting::Limited`].
+    async fn meet(&self, src: &ObjectOrSet<'_>, dst: &Set, limit: Option<u32>) -> (Meeting, usize) {
+        let Some(start) = self.start_vertex(src).await else {
+            return (Meeting::Disjoint, 0);
+        };
+        // computed relations are no edges, only the search of `RelationGraph::check_path` follows
+        // them
+        if !self.schema.is_empty() {
+            return (Meeting::Unconfirmed, 0);
+        }
+
+        // the verticies with their distance to `src` and to `dst`. Like in
+        // `RelationGraph::start_frontier` the wildcard of an object is an additional root.
+        let mut forward = Frontier::default();
+        if self.wildcards && start.id.relation.is_none() && start.id.id != WILDCARD_ID {
+            let wildcard: Object = (start.id.namespace.as_str(), WILDCARD_ID).into();
+            let wildcard = self
+                .verticies
+                .read()
+                .await
+                .get(wildcard.vertex_id())
+                .cloned();
+            if let Some(wildcard) = wildcard {
+                forward.visit(wildcard, 0);
+            }
+        }
+        forward.visit(start, 0);
+        let mut backward = Frontier::default();
+        for vertex in self.target_verticies(dst).await {
+            backward.visit(vertex, 0);
+        }
+
+        // the roots of both directions only meet through at least one relation
+        let within = |distance: u32| limit.map(|limit| distance <= limit).unwrap_or(true);
+        let mut met = false;
+        while !met
+            && !forward.current.is_empty()
+            && !backward.current.is_empty()
+            && within(forward.depth + backward.depth + 1)
+        {
+            met = if forward.cost(true).await <= backward.cost(false).await {
+                forward.expand(&backward, true, &within).await
+            } else {
+                backward.expand(&forward, false, &within).await
+            };
+        }
+
+        let visited = forward.visited.len() + backward.visited.len();
+        let stopped = !forward.current.is_empty() && !backward.current.is_empty();
+        let meeting = if !met && (stopped || forward.limited || backward.limited) {
+            Meeting::Limited
+        } else if !met {
+            Meeting::Disjoint
+        } else if forward.intersections
+            || backward.intersections
+            || self.denies.load(atomic::Ordering::Relaxed) > 0
+        {
+            Meeting::Unconfirmed
+        } else {
+            Meeting::Related
+        };
+        (meeting, visited)
+    }
+
+    /// returns the existing verticies that match `dst`, see [`RelationGraph::is_target`]
+    async fn target_verticies(&self, dst: &Set) -> Vec<Arc<Vertex>> {
+        let aliases = self
+            .namespace_aliases
+            .get(dst.namespace())
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let (ids, relations): (&[&str], &[&str]) = match self.wildcards {
+            true => (
+                &[dst.id(), WILDCARD_ID],
+                &[dst.relation(), WILDCARD_RELATION],
+            ),
+            false => (&[dst.id()], &[dst.relation()]),
+        };
+
+        let verticies = self.verticies.read().await;
+        let mut targets = vec![];
+        let mut target = dst.0.clone();
+        for namespace in [dst.namespace()]
+            .into_iter()
+            .chain(aliases.iter().map(String::as_str))
+        {
+            namespace.clone_into(&mut target.namespace);
+            for &id in ids {
+                id.clone_into(&mut target.id);
+                for &relation in relations {
+                    target.relation = Some(relation.to_string());
+                    targets.extend(verticies.get(&target).cloned());
+                }
+            }
+        }
+        targets
+    }
+
+    /// returns the vertex of `src`, or the wildcard vertex of its namespace if `src` doesn't exist
+    async fn start_vertex(&self, src: &ObjectOrSet<'_>) -> Option<Arc<Vertex>> {
+        let verticies = self.verticies.read().await;
+        match verticies.get(src.vertex_id()) {
+            Some(src) => Some(src.clone()),
+            None if !self.wildcards => None,
+            None => {
+                let wildcard_src: Object = (src.namespace(), WILDCARD_ID).into();
+                verticies.get(wildcard_src.vertex_id()).cloned()
+            }
+        }
+    }
+
+    /// returns the neighbors of `start` and, if `start` is an object, the neighbors of the wildcard
+    /// of its namespace
+    ///
+    /// The wildcard is an additional root of the traversal, so relations granted to the wildcard
+    /// (e.g. (`user`, `*`) being a member of a group) apply at the same distance as relations of
+    /// `start` itself.
+    async fn start_frontier(&self, start: &Arc<Vertex>) -> Vec<Arc<Vertex>> {
+        let mut frontier: Vec<Arc<Vertex>> = start.edges_out.read().await.iter().cloned().collect();
+
+        if self.wildcards && start.id.relation.is_none() && start.id.id != WILDCARD_ID {
+            let wildcard: Object = (start.id.namespace.as_str(), WILDCARD_ID).into();
+            let wildcard = self
+                .verticies
+                .read()
+                .await
+                .get(wildcard.vertex_id())
+                .cloned();
+            if let Some(wildcard) = wildcard {
+                frontier.extend(wildcard.edges_out.read().await.iter().cloned());
+            }
+        }
+        frontier
+    }
+
+    /// synchronous version of [`RelationGraph::check`] for callers outside of an async runtime
+    ///
+    /// This blocks the current thread until the check is done. It must not be called from within
+    /// an async context, as it could deadlock the runtime.
+    pub fn check_sync<'a>(
+        &self,
+        src: impl Into<ObjectOrSet<'a>>,
+        dst: &Set,
+        limit: Option<u32>,
+    ) -> bool {
+        futures::executor::block_on(self.check(src, dst, limit))
+    }
+
+    /// checks if `vertex` is the `dst` vertex or the wildcard vertex for the `dst` namespace,
+    /// or one of them in an alias of the `dst` namespace
+    ///
+    /// Without checking the wildcard vertex, not initialized dsts that should be affected by the
+    /// wildcard wouldn't be found. [`WILDCARD_RELATION`] and a relation that implies the `dst`
+    /// relation according to the schema match as well.
+    fn is_target(&self, vertex: &VertexId, dst: &Set) -> bool {
+        let aliases = self
+            .namespace_aliases
+            .get(dst.namespace())
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        (vertex.namespace == dst.namespace() || aliases.contains(&vertex.namespace))
+            && (vertex.id == dst.id() || (self.wildcards && vertex.id == WILDCARD_ID))
+            && vertex.relation.as_deref().is_some_and(|relation| {
+                relation == dst.relation()
+                    || (self.wildcards && relation == WILDCARD_RELATION)
+                    || self
+                        .implied_relations(&vertex.namespace, relation)
+                        .contains(&dst.relation())
+            })
+    }
+
+    /// returns the relations that `relation` of `namespace` implies directly or transitively
+    /// according to the schema, excluding `relation` itself
+    fn implied_relations<'a>(&'a self, namespace: &str, relation: &'a str) -> Vec<&'a str> {
+        let mut implied = vec![];
+        let mut stack = vec![relation];
+        while let Some(relation) = stack.pop() {
+            let Some(relations) = self
+                .schema
+                .get(&(namespace.to_string(), relation.to_string()))
+            else {
+                continue;
+            };
+            for relation in relations {
+                if !implied.contains(&relation.as_str()) {
+                    implied.push(relation.as_str());
+                    stack.push(relation);
+                }
+            }
+        }
+        implied.retain(|x| *x != relation);
+        implied
+    }
+
+    /// returns the existing verticies of the sets that the set `vertex` implies, see
+    /// [`RelationGraph::with_schema`]
+    async fn implied_verticies(&self, vertex: &VertexId) -> Vec<Arc<Vertex>> {
+        let Some(relation) = vertex.relation.as_deref() else {
+            return vec![];
+        };
+        if self.schema.is_empty() || vertex.is_intersection() {
+            return vec![];
+        }
+
+        let verticies = self.verticies.read().await;
+        let mut implied = vertex.clone();
+        self.implied_relations(&vertex.namespace, relation)
+            .into_iter()
+            .filter_map(|relation| {
+                implied.relation = Some(relation.to_string());
+                verticies.get(&implied).cloned()
+            })
+            .collect()
+    }
+
+    /// checks if there is a path from `start`, the vertex of `src`, to `dst` that contains a deny
+    /// relation
+    ///
+    /// An intersection is only passed if `src` is related to all of its sets within the depth
+    /// that is left of `limit` when the path arrives at it. The intersections in `excluded` are
+    /// never passed, like in [`RelationGraph::intersection_satisfied`].
+    async fn is_denied(
+        &self,
+        src: &ObjectOrSet<'_>,
+        start: Arc<Vertex>,
+        dst: &Set,
+        limit: Option<u32>,
+        excluded: &HashSet<VertexId>,
+    ) -> bool {
+        if self.denies.load(atomic::Ordering::Relaxed) == 0 {
+            return false;
+        }
+
+        // the search runs on the vertices paired with whether a deny relation has already been
+        // passed on the way to them
+        let mut visited: FxHashSet<(Arc<Vertex>, bool)> = FxHashSet::default();
+        let mut distance = 0;
+        let mut neighbors = vec![(start, false)];
+        while !neighbors.is_empty() {
+            let mut next_neighbors = vec![];
+            for (vertex, denied) in neighbors {
+                if denied && self.is_target(&vertex.id, dst) {
+                    return true;
+                }
+                if visited.contains(&(vertex.clone(), denied)) {
+                    continue;
+                }
+                visited.insert((vertex.clone(), denied));
+
+                // `src` itself may be an intersection, which is not checked
+                if distance > 0 && vertex.id.is_intersection() {
+                    // one of the sets was reached at the previous distance
+                    let remaining = limit.map(|limit| limit.saturating_sub(distance - 1));
+                    if !self
+                        .intersection_satisfied(src, &vertex, remaining, excluded)
+                        .await
+                    {
+                        continue;
+                    }
+                }
+
+                next_neighbors.extend(
+                    vertex
+                        .edges_out
+                        .read()
+                        .await
+                        .iter()
+                        .map(|v| (v.clone(), denied)),
+                );
+                if !denied {
+                    next_neighbors.extend(
+                        vertex
+                            .denies_out
+                            .read()
+                            .await
+                            .iter()
+                            .map(|v| (v.clone(), true)),
+                    );
+                }
+            }
+            neighbors = next_neighbors;
+            distance += 1;
+        }
+        false
+    }
+
+    /// get all relations to the sets of `namespace` as (`src`, `dst`) pairs
+    ///
+    /// Every relation created with [`RelationGraph::insert`] is returned once. The edges that
+    /// connect a set to the wildcard of its namespace and deny relations are omitted. The pairs
+    /// are sorted by `dst` and then by `src`, each by namespace, id and relation.
+    pub async fn list_relations(&self, namespace: &str) -> Vec<(ObjectOrSet<'static>, Set)> {
+        let verticies = self.verticies.read().await;
+        let mut relations = vec![];
+        for dst in namespace_verticies(&verticies, namespace)
+            .filter(|x| x.id.relation.is_some() && !x.id.is_intersection())
+        {
+            for src in sorted_edges_in(dst).await {
+                if !is_wildcard_connector(&src.id, &dst.id) {
+                    relations.push((src.id.clone().into_object_or_set(), Set(dst.id.clone())));
+                }
+            }
+        }
+        relations
+    }
+
+    /// get the objects and sets that are *directly* related to `set`
+    ///
+    /// Unlike [`RelationGraph::expand`] this doesn't follow the sources any further. The edges
+    /// that connect `set` to the wildcard of its namespace are omitted. The result is sorted by
+    /// namespace, id and relation.
+    pub async fn direct_members(&self, set: &Set) -> Vec<ObjectOrSet<'static>> {
+        let Some(vertex) = self.verticies.read().await.get(set.vertex_id()).cloned() else {
+            return vec![];
+        };
+
+        let mut members = vertex
+            .edges_in
+            .read()
+            .await
+            .iter()
+            .filter(|src| !is_set_wildcard_connector(&src.id, &vertex.id))
+            .map(|src| src.id.clone())
+            .collect::<Vec<_>>();
+        members.sort();
+
+        members
+            .into_iter()
+            .map(VertexId::into_object_or_set)
+            .collect()
+    }
+
+    /// get the sets `src` is *directly* related to, the reverse of
+    /// [`RelationGraph::direct_members`]
+    ///
+    /// The edges that connect `src` to the wildcard of its namespace and the intersections `src`
+    /// is part of are omitted. The result is sorted by namespace, id and relation.
+    pub async fn direct_relations(&self, src: impl Into<ObjectOrSet<'_>>) -> Vec<Set> {
+        let src: ObjectOrSet<'_> = src.into();
+        let Some(vertex) = self.verticies.read().await.get(src.vertex_id()).cloned() else {
+            return vec![];
+        };
+
+        let mut relations = vertex
+            .edges_out
+            .read()
+            .await
+            .iter()
+            .filter(|dst| dst.id.relation.is_some() && !dst.id.is_intersection())
+            .filter(|dst| !is_wildcard_connector(&vertex.id, &dst.id))
+            .map(|dst| dst.id.clone())
+            .collect::<Vec<_>>();
+        relations.sort();
+
+        relations.into_iter().map(Set).collect()
+    }
+
+    /// get all objects that are related to dst with the relation path
+    ///
+    /// Objects that are denied access to `dst` (see [`RelationGraph::insert_deny`]) are omitted.
+    ///
+    /// A wildcard `dst` (e.g. (`file`, `*`, `read`)) is expanded directly and only returns the
+    /// objects related to the wildcard itself. A concrete `dst` that does not exist falls back
+    /// to the wildcard of its namespace.
+    ///
+    /// Every set is traversed at most once and every object is returned at most once with its
+    /// shortest path, so cyclic memberships (e.g. group `a` member of `b` member of `a`) neither
+    /// repeat objects nor prevent termination, and no path is longer than the number of sets.
+    pub async fn expand(&self, dst: &Set) -> Vec<(Object, Vec<Set>)> {
+        self.expand_filtered(dst, |_| true).await
+    }
+
+    /// get all objects for which `filter` returns `true` that are related to dst with the
+    /// relation path
+    ///
+    /// The `filter` is only applied to the related objects, not to the sets in between. See
+    /// [`RelationGraph::expand`] for details.
+    pub async fn expand_filtered(
+        &self,
+        dst: &Set,
+        filter: impl Fn(&Object) -> bool,
+    ) -> Vec<(Object, Vec<Set>)> {
+        self.expand_capped_filtered(dst, None, None, filter).await.0
+    }
+
+    /// get all objects that are related to dst with a relation path of at most `limit` sets
+    ///
+    /// `limit` counts the sets like the depth limit of [`RelationGraph::check`], an object that
+    /// is directly related to `dst` has a path of length 1. See [`RelationGraph::expand`] for
+    /// details.
+    pub async fn expand_limited(&self, dst: &Set, limit: Option<u32>) -> Vec<(Object, Vec<Set>)> {
+        self.expand_capped(dst, None, limit).await.0
+    }
+
+    /// get at most `max_results` objects that are related to dst with the relation path and
+    /// whether there are more
+    ///
+    /// The traversal stops as soon as `max_results` objects are found, the returned flag is `true`
+    /// if it stopped before all related objects were found. The objects are returned in the order
+    /// of their distance to `dst`, equally distant objects ordered by their path, so the same graph
+    /// always returns the same objects. Objects that are further away from `dst` than `limit`
+    /// are omitted like in [`RelationGraph::expand_limited`], without setting the flag. See
+    /// [`RelationGraph::expand`] for details.
+    pub async fn expand_capped(
+        &self,
+        dst: &Set,
+        max_results: Option<usize>,
+        limit: Option<u32>,
+    ) -> (Vec<(Object, Vec<Set>)>, bool) {
+        self.expand_capped_filtered(dst, max_results, limit, |_| true)
+            .await
+    }
+
+    /// [`RelationGraph::expand_capped`] that only returns objects for which `filter` returns
+    /// `true`
+    async fn expand_capped_filtered(
+        &self,
+        dst: &Set,
+        max_results: Option<usize>,
+        limit: Option<u32>,
+        filter: impl Fn(&Object) -> bool,
+    ) -> (Vec<(Object, Vec<Set>)>, bool) {
+        let start_vertex = {
+            let verticies = self.verticies.read().await;
+            match verticies.get(dst.vertex_id()) {
+                Some(v) => v.clone(),
+                None if !self.wildcards || dst.id() == WILDCARD_ID => return (vec![], false),
+                None => {
+                    let wildcard_dst: Set = (dst.namespace(), WILDCARD_ID, dst.relation()).into();
+
+                    match verticies.get(wildcard_dst.vertex_id()) {
+                        Some(v) => v.clone(),
+                        None => return (vec![], false),
+                    }
+                }
+            }
+        };
+
+        let mut visited: FxHashSet<Arc<Vertex>> = FxHashSet::default();
+
+        let mut neighbors: Vec<(Arc<Vertex>, Vec<Arc<Vertex>>)> = sorted_edges_in(&start_vertex)
+            .await
+            .into_iter()
+            .map(|v| (v, vec![start_vertex.clone()]))
+            .collect();
+
+        visited.insert(start_vertex);
+
+        let mut expanded_verticies: Vec<(Arc<Vertex>, Vec<Arc<Vertex>>)> = vec![];
+        let mut truncated = false;
+        let mut distance = 1;
+
+        'search: while !neighbors.is_empty() && limit.map(|limit| distance <= limit).unwrap_or(true)
+        {
+            let mut next_neighbors = vec![];
+            for (neighbor, mut neighbor_path) in neighbors {
+                if visited.contains(&neighbor) {
+                    continue;
+                }
+
+                if neighbor.id.relation.is_none() {
+                    // an object is expanded once, with its shortest path. A path that is not
+                    // allowed doesn't mark it as visited, another path may still be.
+                    if !filter(&Object(neighbor.id.clone())) {
+                        visited.insert(neighbor);
+                    } else if self.expand_allowed(&neighbor, &neighbor_path, dst).await {
+                        if max_results.is_some_and(|max| expanded_verticies.len() >= max) {
+                            truncated = true;
+                            break 'search;
+                        }
+                        visited.insert(neighbor.clone());
+                        expanded_verticies.push((neighbor, neighbor_path));
+                    }
+                    continue;
+                }
+
+                neighbor_path.push(neighbor.clone());
+
+                next_neighbors.extend(
+                    sorted_edges_in(&neighbor)
+                        .await
+                        .into_iter()
+                        .map(|v| (v, neighbor_path.clone())),
+                );
+
+                visited.insert(neighbor);
+            }
+            neighbors = next_neighbors;
+            distance += 1;
+        }
+
+        let expanded = expanded_verticies
+            .into_iter()
+            .map(|(v, path)| {
+                (
+                    Object(v.id.clone()),
+                    path.into_iter().map(|w| Set(w.id.clone())).collect(),
+                )
+            })
+            .collect();
+        (expanded, truncated)
+    }
+
+    /// get the objects and sets that are related to `dst` as tree
+    ///
+    /// The children of a set are the objects, sets and intersections that are directly related
+    /// to it (see [`RelationGraph::direct_members`]), the sets are expanded further while objects
+    /// and intersections are leafs. Unlike [`RelationGraph::expand`] this keeps the branching of
+    /// the relations, a set related to `dst` through multiple paths appears once on each of them.
+    ///
+    /// Only the first node of a set in depth-first order has children, every other node of the
+    /// set is a [`ExpandNode::reference`] to it. This keeps the tree as large as the graph, even
+    /// for cycles (e.g. group `a` member of `b` member of `a`) and sets that are related to
+    /// `dst` through many paths. The wildcard of a set (e.g. (`file`, `*`,
+    /// `read`)) is a child of every set of its relation once anything is related to it. Deny
+    /// relations are not applied, use [`RelationGraph::expand`] to get the objects that actually
+    /// have access.
+    pub async fn expand_tree(&self, dst: &Set) -> ExpandNode {
+        let start_vertex = {
+            let verticies = self.verticies.read().await;
+            match verticies.get(dst.vertex_id()) {
+                Some(v) => Some(v.clone()),
+                None if self.wildcards && dst.id() != WILDCARD_ID => {
+                    let wildcard_dst: Set = (dst.namespace(), WILDCARD_ID, dst.relation()).into();
+                    verticies.get(wildcard_dst.vertex_id()).cloned()
+                }
+                None => None,
+            }
+        };
+
+        // the sources of every reachable set are read once, the tree is built afterwards
+        let mut children: FxHashMap<Arc<Vertex>, Vec<Arc<Vertex>>> = FxHashMap::default();
+        let mut stack: Vec<Arc<Vertex>> = start_vertex.iter().cloned().collect();
+        while let Some(vertex) = stack.pop() {
+            if children.contains_key(&vertex) {
+                continue;
+            }
+            let srcs = sorted_edges_in(&vertex).await;
+            stack.extend(
+                srcs.iter()
+                    .filter(|v| v.id.relation.is_some() && !v.id.is_intersection())
+                    .cloned(),
+            );
+            children.insert(vertex, srcs);
+        }
+
+        let mut expanded = FxHashSet::default();
+        match start_vertex {
+            Some(v) if v.id == dst.0 => expand_tree_node(&v, &children, &mut expanded),
+            // a missing `dst` is related to the wildcard of its set like every other set
+            Some(v) if !children[&v].is_empty() => ExpandNode {
+                value: ObjectOrSet::Set(Cow::Owned(dst.clone())),
+                children: vec![expand_tree_node(&v, &children, &mut expanded)],
+                reference: false,
+            },
+            _ => ExpandNode {
+                value: ObjectOrSet::Set(Cow::Owned(dst.clone())),
+                children: vec![],
+                reference: false,
+            },
+        }
+    }
+
+    /// checks if the object `v` that reaches `dst` through `path` is part of the expansion
+    ///
+    /// Objects that reach `dst` through an intersection must be related to all of its sets and
+    /// objects that are denied access to `dst` are omitted.
+    async fn expand_allowed(&self, v: &Arc<Vertex>, path: &[Arc<Vertex>], dst: &Set) -> bool {
+        let obj: ObjectOrSet = Object(v.id.clone()).into();
+        for intersection in path.iter().filter(|w| w.id.is_intersection()) {
+            if !self
+                .intersection_satisfied(&obj, intersection, None, &HashSet::new())
+                .await
+            {
+                return false;
+            }
+        }
+        !self
+            .is_denied(&obj, v.clone(), dst, None, &HashSet::new())
+            .await
+    }
+
+    /// renders the graph as [Graphviz](https://graphviz.org) digraph, e.g. for `dot -Tsvg`
+    ///
+    /// Every vertex is labeled `namespace:id` or `namespace:id#relation`, wildcards are dashed.
+    /// Deny relations are drawn red, the edges [`RelationGraph::insert`] creates between a
+    /// vertex and its wildcard are omitted. The output is sorted like the savefile.
+    pub async fn to_dot(&self) -> String {
+        let verticies = self.verticies.read().await;
+        let mut dot = String::from("digraph {\n");
+        for vertex in verticies.iter() {
+            let style = match vertex.id.id == WILDCARD_ID {
+                true => " [style=dashed]",
+                false => "",
+            };
+            dot.push_str(&format!("  {}{}\n", dot_id(&vertex.id), style));
+        }
+        for vertex in verticies.iter() {
+            let mut edges = vertex
+                .edges_out
+                .read()
+                .await
+                .iter()
+                .filter(|dst| !is_wildcard_connector(&vertex.id, &dst.id))
+                .map(|dst| (dst.id.clone(), false))
+                .collect::<Vec<_>>();
+            edges.extend(
+                vertex
+                    .denies_out
+                    .read()
+                    .await
+                    .iter()
+                    .map(|dst| (dst.id.clone(), true)),
+            );
+            edges.sort();
+            for (dst, deny) in edges {
+                let style = match deny {
+                    true => " [color=red]",
+                    false => "",
+                };
+                dot.push_str(&format!(
+                    "  {} -> {}{}\n",
+                    dot_id(&vertex.id),
+                    dot_id(&dst),
+                    style
+                ));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// write graph to file
+    ///
+    /// The output is deterministic, writing a graph that was read from a savefile reproduces the
+    /// same savefile. Errors of `writeable` are returned, the savefile is incomplete in this case.
+    pub async fn write_savefile(
+        &self,
+        writeable: &mut (impl AsyncWriteExt + Unpin),
+    ) -> io::Result<()> {
+        self.snapshot().await.write_savefile(writeable).await
+    }
+
+    /// write graph to file compressed with gzip, e.g. as `graph.dat.gz`
+    ///
+    /// The savefile is the same as the one of [`RelationGraph::write_savefile`]. `writeable` is
+    /// shut down after the gzip trailer is written.
+    pub async fn write_savefile_gz(
+        &self,
+        writeable: &mut (impl AsyncWrite + Unpin),
+    ) -> io::Result<()> {
+        self.snapshot().await.write_savefile_gz(writeable).await
+    }
+
+    /// write graph to the savefile at `path` without corrupting it if the write fails
+    ///
+    /// The savefile is written to `<path>.tmp`, synced to disk and only then renamed to `path`.
+    /// If writing fails, the previous savefile at `path` is left untouched.
+    pub async fn save_to_path(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.snapshot().await.save_to_path(path).await
+    }
+
+    /// write graph to one savefile per namespace into `dir`
+    ///
+    /// Each file is named after the namespace of the objects it contains (e.g. `file.dat`) and
+    /// uses the same format as [`RelationGraph::write_savefile`]. Every file is replaced like in
+    /// [`RelationGraph::save_to_path`]. Fails with [`io::ErrorKind::InvalidInput`] before
+    /// writing any file if a namespace is no valid file name, e.g. `../file` or `.file`.
+    pub async fn write_savefiles_by_namespace(&self, dir: impl AsRef<Path>) -> io::Result<()> {
+        let snapshot = self.snapshot().await;
+        let namespaces = snapshot
+            .sets
+            .iter()
+            .map(|set| set.id.namespace.as_str())
+            .collect::<BTreeSet<_>>();
+
+        if let Some(namespace) = namespaces
+            .iter()
+            .find(|namespace| namespace.starts_with('.') || namespace.contains(['/', '\\']))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("namespace `{namespace}` is no valid savefile name"),
+            ));
+        }
+
+        for namespace in namespaces {
+            snapshot
+                .save_namespaces_to_path(
+                    dir.as_ref().join(format!("{namespace}.dat")),
+                    Some(namespace),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// export the relations of the graph as JSON
+    ///
+    /// The graph is exported as `{"revision": 1, "relations": [...]}`, every relation as
+    /// `{"src": {...}, "dst": {...}}` and deny relations with an additional `"deny": true`. See
+    /// [`GraphSnapshot::to_json`] for details.
+    pub async fn export_json(&self) -> serde_json::Value {
+        self.snapshot().await.to_json()
+    }
+
+    /// copies the relations of the graph into an owned [`GraphSnapshot`]
+    ///
+    /// Every change of the relations holds the write lock of all verticies, so copying them
+    /// under the read lock captures the graph between two changes. The graph is only locked
+    /// while copying, not while the snapshot is written.
+    pub async fn snapshot(&self) -> GraphSnapshot {
+        let verticies = self.verticies.read().await;
+        let mut sets = vec![];
+        // the sets of an intersection are restored when it is read as a source
+        for vertex in verticies
+            .iter()
+            .filter(|x| x.id.relation.is_some() && !x.id.is_intersection())
+        {
+            let srcs = vertex
+                .edges_in
+                .read()
+                .await
+                .iter()
+                // without wildcards the edge is an explicit relation of the same shape
+                .filter(|src| !self.wildcards || !is_set_wildcard_connector(&src.id, &vertex.id))
+                .map(|src| src.id.clone())
+                .collect::<Vec<_>>();
+            let denied_srcs = vertex
+                .denies_in
+                .read()
+                .await
+                .iter()
+                .map(|src| src.id.clone())
+                .collect::<Vec<_>>();
+            if !srcs.is_empty() || !denied_srcs.is_empty() {
+                sets.push(SnapshotSet {
+                    id: vertex.id.clone(),
+                    srcs,
+                    denied_srcs,
+                });
+            }
+        }
+
+        GraphSnapshot {
+            revision: self.current_revision(),
+            sets,
+        }
+    }
+
+    /// read graph from file
+    ///
+    /// The revision of the graph is restored from the header of the savefile, or `0` if the
+    /// savefile has no header. A malformed savefile fails with the [`SavefileError`] of its first
+    /// malformed line.
+    pub async fn read_savefile(
+        readable: &mut (impl AsyncBufReadExt + Unpin),
+    ) -> Result<Self, SavefileError> {
+        let graph = Self::default();
+        let revision = graph.read_into(readable).await?;
+        graph.revision.store(revision, atomic::Ordering::Relaxed);
+        Ok(graph)
+    }
+
+    /// add the relations of a savefile to the graph
+    ///
+    /// Unlike [`RelationGraph::read_savefile`] the graph keeps its configuration, e.g. a graph
+    /// created with [`RelationGraph::with_wildcards`] `false` reads the relations of wildcard
+    /// sets as explicit relations. The revision of the graph is raised to the one of the
+    /// savefile if it is higher.
+    pub async fn extend_from_savefile(
+        &self,
+        readable: &mut (impl AsyncBufReadExt + Unpin),
+    ) -> Result<(), SavefileError> {
+        let revision = self.read_into(readable).await?;
+        self.revision.fetch_max(revision, atomic::Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// read graph from a file compressed with gzip, written by
+    /// [`RelationGraph::write_savefile_gz`]
+    ///
+    /// Unlike [`RelationGraph::read_savefile`] `readable` doesn't have to be buffered. A
+    /// corrupted compression fails with [`SavefileError::Io`].
+    pub async fn read_savefile_gz(
+        readable: &mut (impl AsyncRead + Unpin),
+    ) -> Result<Self, SavefileError> {
+        let mut decoder = BufReader::new(GzipDecoder::new(BufReader::new(readable)));
+        Self::read_savefile(&mut decoder).await
+    }
+
+    /// read graph from multiple savefiles and merge them
+    ///
+    /// The revision of the graph is the highest revision of all savefiles.
+    pub async fn read_savefiles(paths: &[impl AsRef<Path>]) -> Result<Self, SavefileError> {
+        let graph = Self::default();
+        let mut revision = 0;
+        for path in paths {
+            let mut reader = BufReader::new(File::open(path).await?);
+            revision = revision.max(graph.read_into(&mut reader).await?);
+        }
+        graph.revision.store(revision, atomic::Ordering::Relaxed);
+        Ok(graph)
+    }
+
+    /// import a graph exported with [`RelationGraph::export_json`]
+    ///
+    /// The revision of the graph is restored from `revision`, or `0` if it is missing. A malformed
+    /// relation fails with the [`JsonError`] of the first malformed relation.
+    pub async fn import_json(value: &serde_json::Value) -> Result<Self, JsonError> {
+        let relations = value
+            .get("relations")
+            .and_then(serde_json::Value::as_array)
+            .ok_or(JsonError::MalformedGraph)?;
+
+        let graph = Self::default();
+        for (index, relation) in relations.iter().enumerate() {
+            let (Some(src), Some(dst)) = (relation.get("src"), relation.get("dst")) else {
+                return Err(JsonError::MalformedRelation { index });
+            };
+            let deny = match relation.get("deny") {
+                None => false,
+                Some(deny) => deny
+                    .as_bool()
+                    .ok_or(JsonError::MalformedRelation { index })?,
+            };
+
+            let src = json_to_object_or_set(src)
+                .map_err(|source| JsonError::InvalidSource { index, source })?;
+            let dst = match json_to_object_or_set(dst) {
+                Ok(ObjectOrSet::Set(dst)) => Ok(dst.into_owned()),
+                Ok(ObjectOrSet::Object(_)) => Err(ParseError::MissingRelation),
+                Ok(ObjectOrSet::Intersection(_)) => {
+                    Err(ParseError::InvalidCharacter(INTERSECTION_SEPARATOR))
+                }
+                Err(err) => Err(err),
+            }
+            .map_err(|source| JsonError::InvalidDestination { index, source })?;
+
+            graph.insert_edge(src, &dst, deny).await;
+        }
+
+        let revision = value
+            .get("revision")
+            .and_then(serde_json::Value::as_u64)
+            .unwrap_or_default();
+        graph.revision.store(revision, atomic::Ordering::Relaxed);
+        Ok(graph)
+    }
+
+    /// adds the relations of a savefile to the graph and returns the revision of the savefile
+    async fn read_into(
+        &self,
+        readable: &mut (impl AsyncBufReadExt + Unpin),
+    ) -> Result<u64, SavefileError> {
+        let mut lines = readable.lines();
+        let mut revision = 0;
+        let mut vertex: Option<(String, String)> = None;
+        let mut line_number = 0;
+        while let Some(line) = lines.next_line().await? {
+            line_number += 1;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(header) = line.strip_prefix('#') {
+                for (key, value) in header.split(' ').filter_map(|x| x.split_once('=')) {
+                    if key == "revision" {
+                        revision = value.parse().unwrap_or_default();
+                    }
+                }
+            } else if let Some(section) = line.strip_prefix('[').and_then(|x| x.strip_suffix(']')) {
+                let object = match parse_savefile_ref(section) {
+                    Ok(ObjectOrSet::Object(obj)) => Ok(obj.into_owned()),
+                    Ok(_) => Err(ParseError::UnexpectedRelation),
+                    Err(err) => Err(err),
+                }
+                .map_err(|source| SavefileError::InvalidSection {
+                    line: line_number,
+                    source,
+                })?;
+                vertex = Some((object.namespace().to_string(), object.id().to_string()));
+            } else {
+                let Some((rel, arr)) = split_once_unescaped(line, '=').and_then(|(rel, arr)| {
+                    let arr = arr.trim().strip_prefix('[')?.strip_suffix(']')?;
+                    Some((rel.trim(), arr.trim()))
+                }) else {
+                    return Err(SavefileError::MalformedLine { line: line_number });
+                };
+                let Some(dst) = &vertex else {
+                    return Err(SavefileError::MissingSection { line: line_number });
+                };
+
+                let (rel, deny) = match rel.strip_prefix(DENY_PREFIX) {
+                    Some(rel) => (rel, true),
+                    None => (rel, false),
+                };
+                let rel = unescape(rel)
+                    .and_then(|rel| match rel.is_empty() {
+                        true => Err(ParseError::EmptyRelation),
+                        false => Ok(rel),
+                    })
+                    .map_err(|source| SavefileError::InvalidRelation {
+                        line: line_number,
+                        source,
+                    })?;
+                let dst = Set::new(dst.0.clone(), dst.1.clone(), rel);
+
+                for obj in split_unescaped(arr, ',')
+                    .map(str::trim)
+                    .filter(|x| !x.is_empty())
+                {
+                    // `self` and `self#rel` reference the object of the current section, all
+                    // other sources are qualified with their namespace. An object with the id
+                    // `self` is therefore never confused with the reference.
+                    let parsed = match obj.strip_prefix(SELF_REF) {
+                        Some(rest) if rest.is_empty() || rest.starts_with('#') => {
+                            parse_savefile_ref(&format!(
+                                "{}:{}{}",
+                                escape(dst.namespace()),
+                                escape(dst.id()),
+                                rest
+                            ))
+                        }
+                        _ => parse_savefile_ref(obj),
+                    };
+                    let src = parsed.map_err(|source| SavefileError::InvalidSource {
+                        line: line_number,
+                        src: obj.to_string(),
+                        source,
+                    })?;
+
+                    self.insert_edge(src, &dst, deny).await;
+                }
+            }
+        }
+        Ok(revision)
+    }
+}
+
+impl GraphSnapshot {
+    /// returns the revision of the graph when the snapshot was taken
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// write the snapshot to file in the format of [`RelationGraph::write_savefile`]
+    pub async fn write_savefile(
+        &self,
+        writeable: &mut (impl AsyncWriteExt + Unpin),
+    ) -> io::Result<()> {
+        self.write_namespaces(writeable, None).await
+    }
+
+    /// write the snapshot to file compressed with gzip like [`RelationGraph::write_savefile_gz`]
+    pub async fn write_savefile_gz(
+        &self,
+        writeable: &mut (impl AsyncWrite + Unpin),
+    ) -> io::Result<()> {
+        let mut encoder = GzipEncoder::new(writeable);
+        self.write_savefile(&mut encoder).await?;
+        encoder.shutdown().await
+    }
+
+    /// write the snapshot to the savefile at `path` like [`RelationGraph::save_to_path`]
+    pub async fn save_to_path(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.save_namespaces_to_path(path, None).await
+    }
+
+    /// [`GraphSnapshot::save_to_path`] of the sections of `namespace` or of all namespaces if it
+    /// is `None`
+    async fn save_namespaces_to_path(
+        &self,
+        path: impl AsRef<Path>,
+        namespace: Option<&str>,
+    ) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+
+        let mut file = File::create(&tmp_path).await?;
+        self.write_namespaces(&mut file, namespace).await?;
+        file.sync_all().await?;
+        drop(file);
+
+        fs::rename(&tmp_path, path).await
+    }
+
+    /// converts the snapshot to the JSON of [`RelationGraph::export_json`]
+    ///
+    /// An object is `{"namespace": "user", "id": "alice"}`, a set additionally has a `relation`
+    /// and an intersection its sorted `relations`. The relations are sorted by `dst` and then by
+    /// `src` like the savefile, the deny relations of a set follow its relations.
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut relations = vec![];
+        for set in &self.sets {
+            for (deny, srcs) in [(false, &set.srcs), (true, &set.denied_srcs)] {
+                let mut srcs = srcs.iter().collect::<Vec<_>>();
+                srcs.sort();
+                for src in srcs {
+                    let mut relation = serde_json::json!({
+                        "src": vertex_id_to_json(src),
+                        "dst": vertex_id_to_json(&set.id),
+                    });
+                    if deny {
+                        relation["deny"] = true.into();
+                    }
+                    relations.push(relation);
+                }
+            }
+        }
+
+        serde_json::json!({
+            "revision": self.revision,
+            "relations": relations,
+        })
+    }
+
+    /// writes the sections of all objects of `namespace` or of all namespaces if it is `None`
+    async fn write_namespaces(
+        &self,
+        writeable: &mut (impl AsyncWriteExt + Unpin),
+        namespace: Option<&str>,
+    ) -> io::Result<()> {
+        writeable
+            .write_all(
+                format!(
+                    "# rebacs-version={} revision={}\n",
+                    SAVEFILE_VERSION, self.revision
+                )
+                .as_bytes(),
+            )
+            .await?;
+
+        let mut current: (String, String) = (String::new(), String::new());
+        let mut lines: Vec<String> = vec![];
+        let sets = self.sets.iter().filter(|set| match namespace {
+            Some(namespace) => set.id.namespace == namespace,
+            None => true,
+        });
+        for set in sets {
+            if current != (set.id.namespace.clone(), set.id.id.clone()) {
+                write_section(writeable, &current, &lines).await?;
+                current = (set.id.namespace.clone(), set.id.id.clone());
+                lines.clear();
+            }
+            let rel = set.id.relation.as_deref().unwrap_or_default();
+
+            for (prefix, srcs) in [("", &set.srcs), (DENY_PREFIX, &set.denied_srcs)] {
+                let mut srcs = srcs
+                    .iter()
+                    .map(|src| savefile_src(src, &current))
+                    .collect::<Vec<_>>();
+                if !srcs.is_empty() {
+                    srcs.sort();
+                    lines.push(format!(
+                        "{}{} = [ {} ]\n",
+                        prefix,
+                        escape_relation(rel),
+                        srcs.join(", ")
+                    ));
+                }
+            }
+        }
+        write_section(writeable, &current, &lines).await?;
+        writeable.flush().await
+    }
+}
+
+impl Vertex {
+    fn new(id: VertexId) -> Self {
+        Self {
+            id,
+            edges_in: RwLock::new(HashSet::new()),
+            edges_out: RwLock::new(HashSet::new()),
+            denies_in: RwLock::new(HashSet::new()),
+            denies_out: RwLock::new(HashSet::new()),
+        }
+    }
+
+    /// checks if the vertex has neither relations nor deny relations
+    async fn is_isolated(&self) -> bool {
+        // every lock is released before the next one is taken
+        let edges_in = self.edges_in.read().await.is_empty();
+        let edges_out = self.edges_out.read().await.is_empty();
+        let denies_in = self.denies_in.read().await.is_empty();
+        let denies_out = self.denies_out.read().await.is_empty();
+        edges_in && edges_out && denies_in && denies_out
+    }
+
+    /// checks if the vertex has no deny relations and all its relations are wildcard connectors
+    ///
+    /// Without `wildcards` there are no connectors, every edge is a relation.
+    async fn has_only_wildcard_connectors(&self, wildcards: bool) -> bool {
+        let denies_in = self.denies_in.read().await.is_empty();
+        let denies_out = self.denies_out.read().await.is_empty();
+        if !denies_in || !denies_out {
+            return false;
+        }
+        let edges_in = self
+            .edges_in
+            .read()
+            .await
+            .iter()
+            .all(|src| wildcards && is_wildcard_connector(&src.id, &self.id));
+        let edges_out = self
+            .edges_out
+            .read()
+            .await
+            .iter()
+            .all(|dst| wildcards && is_wildcard_connector(&self.id, &dst.id));
+        edges_in && edges_out
+    }
+}
+
+impl Debug for Vertex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("vertex").field("id", &self.id).finish()
+    }
+}
+
+/// removes the edges of `vertex` to and from other verticies
+async fn detach(vertex: &Arc<Vertex>) -> Vec<Arc<Vertex>> {
+    let srcs = vertex.edges_in.write().await.drain().collect::<Vec<_>>();
+    for src in &srcs {
+        src.edges_out.write().await.remove(vertex);
+    }
+    let dsts = vertex.edges_out.write().await.drain().collect::<Vec<_>>();
+    for dst in &dsts {
+        dst.edges_in.write().await.remove(vertex);
+    }
+    srcs.into_iter().chain(dsts).collect()
+}
+
+/// removes all edges of `vertex` and adds the relations they represent as (`src`, `dst`, `deny`)
+/// to `relations` and the verticies on the other end of the edges to `neighbors`
+///
+/// The edges between an intersection and its sets and, if `wildcards` is set, the edges to the
+/// wildcards are no relations. An edge between two verticies that are both drained is only
+/// added once, by the first of them.
+async fn drain_relations(
+    vertex: &Arc<Vertex>,
+    relations: &mut Vec<(VertexId, VertexId, bool)>,
+    neighbors: &mut Vec<Arc<Vertex>>,
+    wildcards: bool,
+) {
+    let is_connector =
+        |src: &VertexId, dst: &VertexId| wildcards && is_wildcard_connector(src, dst);
+    let dsts = vertex.edges_out.write().await.drain().collect::<Vec<_>>();
+    for dst in dsts {
+        dst.edges_in.write().await.remove(vertex);
+        if !is_connector(&vertex.id, &dst.id) && !dst.id.is_intersection() {
+            relations.push((vertex.id.clone(), dst.id.clone(), false));
+        }
+        neighbors.push(dst);
+    }
+    let srcs = vertex.edges_in.write().await.drain().collect::<Vec<_>>();
+    for src in srcs {
+        src.edges_out.write().await.remove(vertex);
+        if !is_connector(&src.id, &vertex.id) && !vertex.id.is_intersection() {
+            relations.push((src.id.clone(), vertex.id.clone(), false));
+        }
+        neighbors.push(src);
+    }
+    let dsts = vertex.denies_out.write().await.drain().collect::<Vec<_>>();
+    for dst in dsts {
+        dst.denies_in.write().await.remove(vertex);
+        relations.push((vertex.id.clone(), dst.id.clone(), true));
+        neighbors.push(dst);
+    }
+    let srcs = vertex.denies_in.write().await.drain().collect::<Vec<_>>();
+    for src in srcs {
+        src.denies_out.write().await.remove(vertex);
+        relations.push((src.id.clone(), vertex.id.clone(), true));
+        neighbors.push(src);
+    }
+}
+
+/// detaches the intersection `vertex` from its sets if it is no longer the source of a relation
+///
+/// An intersection without any relations is only connected to its sets, which are removed if
+/// they are unused afterwards.
+async fn remove_unused_intersection(
+    verticies: &mut BTreeSet<Arc<Vertex>>,
+    vertex: &Arc<Vertex>,
+    wildcards: bool,
+) {
+    let unused = vertex.edges_out.read().await.is_empty();
+    let unused = unused && vertex.denies_out.read().await.is_empty();
+    if vertex.id.is_intersection() && unused {
+        let sets = vertex.edges_in.write().await.drain().collect::<Vec<_>>();
+        for set in sets {
+            set.edges_out.write().await.remove(vertex);
+            remove_if_unused(verticies, &set, wildcards).await;
+        }
+    }
+}
+
+/// removes `vertex` if it no longer takes part in any relation
+///
+/// A vertex that is only connected to wildcards is detached from them, and the wildcards are
+/// removed as well once they have no edges left, so [`RelationGraph::remove`] leaves nothing
+/// behind for [`RelationGraph::compact`]. Without `wildcards` every edge is a relation.
+async fn remove_if_unused(
+    verticies: &mut BTreeSet<Arc<Vertex>>,
+    vertex: &Arc<Vertex>,
+    wildcards: bool,
+) {
+    if vertex.id.id == WILDCARD_ID || !vertex.has_only_wildcard_connectors(wildcards).await {
+        if vertex.is_isolated().await {
+            verticies.remove(&vertex.id);
+        }
+        return;
+    }
+
+    for wildcard in detach(vertex).await {
+        if wildcard.is_isolated().await {
+            verticies.remove(&wildcard.id);
         }
     }
+    verticies.remove(&vertex.id);
 }
 
-impl RelationGraph {
-    /// create a new relation between from a [`Object`] or [`Set`] to a [`Set`]
-    pub async fn insert(&self, src: impl Into<ObjectOrSet<'_>>, dst: &Set) {
-        let src: ObjectOrSet<'_> = src.into();
-        let mut verticies = self.verticies.write().await;
-
-        let mut get_or_create = |vertex: &VertexId| match verticies.get(vertex) {
-            Some(vertex) => vertex.clone(),
-            None => {
-                let vertex = Arc::new(Vertex {
-                    id: vertex.clone(),
-                    edges_out: RwLock::new(HashSet::new()),
-                    edges_in: RwLock::new(HashSet::new()),
-                });
-                verticies.insert(vertex.clone());
-                vertex
-            }
-        };
-
-        let src_without_relation = src.relation().is_none();
+/// formats the source of a relation in the section of the object `current`
+fn savefile_src(src: &VertexId, current: &(String, String)) -> String {
+    let obj = if src.namespace == current.0 && src.id == current.1 {
+        SELF_REF.to_string()
+    } else {
+        format!("{}:{}", escape(&src.namespace), escape(&src.id))
+    };
+
+    if let Some(rel) = &src.relation {
+        format!("{}#{}", &obj, escape_relation(rel))
+    } else {
+        obj
+    }
+}
 
-        let src_wildcard: ObjectOrSet = (src.namespace(), WILDCARD_ID, src.relation()).into();
-        let src_wildcard = get_or_create(src_wildcard.vertex_id());
-        let src_vertex = get_or_create(src.vertex_id());
+/// formats `vertex` as quoted Graphviz id for [`RelationGraph::to_dot`]
+fn dot_id(vertex: &VertexId) -> String {
+    let label = match &vertex.relation {
+        Some(relation) => format!("{}:{}#{}", vertex.namespace, vertex.id, relation),
+        None => format!("{}:{}", vertex.namespace, vertex.id),
+    };
+    format!("\"{}\"", label.replace('\\', "\\\\").replace('"', "\\\""))
+}
 
-        let dst_wildcard: Set = (dst.namespace(), WILDCARD_ID, dst.relation()).into();
-        let dst_wildcard = get_or_create(dst_wildcard.vertex_id());
-        let dst_vertex = get_or_create(dst.vertex_id());
+/// one direction of the bidirectional search of [`RelationGraph::meet`]
+#[derive(Default)]
+struct Frontier {
+    /// the visited verticies with their distance to the start of the direction
+    visited: FxHashMap<Arc<Vertex>, u32>,
+    /// the verticies at the largest distance, which are expanded next
+    current: Vec<Arc<Vertex>>,
+    /// distance of the `current` verticies
+    depth: u32,
+    /// whether an intersection was visited
+    intersections: bool,
+    /// whether a vertex of the other direction was reached beyond the limit
+    limited: bool,
+    /// cached result of [`Frontier::cost`]
+    cost: Option<usize>,
+}
 
-        if src_without_relation && src_vertex.id.id != WILDCARD_ID {
-            add_edge(src_vertex.clone(), src_wildcard).await;
-        } else if !src_without_relation {
-            add_edge(src_wildcard, src_vertex.clone()).await;
+impl Frontier {
+    fn visit(&mut self, vertex: Arc<Vertex>, depth: u32) {
+        if self.visited.contains_key(&vertex) {
+            return;
         }
-
-        add_edge(dst_wildcard, dst_vertex.clone()).await;
-        add_edge(src_vertex, dst_vertex).await;
+        self.intersections |= vertex.id.is_intersection();
+        self.cost = None;
+        self.depth = depth;
+        self.visited.insert(vertex.clone(), depth);
+        self.current.push(vertex);
     }
 
-    /// remove a relation
-    pub async fn remove(&self, src: impl Into<ObjectOrSet<'_>>, dst: &Set) {
-        let src: ObjectOrSet<'_> = src.into();
-        let mut verticies = self.verticies.write().await;
-
-        let src = verticies.get(src.vertex_id()).cloned();
-        let dst = verticies.get(dst.vertex_id()).cloned();
+    /// returns the number of edges the next [`Frontier::expand`] follows
+    async fn cost(&mut self, outgoing: bool) -> usize {
+        if let Some(cost) = self.cost {
+            return cost;
+        }
+        let mut cost = 0;
+        for vertex in &self.current {
+            cost += match outgoing {
+                true => vertex.edges_out.read().await.len(),
+                false => vertex.edges_in.read().await.len(),
+            };
+        }
+        self.cost = Some(cost);
+        cost
+    }
 
-        if let (Some(src), Some(dst)) = (src, dst) {
-            src.edges_out.write().await.retain(|x| x != &dst);
-            dst.edges_in.write().await.retain(|x| x != &src);
+    fn distance(&self, vertex: &Arc<Vertex>) -> Option<u32> {
+        self.visited.get(vertex).copied()
+    }
 
-            if src.edges_in.read().await.is_empty() && src.edges_out.read().await.is_empty() {
-                verticies.remove(&src.id);
-            }
-            if dst.edges_in.read().await.is_empty() && dst.edges_out.read().await.is_empty() {
-                verticies.remove(&dst.id);
+    /// visits the neighbors of the `current` verticies, following the relations if `outgoing`
+    /// and the members otherwise, and returns whether `other` visited one of them within
+    /// the distance accepted by `within`
+    async fn expand(
+        &mut self,
+        other: &Frontier,
+        outgoing: bool,
+        within: impl Fn(u32) -> bool,
+    ) -> bool {
+        let depth = self.depth + 1;
+        let mut met = false;
+        self.cost = None;
+        for vertex in std::mem::take(&mut self.current) {
+            let edges = match outgoing {
+                true => vertex.edges_out.read().await,
+                false => vertex.edges_in.read().await,
+            };
+            for neighbor in edges.iter() {
+                if self.visited.contains_key(neighbor) {
+                    continue;
+                }
+                if let Some(distance) = other.distance(neighbor) {
+                    match within(distance + depth) {
+                        true => met = true,
+                        false => self.limited = true,
+                    }
+                }
+                // the members of an object are the objects connected to its wildcard, which are
+                // never reached from `src`
+                if outgoing || neighbor.id.relation.is_some() {
+                    self.visit(neighbor.clone(), depth);
+                }
             }
         }
+        self.depth = depth;
+        met
     }
+}
 
-    /// checks if there is a *direct* relation between `src` and `dst`
-    pub async fn has(&self, src: impl Into<ObjectOrSet<'_>>, dst: &Set) -> bool {
-        let src: ObjectOrSet<'_> = src.into();
-        let (src, dst) = {
-            let verticies = self.verticies.read().await;
-            (
-                verticies.get(src.vertex_id()).cloned(),
-                verticies.get(dst.vertex_id()).cloned(),
-            )
-        };
-
-        if let (Some(src), Some(dst)) = (src, dst) {
-            src.edges_out.read().await.contains(&dst)
+/// escapes the characters of a namespace, id or relation that have a meaning in the savefile
+///
+/// [`RESERVED_CHARS`], [`DENY_PREFIX`] and [`ESCAPE_CHAR`] are prefixed with [`ESCAPE_CHAR`],
+/// whitespace and control characters are written as `\u{hex}`, so every string survives the
+/// trimming and splitting of [`RelationGraph::read_savefile`].
+fn escape(part: &str) -> String {
+    let mut escaped = String::with_capacity(part.len());
+    for c in part.chars() {
+        if c == ESCAPE_CHAR || RESERVED_CHARS.contains(&c) || DENY_PREFIX.starts_with(c) {
+            escaped.push(ESCAPE_CHAR);
+            escaped.push(c);
+        } else if c.is_whitespace() || c.is_control() {
+            escaped.push_str(&format!("{}u{{{:x}}}", ESCAPE_CHAR, c as u32));
         } else {
-            false
+            escaped.push(c);
         }
     }
+    escaped
+}
 
-    /// checks if there is a *path* between src and dst using [BFS](https://en.wikipedia.org/wiki/Breadth-first_search)
-    ///
-    /// # Arguments
-    /// * `src` - start of the path
-    /// * `dst` - end of the path
-    /// * `limit` - optional maximum search depth of the search before returing false
-    pub async fn check<'a>(
-        &self,
-        src: impl Into<ObjectOrSet<'_>>,
-        dst: &Set,
-        limit: Option<u32>,
-    ) -> bool {
-        let src: ObjectOrSet<'_> = src.into();
-        let mut distance = 1;
+/// escapes the relations of a set or an [`Intersection`], keeping their separator
+fn escape_relation(relation: &str) -> String {
+    relation
+        .split(INTERSECTION_SEPARATOR)
+        .map(escape)
+        .collect::<Vec<_>>()
+        .join(&INTERSECTION_SEPARATOR.to_string())
+}
 
-        let mut neighbors: Vec<Arc<Vertex>> = if let Some(src) =
-            self.verticies.read().await.get(src.vertex_id())
-        {
-            src.edges_out.read().await.iter().cloned().collect()
-        } else {
-            let wildcard_src: Object = (src.namespace(), WILDCARD_ID).into();
-            if let Some(wildcard_src) = self.verticies.read().await.get(wildcard_src.vertex_id()) {
-                wildcard_src
-                    .edges_out
-                    .read()
-                    .await
-                    .iter()
-                    .cloned()
-                    .collect()
-            } else {
-                return false;
+/// reverts [`escape`]
+fn unescape(part: &str) -> Result<String, ParseError> {
+    let mut unescaped = String::with_capacity(part.len());
+    let mut chars = part.chars();
+    while let Some(c) = chars.next() {
+        if c != ESCAPE_CHAR {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('u') => {
+                let rest = chars.as_str();
+                let (hex, rest) = rest
+                    .strip_prefix('{')
+                    .and_then(|x| x.split_once('}'))
+                    .ok_or(ParseError::InvalidEscape)?;
+                let c = u32::from_str_radix(hex, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or(ParseError::InvalidEscape)?;
+                unescaped.push(c);
+                chars = rest.chars();
             }
-        };
-
-        let mut visited: HashSet<Arc<Vertex>> = HashSet::new();
+            Some(c) => unescaped.push(c),
+            None => return Err(ParseError::InvalidEscape),
+        }
+    }
+    Ok(unescaped)
+}
 
-        while !neighbors.is_empty() {
-            if let Some(limit) = limit {
-                if distance > limit {
-                    return false;
-                }
+/// splits `value` at every `separator` that isn't escaped
+fn split_unescaped(value: &str, separator: char) -> impl Iterator<Item = &str> {
+    let mut rest = Some(value);
+    std::iter::from_fn(move || {
+        let value = rest?;
+        match split_once_unescaped(value, separator) {
+            Some((part, tail)) => {
+                rest = Some(tail);
+                Some(part)
             }
+            None => rest.take(),
+        }
+    })
+}
 
-            let mut next_neighbors = vec![];
-            for neighbor in neighbors {
-                if distance > 1 && visited.contains(&neighbor) {
-                    continue;
-                }
-
-                //check if the current vertex is the dst vertex or the wildcard vertex for the dst
-                //namespace. Without checking the wildcard vertex, not initialized dsts that should
-                //be affected by the wildcard wouldn't be found.
-                if &neighbor.id == dst
-                    || (neighbor.id.namespace == dst.namespace()
-                        && neighbor.id.id == WILDCARD_ID
-                        && neighbor.id.relation.as_deref() == Some(dst.relation()))
-                {
-                    return true;
-                }
-
-                let mut vertex_neighbors =
-                    neighbor.edges_out.read().await.iter().cloned().collect();
-                next_neighbors.append(&mut vertex_neighbors);
-
-                visited.insert(neighbor);
-            }
-            neighbors = next_neighbors;
-            distance += 1;
+/// splits `value` at the first `separator` that isn't escaped
+fn split_once_unescaped(value: &str, separator: char) -> Option<(&str, &str)> {
+    let mut escaped = false;
+    for (i, c) in value.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == ESCAPE_CHAR {
+            escaped = true;
+        } else if c == separator {
+            return Some((&value[..i], &value[i + c.len_utf8()..]));
         }
-        false
     }
+    None
+}
 
-    /// get all objects that are related to dst with the relation path
-    pub async fn expand(&self, dst: &Set) -> Vec<(Object, Vec<Set>)> {
-        let start_vertex = {
-            let verticies = self.verticies.read().await;
-            match verticies.get(dst.vertex_id()) {
-                Some(v) => v.clone(),
-                None => {
-                    let wildcard_dst: Set = (dst.namespace(), WILDCARD_ID, dst.relation()).into();
-
-                    match verticies.get(wildcard_dst.vertex_id()) {
-                        Some(v) => v.clone(),
-                        None => return vec![],
-                    }
-                }
-            }
-        };
+/// parses an escaped source or section of the savefile
+///
+/// Unlike the [`TryFrom<&str>`] implementations the parts are unescaped instead of validated, so
+/// every identifier written by [`RelationGraph::write_savefile`] is read back unchanged.
+fn parse_savefile_ref(value: &str) -> Result<ObjectOrSet<'static>, ParseError> {
+    let (namespace, rest) = split_once_unescaped(value, ':').ok_or(ParseError::MissingSeparator)?;
+    let (id, relation) = match split_once_unescaped(rest, '#') {
+        Some((id, relation)) => (id, Some(relation)),
+        None => (rest, None),
+    };
+
+    let namespace = unescape(namespace)?;
+    if namespace.is_empty() {
+        return Err(ParseError::EmptyNamespace);
+    }
+    let id = unescape(id)?;
+    if id.is_empty() {
+        return Err(ParseError::EmptyId);
+    }
+    let Some(relation) = relation else {
+        return Ok(Object::new(namespace, id).into());
+    };
+
+    let mut relations = split_unescaped(relation, INTERSECTION_SEPARATOR)
+        .map(unescape)
+        .collect::<Result<Vec<_>, _>>()?;
+    if relations.iter().any(String::is_empty) {
+        return Err(ParseError::EmptyRelation);
+    }
+    match relations.len() {
+        1 => Ok(Set::new(namespace, id, relations.remove(0)).into()),
+        _ => Ok(Intersection::new(namespace, id, relations).into()),
+    }
+}
 
-        let mut visited: HashSet<Arc<Vertex>> = HashSet::new();
+/// reconstructs the sets of a path found by [`RelationGraph::search`]
+///
+/// The path ends at `matched`, which was reached from `predecessor`. The matched [`Set`] `dst`
+/// itself, objects and intersections are not part of the returned sets.
+fn search_path(
+    visited: &FxHashMap<Arc<Vertex>, Option<Arc<Vertex>>>,
+    predecessor: Option<Arc<Vertex>>,
+    matched: &Arc<Vertex>,
+    dst: &Set,
+) -> Vec<Set> {
+    let mut path = vec![matched.clone()];
+    let mut current = predecessor;
+    while let Some(vertex) = current {
+        current = visited.get(&vertex).cloned().flatten();
+        path.push(vertex);
+    }
+    path.reverse();
 
-        let mut neighbors: Vec<(Arc<Vertex>, Vec<Arc<Vertex>>)> = start_vertex
-            .edges_in
-            .read()
-            .await
-            .iter()
-            .map(|v| (v.clone(), vec![start_vertex.clone()]))
-            .collect();
+    path.into_iter()
+        .filter(|v| v.id.relation.is_some() && !v.id.is_intersection() && v.id != dst.0)
+        .map(|v| Set(v.id.clone()))
+        .collect()
+}
 
-        visited.insert(start_vertex);
+/// builds the [`ExpandNode`] of `vertex` from the sources of the reachable sets, the sets in
+/// `expanded` already have a node with children and are only referenced
+fn expand_tree_node(
+    vertex: &Arc<Vertex>,
+    children: &FxHashMap<Arc<Vertex>, Vec<Arc<Vertex>>>,
+    expanded: &mut FxHashSet<Arc<Vertex>>,
+) -> ExpandNode {
+    let value = vertex.id.clone().into_object_or_set();
+    let Some(srcs) = children.get(vertex) else {
+        return ExpandNode {
+            value,
+            children: vec![],
+            reference: false,
+        };
+    };
+    if !expanded.insert(vertex.clone()) {
+        return ExpandNode {
+            value,
+            children: vec![],
+            reference: true,
+        };
+    }
 
-        let mut expanded_verticies: Vec<(Arc<Vertex>, Vec<Arc<Vertex>>)> = vec![];
+    let children = srcs
+        .iter()
+        .filter(|src| {
+            !is_set_wildcard_connector(&src.id, &vertex.id)
+                || children.get(*src).is_some_and(|srcs| !srcs.is_empty())
+        })
+        .map(|src| expand_tree_node(src, children, expanded))
+        .collect();
 
-        while !neighbors.is_empty() {
-            let mut next_neighbors = vec![];
-            for (neighbor, mut neighbor_path) in neighbors {
-                if visited.contains(&neighbor) {
-                    continue;
-                }
+    ExpandNode {
+        value,
+        children,
+        reference: false,
+    }
+}
 
-                if neighbor.id.relation.is_none() {
-                    expanded_verticies.push((neighbor, neighbor_path));
-                    continue;
-                }
+/// returns the sources of the relations of `vertex` ordered by their id
+async fn sorted_edges_in(vertex: &Vertex) -> Vec<Arc<Vertex>> {
+    let mut srcs: Vec<Arc<Vertex>> = vertex.edges_in.read().await.iter().cloned().collect();
+    srcs.sort();
+    srcs
+}
 
-                neighbor_path.push(neighbor.clone());
+/// converts `id` to the JSON of [`GraphSnapshot::to_json`]
+fn vertex_id_to_json(id: &VertexId) -> serde_json::Value {
+    let mut value = serde_json::json!({
+        "namespace": id.namespace,
+        "id": id.id,
+    });
+    match &id.relation {
+        Some(relation) if id.is_intersection() => {
+            value["relations"] = relation
+                .split(INTERSECTION_SEPARATOR)
+                .collect::<Vec<_>>()
+                .into();
+        }
+        Some(relation) => value["relation"] = relation.as_str().into(),
+        None => {}
+    }
+    value
+}
 
-                next_neighbors.append(
-                    &mut neighbor
-                        .edges_in
-                        .read()
-                        .await
-                        .iter()
-                        .map(|v| (v.clone(), neighbor_path.clone()))
-                        .collect(),
-                );
+/// parses the JSON of [`vertex_id_to_json`], validating it like [`Object::try_new`]
+fn json_to_object_or_set(value: &serde_json::Value) -> Result<ObjectOrSet<'static>, ParseError> {
+    let field = |key: &str| value.get(key).and_then(serde_json::Value::as_str);
+    let namespace = field("namespace")
+        .ok_or(ParseError::EmptyNamespace)?
+        .to_string();
+    let id = field("id").ok_or(ParseError::EmptyId)?.to_string();
+
+    if let Some(relations) = value.get("relations") {
+        let relations = relations
+            .as_array()
+            .ok_or(ParseError::EmptyRelation)?
+            .iter()
+            .map(|relation| relation.as_str().map(str::to_string))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(ParseError::EmptyRelation)?;
+        return Intersection::try_new(namespace, id, relations)
+            .map(|x| ObjectOrSet::Intersection(Cow::Owned(x)));
+    }
 
-                visited.insert(neighbor);
-            }
-            neighbors = next_neighbors;
+    match value.get("relation") {
+        None => Object::try_new(namespace, id).map(|x| ObjectOrSet::Object(Cow::Owned(x))),
+        Some(relation) => {
+            let relation = relation.as_str().ok_or(ParseError::EmptyRelation)?;
+            Set::try_new(namespace, id, relation.to_string())
+                .map(|x| ObjectOrSet::Set(Cow::Owned(x)))
         }
+    }
+}
 
-        expanded_verticies
-            .into_iter()
-            .map(|(v, path)| {
-                (
-                    Object(v.id.clone()),
-                    path.into_iter().map(|w| Set(w.id.clone())).collect(),
-                )
-            })
-            .collect()
+/// writes the section of an object to a savefile, sections without any relations are skipped
+async fn write_section(
+    writeable: &mut (impl AsyncWriteExt + Unpin),
+    object: &(String, String),
+    lines: &[String],
+) -> io::Result<()> {
+    if lines.is_empty() {
+        return Ok(());
     }
+    writeable
+        .write_all(format!("\n[{}:{}]\n", escape(&object.0), escape(&object.1)).as_bytes())
+        .await?;
+    for line in lines {
+        writeable.write_all(line.as_bytes()).await?;
+    }
+    Ok(())
+}
 
-    /// write graph to file
-    pub async fn write_savefile(&self, writeable: &mut (impl AsyncWriteExt + Unpin)) {
-        let mut current: (String, String) = (String::new(), String::new());
-        for vertex in self.verticies.read().await.iter() {
-            if current != (vertex.id.namespace.clone(), vertex.id.id.clone()) {
-                current = (vertex.id.namespace.clone(), vertex.id.id.clone());
-                writeable.write_all("\n".as_bytes()).await.unwrap();
-                writeable
-                    .write_all(format!("[{}:{}]\n", &current.0, &current.1).as_bytes())
-                    .await
-                    .unwrap();
-            }
+/// returns the verticies of `namespace` in order, using a range query instead of a full scan
+fn namespace_verticies<'a>(
+    verticies: &'a BTreeSet<Arc<Vertex>>,
+    namespace: &'a str,
+) -> impl Iterator<Item = &'a Arc<Vertex>> {
+    let start = VertexId {
+        namespace: namespace.to_string(),
+        id: String::new(),
+        relation: None,
+    };
+    verticies
+        .range::<VertexId, _>(start..)
+        .take_while(move |x| x.id.namespace == namespace)
+}
 
-            let srcs = vertex
-                .edges_in
-                .read()
-                .await
-                .iter()
-                .filter(|x| x.id.id != WILDCARD_ID)
-                .map(|src| {
-                    let obj = if src.id.namespace == current.0 && src.id.id == current.1 {
-                        "self".to_string()
-                    } else {
-                        format!("{}:{}", &src.id.namespace, &src.id.id)
-                    };
+/// returns the vertex of the object (`namespace`, `id`) followed by the verticies of its sets
+fn object_verticies<'a>(
+    verticies: &'a BTreeSet<Arc<Vertex>>,
+    namespace: &'a str,
+    id: &'a str,
+) -> impl Iterator<Item = &'a Arc<Vertex>> {
+    let start = VertexId {
+        namespace: namespace.to_string(),
+        id: id.to_string(),
+        relation: None,
+    };
+    verticies
+        .range::<VertexId, _>(start..)
+        .take_while(move |x| x.id.namespace == namespace && x.id.id == id)
+}
 
-                    if let Some(rel) = &src.id.relation {
-                        format!("{}#{}", &obj, &rel)
-                    } else {
-                        obj
-                    }
-                })
-                .reduce(|acc, x| acc + ", " + &x)
-                .unwrap_or_default();
+/// rejects whitespace, control characters and [`RESERVED_CHARS`] in a namespace, id or relation
+fn validate_part(part: &str) -> Result<(), ParseError> {
+    match part
+        .chars()
+        .find(|c| c.is_whitespace() || c.is_control() || RESERVED_CHARS.contains(c))
+    {
+        Some(c) => Err(ParseError::InvalidCharacter(c)),
+        None => Ok(()),
+    }
+}
 
-            if let Some(rel) = &vertex.id.relation {
-                writeable
-                    .write_all(format!("{} = [ {} ]\n", &rel, &srcs).as_bytes())
-                    .await
-                    .unwrap();
-            }
-        }
+/// returns the [`CONTAINS_RELATION`] sets of `parent` and `child`
+fn contains_sets(parent: &Object, child: &Object) -> (Set, Set) {
+    (
+        (parent.namespace(), parent.id(), CONTAINS_RELATION).into(),
+        (child.namespace(), child.id(), CONTAINS_RELATION).into(),
+    )
+}
+
+/// rejects empty relations and relations that can't be stored in the savefile
+fn validate_relation(relation: &str) -> Result<(), ParseError> {
+    if relation.is_empty() {
+        return Err(ParseError::EmptyRelation);
     }
-    /// read graph from file
-    pub async fn read_savefile(readable: &mut (impl AsyncBufReadExt + Unpin)) -> Self {
-        let mut lines = readable.lines();
-        let graph = Self::default();
-        let mut vertex: Option<(String, String)> = None;
-        while let Ok(Some(line)) = lines.next_line().await {
-            if line.starts_with('[') && line.ends_with(']') {
-                let line = &mut line[1..line.len() - 1].split(':');
-                let namespace = line.next().unwrap();
-                let id = line.next().unwrap();
-                vertex = Some((namespace.to_string(), id.to_string()));
-            } else if line.contains('=') && line.contains('[') && line.contains(']') {
-                if let Some(dst) = &vertex {
-                    let equals_pos = line.find('=').unwrap();
-                    let arr_start = line.find('[').unwrap();
-                    let arr_stop = line.find(']').unwrap();
-
-                    let rel = line[..equals_pos].trim();
-                    let arr = line[arr_start + 1..arr_stop].trim().split(", ");
-
-                    for obj in arr {
-                        let src: ObjectOrSet = if obj.contains('#') {
-                            let sep_1 = obj.find(':');
-                            let sep_2 = obj.find('#').unwrap();
-
-                            let (namespace, id) = if let Some(sep_1) = sep_1 {
-                                (&obj[..sep_1], &obj[sep_1 + 1..sep_2])
-                            } else {
-                                (dst.0.as_str(), dst.1.as_str())
-                            };
-
-                            let rel = &obj[sep_2 + 1..];
-
-                            ObjectOrSet::Set(Cow::Owned((namespace, id, rel).into()))
-                        } else {
-                            let sep_1 = obj.find(':');
-
-                            let (namespace, id) = if let Some(sep_1) = sep_1 {
-                                (&obj[..sep_1], &obj[sep_1 + 1..])
-                            } else {
-                                (dst.0.as_str(), dst.1.as_str())
-                            };
-                            ObjectOrSet::Object(Cow::Owned((namespace, id).into()))
-                        };
-
-                        graph
-                            .insert(src, &(dst.0.as_str(), dst.1.as_str(), rel).into())
-                            .await;
-                    }
-                }
-            }
-        }
-        graph
+    if relation.starts_with(DENY_PREFIX) {
+        return Err(ParseError::InvalidCharacter('!'));
     }
+    validate_part(relation)
 }
 
-impl Debug for Vertex {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("vertex").field("id", &self.id).finish()
-    }
+/// checks if `src -> dst` is an edge [`RelationGraph::insert`] creates between a vertex and its
+/// wildcard
+fn is_wildcard_connector(src: &VertexId, dst: &VertexId) -> bool {
+    is_set_wildcard_connector(src, dst) || is_object_wildcard_connector(src, dst)
 }
 
-async fn add_edge(from: Arc<Vertex>, to: Arc<Vertex>) {
-    if !from.edges_out.read().await.contains(&to) {
-        from.edges_out.write().await.insert(to.clone());
-    }
-    if !to.edges_in.read().await.contains(&from) {
-        to.edges_in.write().await.insert(from);
+/// checks if `src -> dst` is the edge [`RelationGraph::insert`] creates from the wildcard of a
+/// set to the set itself (e.g. (`file`, `*`, `read`) -> (`file`, `foo`, `read`))
+fn is_set_wildcard_connector(src: &VertexId, dst: &VertexId) -> bool {
+    src.id == WILDCARD_ID
+        && dst.id != WILDCARD_ID
+        && src.namespace == dst.namespace
+        && src.relation.is_some()
+        && src.relation == dst.relation
+}
+
+/// checks if `src -> dst` is the edge [`RelationGraph::insert`] creates from an object to the
+/// wildcard of its namespace (e.g. (`user`, `alice`) -> (`user`, `*`))
+fn is_object_wildcard_connector(src: &VertexId, dst: &VertexId) -> bool {
+    src.id != WILDCARD_ID
+        && dst.id == WILDCARD_ID
+        && src.namespace == dst.namespace
+        && src.relation.is_none()
+        && dst.relation.is_none()
+}
+
+/// adds an edge between `from` and `to`, returns `false` if the edge already existed
+async fn add_edge(from: Arc<Vertex>, to: Arc<Vertex>) -> bool {
+    let (mut edges_out, mut edges_in) =
+        write_ordered(&from, &from.edges_out, &to, &to.edges_in).await;
+    let created = edges_out.insert(to.clone());
+    edges_in.insert(from.clone());
+    created
+}
+
+/// adds a deny edge between `from` and `to`, returns `false` if the edge already existed
+async fn add_deny_edge(from: Arc<Vertex>, to: Arc<Vertex>) -> bool {
+    let (mut denies_out, mut denies_in) =
+        write_ordered(&from, &from.denies_out, &to, &to.denies_in).await;
+    let created = denies_out.insert(to.clone());
+    denies_in.insert(from.clone());
+    created
+}
+
+/// write-locks the outgoing set of `from` and the incoming set of `to` following the lock order
+/// documented on [`Vertex`]
+async fn write_ordered<'a>(
+    from: &Vertex,
+    out: &'a RwLock<HashSet<Arc<Vertex>>>,
+    to: &Vertex,
+    inc: &'a RwLock<HashSet<Arc<Vertex>>>,
+) -> (
+    RwLockWriteGuard<'a, HashSet<Arc<Vertex>>>,
+    RwLockWriteGuard<'a, HashSet<Arc<Vertex>>>,
+) {
+    if from.id < to.id {
+        let out = out.write().await;
+        (out, inc.write().await)
+    } else {
+        let inc = inc.write().await;
+        (out.write().await, inc)
     }
 }
 
@@ -548,18 +3341,30 @@ impl From<(String, String, String)> for Set {
     }
 }
 
+/// a relation containing `&` creates an [`Intersection`]
 impl From<(&str, &str, Option<&str>)> for ObjectOrSet<'_> {
     fn from(value: (&str, &str, Option<&str>)) -> Self {
-        match value.2 {
-            Some(r) => Self::Set(Cow::Owned((value.0, value.1, r).into())),
-            None => Self::Object(Cow::Owned((value.0, value.1).into())),
-        }
+        (
+            value.0.to_string(),
+            value.1.to_string(),
+            value.2.map(str::to_string),
+        )
+            .into()
     }
 }
 
+/// a relation containing `&` creates an [`Intersection`]
 impl From<(String, String, Option<String>)> for ObjectOrSet<'_> {
     fn from(value: (String, String, Option<String>)) -> Self {
         match value.2 {
+            Some(r) if r.contains(INTERSECTION_SEPARATOR) => {
+                let relations = r.split(INTERSECTION_SEPARATOR).map(str::to_string);
+                Self::Intersection(Cow::Owned(Intersection::new(
+                    value.0,
+                    value.1,
+                    relations.collect(),
+                )))
+            }
             Some(r) => Self::Set(Cow::Owned((value.0, value.1, r).into())),
             None => Self::Object(Cow::Owned((value.0, value.1).into())),
         }
@@ -600,6 +3405,18 @@ impl From<Object> for ObjectOrSet<'_> {
     }
 }
 
+impl From<Intersection> for ObjectOrSet<'_> {
+    fn from(value: Intersection) -> Self {
+        Self::Intersection(Cow::Owned(value))
+    }
+}
+
+impl<'a> From<&'a Intersection> for ObjectOrSet<'a> {
+    fn from(value: &'a Intersection) -> Self {
+        Self::Intersection(Cow::Borrowed(value))
+    }
+}
+
 impl<'a> From<&'a Set> for ObjectOrSet<'a> {
     fn from(value: &'a Set) -> Self {
         Self::Set(Cow::Borrowed(value))
@@ -616,6 +3433,71 @@ impl<'a> From<&'a ObjectOrSet<'a>> for ObjectOrSet<'a> {
         match value {
             Self::Object(obj) => Self::Object(Cow::Borrowed(obj.borrow())),
             Self::Set(set) => Self::Set(Cow::Borrowed(set.borrow())),
+            Self::Intersection(intersection) => {
+                Self::Intersection(Cow::Borrowed(intersection.borrow()))
+            }
+        }
+    }
+}
+
+impl TryFrom<&str> for ObjectOrSet<'_> {
+    type Error = ParseError;
+
+    /// parses `namespace:id` into an [`Object`], `namespace:id#relation` into a [`Set`] and
+    /// `namespace:id#relation&relation` into an [`Intersection`]
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let (namespace, rest) = value.split_once(':').ok_or(ParseError::MissingSeparator)?;
+        let (id, relation) = match rest.split_once('#') {
+            Some((id, relation)) => (id, Some(relation)),
+            None => (rest, None),
+        };
+
+        match relation {
+            Some(relation) if relation.contains(INTERSECTION_SEPARATOR) => {
+                Ok(Intersection::try_new(
+                    namespace.to_string(),
+                    id.to_string(),
+                    relation
+                        .split(INTERSECTION_SEPARATOR)
+                        .map(str::to_string)
+                        .collect(),
+                )?
+                .into())
+            }
+            Some(relation) => {
+                Ok(
+                    Set::try_new(namespace.to_string(), id.to_string(), relation.to_string())?
+                        .into(),
+                )
+            }
+            None => Ok(Object::try_new(namespace.to_string(), id.to_string())?.into()),
+        }
+    }
+}
+
+impl TryFrom<&str> for Object {
+    type Error = ParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match ObjectOrSet::try_from(value)? {
+            ObjectOrSet::Object(obj) => Ok(obj.into_owned()),
+            ObjectOrSet::Set(_) | ObjectOrSet::Intersection(_) => {
+                Err(ParseError::UnexpectedRelation)
+            }
+        }
+    }
+}
+
+impl TryFrom<&str> for Set {
+    type Error = ParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match ObjectOrSet::try_from(value)? {
+            ObjectOrSet::Object(_) => Err(ParseError::MissingRelation),
+            ObjectOrSet::Set(set) => Ok(set.into_owned()),
+            ObjectOrSet::Intersection(_) => {
+                Err(ParseError::InvalidCharacter(INTERSECTION_SEPARATOR))
+            }
         }
     }
 }
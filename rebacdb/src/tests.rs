@@ -1,4 +1,10 @@
-use crate::{Object, RelationGraph, Set, WILDCARD_ID};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use crate::{
+    CheckCacheStats, ExpandNode, GraphInconsistency, Intersection, JsonError, LimitReached,
+    Meeting, Object, ObjectOrSet, ParseError, RelationGraph, SavefileError, Set, CONTAINS_RELATION,
+    WILDCARD_ID, WILDCARD_RELATION,
+};
 
 #[tokio::test]
 async fn simple_graph() {
@@ -69,3 +75,2814 @@ async fn wildcard() {
     assert!(!graph.check(&bob, &some_app_read, None).await);
     assert!(!graph.check(&charlie, &some_app_read, None).await);
 }
+
+#[tokio::test]
+async fn wildcard_relation() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+    let charlie: Object = ("user", "charlie").into();
+
+    let foo_all: Set = ("file", "foo", WILDCARD_RELATION).into();
+    let all_files_all: Set = ("file", WILDCARD_ID, WILDCARD_RELATION).into();
+    let foo_read: Set = ("file", "foo", "read").into();
+    let foo_write: Set = ("file", "foo", "write").into();
+    let bar_read: Set = ("file", "bar", "read").into();
+    let admins: Set = ("group", "admins", "member").into();
+
+    // wildcard relation on a single object
+    graph.insert(&alice, &foo_all).await;
+    assert!(graph.check(&alice, &foo_read, None).await);
+    assert!(graph.check(&alice, &foo_write, None).await);
+    assert!(!graph.check(&alice, &bar_read, None).await);
+    assert!(!graph.check(&bob, &foo_read, None).await);
+
+    // wildcard relation and id combined, through a group
+    graph.insert(&bob, &admins).await;
+    graph.insert(&admins, &all_files_all).await;
+    assert!(graph.check(&bob, &foo_write, None).await);
+    assert!(graph.check(&bob, &bar_read, None).await);
+    assert!(graph.check(&bob, &foo_all, None).await);
+    assert!(
+        !graph
+            .check(&bob, &("app", "foo", "read").into(), None)
+            .await
+    );
+
+    // wildcard relations are only matched, a grant for a relation doesn't grant all relations
+    graph.insert(&charlie, &foo_read).await;
+    assert!(!graph.check(&charlie, &foo_all, None).await);
+
+    // the wildcard relation is denied like any other relation
+    graph.insert_deny(&alice, &foo_all).await;
+    assert!(!graph.check(&alice, &foo_read, None).await);
+
+    let graph = RelationGraph::default().with_wildcards(false);
+    graph.insert(&alice, &foo_all).await;
+    assert!(!graph.check(&alice, &foo_read, None).await);
+    assert!(graph.check(&alice, &foo_all, None).await);
+}
+
+#[tokio::test]
+async fn list_relations() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+    let user_wildcard: Object = ("user", WILDCARD_ID).into();
+
+    let foo_read: Set = ("application", "foo", "read").into();
+    let bar_read: Set = ("application", "bar", "read").into();
+    let app_read: Set = ("application", WILDCARD_ID, "read").into();
+
+    graph.insert(&alice, &foo_read).await;
+    graph.insert(&user_wildcard, &foo_read).await;
+    graph.insert(&bob, &bar_read).await;
+    graph.insert(&alice, &app_read).await;
+    graph.insert_deny(&bob, &foo_read).await;
+    graph
+        .insert(&foo_read, &("group", "g", "member").into())
+        .await;
+
+    assert_eq!(
+        graph.list_relations("application").await,
+        vec![
+            (alice.clone().into(), app_read),
+            (bob.into(), bar_read),
+            (user_wildcard.into(), foo_read.clone()),
+            (alice.into(), foo_read.clone()),
+        ]
+    );
+    assert_eq!(
+        graph.list_relations("group").await,
+        vec![(foo_read.into(), ("group", "g", "member").into())]
+    );
+    assert!(graph.list_relations("user").await.is_empty());
+}
+
+#[tokio::test]
+async fn insert_reports_creation() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let foo_read: Set = ("application", "foo", "read").into();
+    let foo_write: Set = ("application", "foo", "write").into();
+
+    assert!(graph.insert(&alice, &foo_read).await);
+    assert!(!graph.insert(&alice, &foo_read).await);
+    assert!(graph.insert(&foo_write, &foo_read).await);
+    assert!(!graph.insert(&foo_write, &foo_read).await);
+
+    graph.remove(&alice, &foo_read).await;
+    assert!(graph.insert(&alice, &foo_read).await);
+}
+
+#[tokio::test]
+async fn repeated_insert() {
+    let graph = RelationGraph::default().with_wildcards(false);
+
+    let alice: Object = ("user", "alice").into();
+    let foo_read: Set = ("application", "foo", "read").into();
+
+    for _ in 0..3 {
+        graph.insert(&alice, &foo_read).await;
+    }
+    assert_eq!(graph.edge_count(true).await, 1);
+    assert_eq!(graph.direct_relations(&alice).await, vec![foo_read.clone()]);
+
+    // a single remove deletes the relation and the verticies it leaves without edges
+    assert!(graph.remove(&alice, &foo_read).await);
+    assert!(!graph.has(&alice, &foo_read).await);
+    assert_eq!(graph.edge_count(true).await, 0);
+    assert_eq!(graph.vertex_count().await, 0);
+}
+
+#[tokio::test]
+async fn check_unknown_verticies() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let mallory: Object = ("user", "mallory").into();
+    let foo_read: Set = ("application", "foo", "read").into();
+    let bar_read: Set = ("application", "bar", "read").into();
+
+    graph.insert(&alice, &foo_read).await;
+
+    // verticies that were never inserted are not related to anything
+    for (src, dst) in [
+        (&mallory, &foo_read),
+        (&alice, &bar_read),
+        (&mallory, &bar_read),
+    ] {
+        assert!(!graph.has(src, dst).await);
+        assert!(!graph.check(src, dst, None).await);
+        assert!(graph.check_path(src, dst, None).await.is_none());
+        assert_eq!(graph.try_check(src, dst, None).await, Ok(false));
+        assert!(!graph.check_sync(src, dst, None));
+    }
+    assert!(graph.reachable_sets(&mallory, None).await.is_empty());
+}
+
+#[tokio::test]
+async fn remove_set_relation() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let eng_member: Set = ("group", "eng", "member").into();
+    let foo_read: Set = ("application", "foo", "read").into();
+
+    graph.insert(&alice, &eng_member).await;
+    graph.insert(&eng_member, &foo_read).await;
+
+    // the set is still the source of a relation after its member is removed
+    assert!(graph.remove(&alice, &eng_member).await);
+    assert_eq!(
+        graph.direct_relations(&eng_member).await,
+        vec![foo_read.clone()]
+    );
+    assert!(!graph.check(&alice, &foo_read, None).await);
+
+    // removing the relation of the set leaves no edges behind in either direction
+    assert!(graph.remove(&eng_member, &foo_read).await);
+    assert!(graph.direct_relations(&eng_member).await.is_empty());
+    assert!(graph.direct_members(&foo_read).await.is_empty());
+    assert_eq!(graph.edge_count(false).await, 0);
+    assert_eq!(graph.vertex_count().await, 0);
+    assert!(graph.list_relations("application").await.is_empty());
+}
+
+#[tokio::test]
+async fn remove_reports_removal() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+    let foo_read: Set = ("application", "foo", "read").into();
+
+    graph.insert(&alice, &foo_read).await;
+    graph.insert_deny(&bob, &foo_read).await;
+
+    assert!(!graph.remove(&bob, &foo_read).await);
+    assert!(graph.remove(&alice, &foo_read).await);
+    assert!(!graph.remove(&alice, &foo_read).await);
+
+    assert!(!graph.remove_deny(&alice, &foo_read).await);
+    assert!(graph.remove_deny(&bob, &foo_read).await);
+    assert!(!graph.remove_deny(&bob, &foo_read).await);
+}
+
+#[tokio::test]
+async fn expand_wildcard() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+
+    let foo_read: Set = ("application", "foo", "read").into();
+    let app_read: Set = ("application", WILDCARD_ID, "read").into();
+
+    graph.insert(&alice, &app_read).await;
+    graph.insert(&bob, &foo_read).await;
+
+    assert_eq!(
+        graph.expand(&app_read).await,
+        vec![(alice.clone(), vec![app_read.clone()])]
+    );
+
+    let mut expanded = graph.expand(&foo_read).await;
+    expanded.sort_by(|a, b| a.0.id().cmp(b.0.id()));
+    assert_eq!(
+        expanded,
+        vec![
+            (alice, vec![foo_read.clone(), app_read.clone()]),
+            (bob, vec![foo_read])
+        ]
+    );
+
+    assert!(graph
+        .expand(&("service", WILDCARD_ID, "read").into())
+        .await
+        .is_empty());
+}
+
+#[tokio::test]
+async fn expand_capped() {
+    let graph = RelationGraph::default();
+
+    let eng_member: Set = ("group", "eng", "member").into();
+    let foo_read: Set = ("application", "foo", "read").into();
+
+    let users: Vec<Object> = (0..10)
+        .map(|i| ("user", format!("u{i}").as_str()).into())
+        .collect();
+    for user in &users[..5] {
+        graph.insert(user, &foo_read).await;
+    }
+    for user in &users[5..] {
+        graph.insert(user, &eng_member).await;
+    }
+    graph.insert(&eng_member, &foo_read).await;
+    graph.insert_deny(&users[1], &foo_read).await;
+
+    let (expanded, truncated) = graph.expand_capped(&foo_read, Some(3), None).await;
+    assert!(truncated);
+    assert_eq!(
+        expanded,
+        vec![
+            (users[0].clone(), vec![foo_read.clone()]),
+            (users[2].clone(), vec![foo_read.clone()]),
+            (users[3].clone(), vec![foo_read.clone()]),
+        ]
+    );
+    assert_eq!(
+        graph.expand_capped(&foo_read, Some(3), None).await.0,
+        expanded
+    );
+
+    let (expanded, truncated) = graph.expand_capped(&foo_read, Some(9), None).await;
+    assert!(!truncated);
+    assert_eq!(expanded.len(), 9);
+    assert_eq!(
+        expanded[8],
+        (users[9].clone(), vec![foo_read.clone(), eng_member])
+    );
+
+    assert_eq!(graph.expand_capped(&foo_read, None, None).await.0, expanded);
+    assert!(graph.expand_capped(&foo_read, Some(8), None).await.1);
+}
+
+#[tokio::test]
+async fn expand_limited() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+    let eng_member: Set = ("group", "eng", "member").into();
+    let org_member: Set = ("group", "org", "member").into();
+    let foo_read: Set = ("application", "foo", "read").into();
+
+    graph.insert(&alice, &eng_member).await;
+    graph.insert(&eng_member, &org_member).await;
+    graph.insert(&org_member, &foo_read).await;
+    graph.insert(&bob, &foo_read).await;
+
+    assert!(graph.expand_limited(&foo_read, Some(0)).await.is_empty());
+    assert_eq!(
+        graph.expand_limited(&foo_read, Some(1)).await,
+        vec![(bob.clone(), vec![foo_read.clone()])]
+    );
+    assert_eq!(graph.expand_limited(&foo_read, Some(2)).await.len(), 1);
+
+    let expanded = graph.expand_limited(&foo_read, Some(3)).await;
+    assert_eq!(
+        expanded,
+        vec![
+            (bob, vec![foo_read.clone()]),
+            (alice, vec![foo_read.clone(), org_member, eng_member]),
+        ]
+    );
+    assert_eq!(graph.expand_limited(&foo_read, None).await, expanded);
+    assert!(graph
+        .expand_capped(&foo_read, None, Some(2))
+        .await
+        .0
+        .iter()
+        .all(|(_, path)| path.len() <= 2));
+}
+
+#[test]
+fn parse() {
+    let alice: Object = ("user", "alice").into();
+    let eng_member: Set = ("group", "eng", "member").into();
+
+    assert_eq!(Object::try_from("user:alice"), Ok(alice.clone()));
+    assert_eq!(Set::try_from("group:eng#member"), Ok(eng_member.clone()));
+    assert_eq!(ObjectOrSet::try_from("user:alice"), Ok(alice.into()));
+    assert_eq!(
+        ObjectOrSet::try_from("group:eng#member"),
+        Ok(eng_member.into())
+    );
+
+    assert_eq!(
+        ObjectOrSet::try_from("alice"),
+        Err(ParseError::MissingSeparator)
+    );
+    assert_eq!(
+        ObjectOrSet::try_from(":alice"),
+        Err(ParseError::EmptyNamespace)
+    );
+    assert_eq!(ObjectOrSet::try_from("user:"), Err(ParseError::EmptyId));
+    assert_eq!(
+        ObjectOrSet::try_from("user:#read"),
+        Err(ParseError::EmptyId)
+    );
+    assert_eq!(
+        ObjectOrSet::try_from("group:eng#"),
+        Err(ParseError::EmptyRelation)
+    );
+    assert_eq!(
+        Object::try_from("group:eng#member"),
+        Err(ParseError::UnexpectedRelation)
+    );
+    assert_eq!(
+        Set::try_from("user:alice"),
+        Err(ParseError::MissingRelation)
+    );
+    assert_eq!(
+        ObjectOrSet::try_from("user:ali:ce"),
+        Err(ParseError::InvalidCharacter(':'))
+    );
+    assert_eq!(
+        Object::try_new("user".to_string(), "alice]\n[user:bob".to_string()),
+        Err(ParseError::InvalidCharacter(']'))
+    );
+    assert_eq!(
+        Set::try_new("file".to_string(), "foo".to_string(), "!read".to_string()),
+        Err(ParseError::InvalidCharacter('!'))
+    );
+    assert_eq!(
+        Set::try_new("file".to_string(), "foo".to_string(), "read".to_string()),
+        Ok(("file", "foo", "read").into())
+    );
+}
+
+#[test]
+fn check_sync() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+    let foo_read: Set = ("application", "foo", "read").into();
+
+    futures::executor::block_on(graph.insert(&alice, &foo_read));
+
+    assert!(graph.check_sync(&alice, &foo_read, None));
+    assert!(!graph.check_sync(&bob, &foo_read, None));
+}
+
+#[tokio::test]
+async fn savefile_roundtrip() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+    let charlie: Object = ("user", "charlie").into();
+    let user_wildcard: Object = ("user", WILDCARD_ID).into();
+
+    let foo_read: Set = ("application", "foo", "read").into();
+    let foo_write: Set = ("application", "foo", "write").into();
+    let bar_read: Set = ("application", "bar", "read").into();
+    let app_read: Set = ("application", WILDCARD_ID, "read").into();
+    let eng_member: Set = ("group", "eng", "member").into();
+    let group_member: Set = ("group", WILDCARD_ID, "member").into();
+
+    graph.insert(&alice, &foo_read).await;
+    graph.insert(&user_wildcard, &foo_read).await;
+    graph.insert(&foo_write, &foo_read).await;
+    graph.insert(&bob, &app_read).await;
+    graph.insert(&bob, &eng_member).await;
+    graph.insert(&eng_member, &bar_read).await;
+    graph.insert(&group_member, &foo_write).await;
+
+    let mut first = vec![];
+    graph.write_savefile(&mut first).await.unwrap();
+
+    let graph = RelationGraph::read_savefile(&mut first.as_slice())
+        .await
+        .unwrap();
+    let mut second = vec![];
+    graph.write_savefile(&mut second).await.unwrap();
+
+    let graph = RelationGraph::read_savefile(&mut second.as_slice())
+        .await
+        .unwrap();
+    let mut third = vec![];
+    graph.write_savefile(&mut third).await.unwrap();
+
+    assert_eq!(first, second);
+    assert_eq!(second, third);
+
+    assert!(graph.check(&charlie, &foo_read, None).await);
+    assert!(graph.check(&bob, &bar_read, None).await);
+    assert!(graph.has(&group_member, &foo_write).await);
+    assert!(!graph.check(&alice, &bar_read, None).await);
+}
+
+#[tokio::test]
+async fn without_wildcards() {
+    let graph = RelationGraph::default().with_wildcards(false);
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+
+    let foo_read: Set = ("application", "foo", "read").into();
+    let app_read: Set = ("application", WILDCARD_ID, "read").into();
+
+    graph.insert(&alice, &foo_read).await;
+    graph.insert(&bob, &app_read).await;
+
+    assert!(graph.check(&alice, &foo_read, None).await);
+    assert!(graph.check(&bob, &app_read, None).await);
+    assert!(!graph.check(&bob, &foo_read, None).await);
+
+    assert_eq!(
+        graph.expand(&foo_read).await,
+        vec![(alice.clone(), vec![foo_read.clone()])]
+    );
+    assert!(graph
+        .expand(&("application", "bar", "read").into())
+        .await
+        .is_empty());
+
+    let mut savefile = vec![];
+    graph.write_savefile(&mut savefile).await.unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&savefile),
+        "# rebacs-version=1 revision=2\n\n[application:*]\nread = [ user:bob ]\n\n[application:foo]\nread = [ user:alice ]\n"
+    );
+
+    graph.remove(&alice, &foo_read).await;
+    graph.remove(&bob, &app_read).await;
+    assert!(graph.verticies.read().await.is_empty());
+}
+
+#[tokio::test]
+async fn without_wildcards_savefile() {
+    let graph = RelationGraph::default().with_wildcards(false);
+
+    let bob: Object = ("user", "bob").into();
+    let foo_read: Set = ("application", "foo", "read").into();
+    let app_read: Set = ("application", WILDCARD_ID, "read").into();
+
+    // an explicit relation of the shape of the edges between wildcards and their sets
+    graph.insert(&app_read, &foo_read).await;
+    graph.insert(&bob, &app_read).await;
+    assert!(graph.check(&bob, &foo_read, None).await);
+
+    let mut savefile = vec![];
+    graph.write_savefile(&mut savefile).await.unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&savefile),
+        "# rebacs-version=1 revision=2\n\n[application:*]\nread = [ user:bob ]\n\n[application:foo]\nread = [ application:*#read ]\n"
+    );
+
+    let read = RelationGraph::default().with_wildcards(false);
+    read.extend_from_savefile(&mut savefile.as_slice())
+        .await
+        .unwrap();
+    assert_eq!(read.current_revision(), 2);
+    assert!(read.has(&app_read, &foo_read).await);
+    assert!(read.check(&bob, &foo_read, None).await);
+    assert!(
+        !read
+            .check(&bob, &("application", "bar", "read").into(), None)
+            .await
+    );
+
+    let mut reread = vec![];
+    read.write_savefile(&mut reread).await.unwrap();
+    assert_eq!(savefile, reread);
+}
+
+#[tokio::test]
+async fn without_wildcards_compact() {
+    let graph = RelationGraph::default().with_wildcards(false);
+
+    let alice: Object = ("user", "alice").into();
+    let foo_read: Set = ("doc", "foo", "read").into();
+    let any_read: Set = ("doc", WILDCARD_ID, "read").into();
+    let user_x: Set = ("user", WILDCARD_ID, "x").into();
+
+    // explicit relations of the shape of the edges between wildcards and their verticies
+    graph.insert(&any_read, &foo_read).await;
+    graph.insert(&alice, &user_x).await;
+
+    assert_eq!(graph.compact().await, 0);
+    assert_eq!(graph.edge_count(true).await, 2);
+    assert!(graph.has(&any_read, &foo_read).await);
+    assert!(graph.has(&alice, &user_x).await);
+}
+
+#[tokio::test]
+async fn without_wildcards_remove() {
+    let graph = RelationGraph::default().with_wildcards(false);
+
+    let alice: Object = ("user", "alice").into();
+    let foo_read: Set = ("doc", "foo", "read").into();
+    let any_read: Set = ("doc", WILDCARD_ID, "read").into();
+
+    graph.insert(&any_read, &foo_read).await;
+    graph.insert(&alice, &foo_read).await;
+
+    assert!(graph.remove(&alice, &foo_read).await);
+    assert!(graph.has(&any_read, &foo_read).await);
+    assert_eq!(graph.edge_count(true).await, 1);
+
+    assert!(graph.remove(&any_read, &foo_read).await);
+    assert!(graph.verticies.read().await.is_empty());
+}
+
+#[tokio::test]
+async fn without_wildcards_rename_object() {
+    let graph = RelationGraph::default().with_wildcards(false);
+
+    let foo_read: Set = ("doc", "foo", "read").into();
+    let bar_read: Set = ("doc", "bar", "read").into();
+    let any_read: Set = ("doc", WILDCARD_ID, "read").into();
+
+    graph.insert(&any_read, &foo_read).await;
+
+    assert_eq!(graph.rename_object("doc", "foo", "bar").await, 1);
+    assert!(graph.has(&any_read, &bar_read).await);
+    assert!(!graph.contains(&foo_read).await);
+    assert_eq!(graph.edge_count(true).await, 1);
+}
+
+#[tokio::test]
+async fn without_wildcards_remove_namespace() {
+    let graph = RelationGraph::default().with_wildcards(false);
+
+    let alice: Object = ("user", "alice").into();
+    let foo_read: Set = ("doc", "foo", "read").into();
+    let any_read: Set = ("doc", WILDCARD_ID, "read").into();
+
+    graph.insert(&any_read, &foo_read).await;
+    graph.insert(&alice, &foo_read).await;
+
+    assert_eq!(graph.remove_namespace("doc").await, 2);
+    assert_eq!(graph.current_revision(), 4);
+    assert!(graph.verticies.read().await.is_empty());
+}
+
+#[tokio::test]
+async fn deny() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+    let charlie: Object = ("user", "charlie").into();
+
+    let foo_read: Set = ("application", "foo", "read").into();
+    let bar_read: Set = ("application", "bar", "read").into();
+
+    graph.insert(&alice, &foo_read).await;
+    graph.insert(&bob, &foo_read).await;
+    assert!(graph.check(&alice, &foo_read, None).await);
+
+    assert!(graph.insert_deny(&alice, &foo_read).await);
+    assert!(!graph.insert_deny(&alice, &foo_read).await);
+    assert!(!graph.check(&alice, &foo_read, None).await);
+    assert!(graph.check(&bob, &foo_read, None).await);
+    assert_eq!(
+        graph.expand(&foo_read).await,
+        vec![(bob.clone(), vec![foo_read.clone()])]
+    );
+
+    graph.remove_deny(&alice, &foo_read).await;
+    assert!(graph.check(&alice, &foo_read, None).await);
+
+    // denying the wildcard denies every object of the namespace
+    graph.insert(&charlie, &bar_read).await;
+    graph
+        .insert_deny(&alice, &("application", WILDCARD_ID, "read").into())
+        .await;
+    assert!(!graph.check(&alice, &foo_read, None).await);
+    assert!(!graph.check(&alice, &bar_read, None).await);
+    assert!(graph.check(&charlie, &bar_read, None).await);
+}
+
+#[tokio::test]
+async fn deny_group_override() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+
+    let admins: Set = ("group", "admins", "member").into();
+    let banned: Set = ("group", "banned", "member").into();
+    let project_access: Set = ("project", "x", "access").into();
+    let project_read: Set = ("project", "x", "read").into();
+
+    graph.insert(&alice, &admins).await;
+    graph.insert(&bob, &admins).await;
+    graph.insert(&admins, &project_access).await;
+    graph.insert(&project_access, &project_read).await;
+
+    graph.insert_deny(&alice, &project_access).await;
+
+    assert!(!graph.check(&alice, &project_access, None).await);
+    assert!(!graph.check(&alice, &project_read, None).await);
+    assert!(graph.check(&alice, &admins, None).await);
+    assert!(graph.check(&bob, &project_read, None).await);
+
+    // deny relations from a set apply to all of its members
+    graph.insert(&bob, &banned).await;
+    graph.insert_deny(&banned, &project_access).await;
+    assert!(!graph.check(&bob, &project_read, None).await);
+
+    let mut savefile = vec![];
+    graph.write_savefile(&mut savefile).await.unwrap();
+    let graph = RelationGraph::read_savefile(&mut savefile.as_slice())
+        .await
+        .unwrap();
+
+    assert!(!graph.check(&alice, &project_read, None).await);
+    assert!(!graph.check(&bob, &project_read, None).await);
+    assert!(graph.check(&alice, &admins, None).await);
+
+    graph.remove_deny(&alice, &project_access).await;
+    assert!(graph.check(&alice, &project_read, None).await);
+}
+
+#[tokio::test]
+async fn contains() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+    let foo_read: Set = ("application", "foo", "read").into();
+    let foo_write: Set = ("application", "foo", "write").into();
+
+    graph.insert(&alice, &foo_read).await;
+
+    assert!(graph.contains(&alice).await);
+    assert!(graph.contains(&foo_read).await);
+    assert!(!graph.contains(&bob).await);
+    assert!(!graph.contains(&foo_write).await);
+}
+
+#[tokio::test]
+async fn to_dot() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+    let eng_member: Set = ("group", "eng", "member").into();
+    let foo_read: Set = ("file", "fo\"o", "read").into();
+
+    graph.insert(&alice, &eng_member).await;
+    graph.insert(&eng_member, &foo_read).await;
+    graph
+        .insert(&Object::from(("user", WILDCARD_ID)), &foo_read)
+        .await;
+    graph.insert_deny(&bob, &foo_read).await;
+
+    let dot = graph.to_dot().await;
+    let lines: Vec<&str> = dot.lines().collect();
+    assert_eq!(lines.first(), Some(&"digraph {"));
+    assert_eq!(lines.last(), Some(&"}"));
+    for line in [
+        r#"  "user:alice""#,
+        r#"  "user:*" [style=dashed]"#,
+        r#"  "file:*#read" [style=dashed]"#,
+        r#"  "user:alice" -> "group:eng#member""#,
+        r#"  "group:eng#member" -> "file:fo\"o#read""#,
+        r#"  "user:*" -> "file:fo\"o#read""#,
+        r#"  "user:bob" -> "file:fo\"o#read" [color=red]"#,
+    ] {
+        assert!(lines.contains(&line), "missing {line} in {dot}");
+    }
+    // the edges between the verticies and their wildcards are omitted
+    assert!(!dot.contains(r#""user:alice" -> "user:*""#));
+    assert!(!dot.contains(r#""file:*#read" -> "file:fo\"o#read""#));
+}
+
+#[tokio::test]
+async fn snapshot() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+    let foo_read: Set = ("application", "foo", "read").into();
+
+    graph.insert(&alice, &foo_read).await;
+    graph.insert_deny(&bob, &foo_read).await;
+    let mut expected = vec![];
+    graph.write_savefile(&mut expected).await.unwrap();
+
+    let snapshot = graph.snapshot().await;
+    graph.remove(&alice, &foo_read).await;
+    graph
+        .insert(&bob, &("application", "bar", "read").into())
+        .await;
+
+    assert_eq!(snapshot.revision(), 2);
+    let mut savefile = vec![];
+    snapshot.write_savefile(&mut savefile).await.unwrap();
+    assert_eq!(
+        String::from_utf8(savefile).unwrap(),
+        String::from_utf8(expected).unwrap()
+    );
+}
+
+#[tokio::test]
+async fn mutation_revision() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+    let foo_read: Set = ("application", "foo", "read").into();
+
+    assert_eq!(graph.insert_revision(&alice, &foo_read).await, Some(1));
+    assert_eq!(graph.insert_revision(&alice, &foo_read).await, None);
+    assert_eq!(
+        graph
+            .insert_each_revision([(&alice, &foo_read), (&bob, &foo_read), (&bob, &foo_read)])
+            .await,
+        vec![None, Some(2), None]
+    );
+    assert_eq!(graph.remove_revision(&bob, &foo_read).await, Some(3));
+    assert_eq!(graph.remove_revision(&bob, &foo_read).await, None);
+    assert_eq!(graph.current_revision(), 3);
+}
+
+#[tokio::test]
+async fn savefile_revision() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let foo_read: Set = ("application", "foo", "read").into();
+    let bar_read: Set = ("application", "bar", "read").into();
+
+    assert_eq!(graph.current_revision(), 0);
+    graph.insert(&alice, &foo_read).await;
+    graph.insert(&alice, &foo_read).await;
+    graph.insert(&alice, &bar_read).await;
+    graph.remove(&alice, &bar_read).await;
+    graph.remove(&alice, &bar_read).await;
+    assert_eq!(graph.current_revision(), 3);
+
+    let mut savefile = vec![];
+    graph.write_savefile(&mut savefile).await.unwrap();
+    assert!(savefile.starts_with(b"# rebacs-version=1 revision=3\n"));
+
+    let graph = RelationGraph::read_savefile(&mut savefile.as_slice())
+        .await
+        .unwrap();
+    assert_eq!(graph.current_revision(), 3);
+    assert!(graph.check(&alice, &foo_read, None).await);
+
+    let legacy = "\n[application:foo]\nread = [ user:alice ]\n";
+    let graph = RelationGraph::read_savefile(&mut legacy.as_bytes())
+        .await
+        .unwrap();
+    assert_eq!(graph.current_revision(), 0);
+    assert!(graph.check(&alice, &foo_read, None).await);
+}
+
+#[tokio::test]
+async fn savefile_escaping() {
+    let graph = RelationGraph::default();
+
+    let ids = [
+        "foo:bar",
+        "read#write",
+        "a,b, c",
+        "[x]",
+        "k=v",
+        "!deny",
+        "back\\slash\\",
+        "a&b",
+        " padded \t",
+        "new\nline",
+        "self",
+        "\\u{41}",
+        "ünï©ødé",
+    ];
+    let objects: Vec<Object> = ids.iter().map(|id| ("us:er", *id).into()).collect();
+    let sets: Vec<Set> = ids.iter().map(|id| ("do#c", *id, "re:l!").into()).collect();
+    let self_set: Set = ("do#c", "self", "a,b").into();
+    let intersection = Intersection::new(
+        "gr[oup".to_string(),
+        "e=ng".to_string(),
+        vec!["ad#min".to_string(), "mem ber".to_string()],
+    );
+
+    for (obj, set) in objects.iter().zip(&sets) {
+        graph.insert(obj, set).await;
+        graph.insert(set, &self_set).await;
+    }
+    graph.insert(&self_set, &sets[0]).await;
+    graph.insert(&intersection, &sets[1]).await;
+    graph.insert_deny(&objects[2], &sets[0]).await;
+
+    let mut savefile = vec![];
+    graph.write_savefile(&mut savefile).await.unwrap();
+    let read = RelationGraph::read_savefile(&mut savefile.as_slice())
+        .await
+        .unwrap();
+    let mut reread = vec![];
+    read.write_savefile(&mut reread).await.unwrap();
+    assert_eq!(
+        String::from_utf8(savefile).unwrap(),
+        String::from_utf8(reread).unwrap()
+    );
+
+    for (obj, set) in objects.iter().zip(&sets) {
+        assert!(read.has(obj, set).await);
+        assert!(read.has(set, &self_set).await);
+    }
+    assert!(read.has(&self_set, &sets[0]).await);
+    assert!(read.has(&intersection, &sets[1]).await);
+    assert!(!read.check(&objects[2], &sets[0], None).await);
+    assert!(read.check(&objects[1], &sets[0], None).await);
+
+    let err = RelationGraph::read_savefile(&mut "[user:a\\]\n".as_bytes()).await;
+    let Err(SavefileError::InvalidSection { line: 1, source }) = err else {
+        panic!("expected an invalid section");
+    };
+    assert_eq!(source, ParseError::InvalidEscape);
+}
+
+#[tokio::test]
+async fn check_relation_prefix() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+    let charlie: Object = ("user", "charlie").into();
+
+    let foo_repo_read: Set = ("project", "foo", "repo.read").into();
+    let foo_repo_write: Set = ("project", "foo", "repo.write").into();
+    let foo_wiki_read: Set = ("project", "foo", "wiki.read").into();
+    let project_repo_read: Set = ("project", WILDCARD_ID, "repo.read").into();
+
+    graph.insert(&alice, &foo_repo_write).await;
+    graph.insert(&bob, &foo_wiki_read).await;
+    graph.insert(&charlie, &project_repo_read).await;
+
+    assert!(
+        graph
+            .check_relation_prefix(&alice, "project", "foo", "repo.", None)
+            .await
+    );
+    assert!(
+        !graph
+            .check_relation_prefix(&bob, "project", "foo", "repo.", None)
+            .await
+    );
+    assert!(
+        graph
+            .check_relation_prefix(&charlie, "project", "bar", "repo.", None)
+            .await
+    );
+    assert!(
+        !graph
+            .check_relation_prefix(&alice, "project", "bar", "repo.", None)
+            .await
+    );
+
+    // a denied relation doesn't hide other relations matching the prefix
+    graph.insert(&alice, &foo_repo_read).await;
+    graph.insert_deny(&alice, &foo_repo_write).await;
+    assert!(
+        graph
+            .check_relation_prefix(&alice, "project", "foo", "repo.", None)
+            .await
+    );
+    graph.remove(&alice, &foo_repo_read).await;
+    assert!(
+        !graph
+            .check_relation_prefix(&alice, "project", "foo", "repo.", None)
+            .await
+    );
+}
+
+#[tokio::test]
+async fn expand_filtered() {
+    let alice: Object = ("user", "alice").into();
+    let backup: Object = ("service", "backup").into();
+
+    let eng_member: Set = ("group", "eng", "member").into();
+    let foo_read: Set = ("application", "foo", "read").into();
+
+    let graph = RelationGraph::from_relations([
+        (ObjectOrSet::from(&alice), &eng_member),
+        ((&backup).into(), &eng_member),
+        ((&eng_member).into(), &foo_read),
+    ])
+    .await;
+
+    assert_eq!(
+        graph
+            .expand_filtered(&foo_read, |obj| obj.namespace() == "user")
+            .await,
+        vec![(alice, vec![foo_read.clone(), eng_member.clone()])]
+    );
+    assert_eq!(
+        graph
+            .expand_filtered(&foo_read, |obj| obj.namespace() == "service")
+            .await,
+        vec![(backup, vec![foo_read.clone(), eng_member])]
+    );
+    assert!(graph
+        .expand_filtered(&foo_read, |obj| obj.namespace() == "group")
+        .await
+        .is_empty());
+}
+
+#[tokio::test]
+async fn from_relations() {
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+    let charlie: Object = ("user", "charlie").into();
+    let user_wildcard: Object = ("user", WILDCARD_ID).into();
+
+    let foo_read: Set = ("application", "foo", "read").into();
+    let bar_read: Set = ("application", "bar", "read").into();
+    let app_read: Set = ("application", WILDCARD_ID, "read").into();
+    let some_app_read: Set = ("application", "bla", "read").into();
+
+    let graph = RelationGraph::from_relations([
+        (&alice, &foo_read),
+        (&user_wildcard, &foo_read),
+        (&bob, &bar_read),
+        (&alice, &app_read),
+    ])
+    .await;
+
+    assert!(graph.check(&alice, &foo_read, None).await);
+    assert!(graph.check(&charlie, &foo_read, None).await);
+    assert!(graph.check(&bob, &bar_read, None).await);
+    assert!(graph.check(&alice, &some_app_read, None).await);
+    assert!(!graph.check(&bob, &some_app_read, None).await);
+}
+
+#[tokio::test]
+async fn insert_each() {
+    let graph = RelationGraph::default();
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+    let foo_read: Set = ("file", "foo", "read").into();
+
+    graph.insert(&bob, &foo_read).await;
+    let created = graph
+        .insert_each([(&alice, &foo_read), (&bob, &foo_read), (&alice, &foo_read)])
+        .await;
+    assert_eq!(created, vec![true, false, false]);
+    assert!(graph.check(&alice, &foo_read, None).await);
+}
+
+#[tokio::test]
+async fn insert_many() {
+    let users: Vec<Object> = (0..500)
+        .map(|i| ("user", format!("u{i}").as_str()).into())
+        .chain([("user", WILDCARD_ID).into()])
+        .collect();
+    let groups: Vec<Set> = (0..100)
+        .map(|i| ("group", format!("g{i}").as_str(), "member").into())
+        .collect();
+    let files: Vec<Set> = (0..50)
+        .map(|i| ("file", format!("f{i}").as_str(), "read").into())
+        .chain([("file", WILDCARD_ID, "read").into()])
+        .collect();
+
+    let mut relations: Vec<(ObjectOrSet, &Set)> = vec![];
+    for (i, user) in users.iter().enumerate() {
+        for j in 0..5 {
+            relations.push((user.into(), &groups[(i * 7 + j * 13) % groups.len()]));
+        }
+    }
+    for (i, group) in groups.iter().enumerate() {
+        relations.push((group.into(), &groups[(i * 3 + 1) % groups.len()]));
+        for j in 0..3 {
+            relations.push((group.into(), &files[(i * 11 + j) % files.len()]));
+        }
+    }
+
+    let single = RelationGraph::default();
+    let mut created = 0;
+    for (src, dst) in &relations {
+        if single.insert(src, dst).await {
+            created += 1;
+        }
+    }
+    let batch = RelationGraph::default();
+    assert_eq!(batch.insert_many(relations.iter().cloned()).await, created);
+    assert_eq!(batch.insert_many(relations.iter().cloned()).await, 0);
+
+    for user in users.iter().step_by(7) {
+        for file in &files {
+            assert_eq!(
+                batch.check(user, file, None).await,
+                single.check(user, file, None).await
+            );
+        }
+    }
+
+    let mut single_savefile = vec![];
+    single.write_savefile(&mut single_savefile).await.unwrap();
+    let mut batch_savefile = vec![];
+    batch.write_savefile(&mut batch_savefile).await.unwrap();
+    assert_eq!(batch_savefile, single_savefile);
+}
+
+#[tokio::test]
+async fn savefile_self_id() {
+    let alice: Object = ("user", "alice").into();
+    let user_self: Object = ("user", "self").into();
+
+    let self_member: Set = ("group", "self", "member").into();
+    let self_admin: Set = ("group", "self", "admin").into();
+    let eng_member: Set = ("group", "eng", "member").into();
+    let foo_read: Set = ("application", "foo", "read").into();
+
+    let graph = RelationGraph::from_relations([
+        (ObjectOrSet::from(&user_self), &self_member),
+        ((&alice).into(), &self_admin),
+        ((&self_admin).into(), &self_member),
+        ((&self_member).into(), &eng_member),
+        ((&user_self).into(), &foo_read),
+    ])
+    .await;
+
+    let mut first = vec![];
+    graph.write_savefile(&mut first).await.unwrap();
+    let graph = RelationGraph::read_savefile(&mut first.as_slice())
+        .await
+        .unwrap();
+    let mut second = vec![];
+    graph.write_savefile(&mut second).await.unwrap();
+
+    assert_eq!(first, second);
+    assert!(graph.has(&user_self, &self_member).await);
+    assert!(graph.has(&self_admin, &self_member).await);
+    assert!(graph.has(&self_member, &eng_member).await);
+    assert!(graph.has(&user_self, &foo_read).await);
+    assert!(graph.check(&alice, &eng_member, None).await);
+    assert!(!graph.check(&alice, &foo_read, None).await);
+}
+
+#[tokio::test]
+async fn reachable_sets() {
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+    let charlie: Object = ("user", "charlie").into();
+
+    let eng_member: Set = ("group", "eng", "member").into();
+    let foo_read: Set = ("application", "foo", "read").into();
+    let bar_read: Set = ("application", "bar", "read").into();
+    let app_write: Set = ("application", WILDCARD_ID, "write").into();
+
+    let graph = RelationGraph::from_relations([
+        (ObjectOrSet::from(&alice), &eng_member),
+        ((&eng_member).into(), &foo_read),
+        ((&eng_member).into(), &bar_read),
+        ((&alice).into(), &app_write),
+        ((&bob).into(), &bar_read),
+    ])
+    .await;
+    graph.insert_deny(&alice, &bar_read).await;
+
+    assert_eq!(
+        graph.reachable_sets(&alice, None).await,
+        vec![app_write.clone(), foo_read.clone(), eng_member.clone()]
+    );
+    assert_eq!(
+        graph.reachable_sets(&alice, Some(1)).await,
+        vec![app_write, eng_member]
+    );
+    assert_eq!(graph.reachable_sets(&bob, None).await, vec![bar_read]);
+    assert!(graph.reachable_sets(&charlie, None).await.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn reciprocal_inserts() {
+    let graph = Arc::new(RelationGraph::default());
+    let a: Set = ("group", "a", "member").into();
+    let b: Set = ("group", "b", "member").into();
+
+    let tasks = [(a.clone(), b.clone()), (b.clone(), a.clone())]
+        .into_iter()
+        .map(|(src, dst)| {
+            let graph = graph.clone();
+            tokio::spawn(async move {
+                for _ in 0..1000 {
+                    graph.insert(&src, &dst).await;
+                    graph.check(&src, &dst, None).await;
+                    graph.remove(&src, &dst).await;
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    tokio::time::timeout(Duration::from_secs(30), async {
+        for task in tasks {
+            task.await.unwrap();
+        }
+    })
+    .await
+    .expect("reciprocal inserts deadlocked");
+
+    assert!(!graph.has(&a, &b).await);
+    assert!(!graph.has(&b, &a).await);
+}
+
+#[tokio::test]
+async fn savefiles_by_namespace() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+
+    let foo_read: Set = ("application", "foo", "read").into();
+    let eng_member: Set = ("group", "eng", "member").into();
+
+    graph.insert(&alice, &foo_read).await;
+    graph.insert(&bob, &eng_member).await;
+    graph.insert(&eng_member, &foo_read).await;
+
+    let dir = std::env::temp_dir().join(format!("rebacs-savefiles-{}", std::process::id()));
+    tokio::fs::create_dir_all(&dir).await.unwrap();
+    graph.write_savefiles_by_namespace(&dir).await.unwrap();
+
+    let mut files = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|x| x.unwrap().file_name().into_string().unwrap())
+        .collect::<Vec<_>>();
+    files.sort();
+    assert_eq!(files, vec!["application.dat", "group.dat"]);
+
+    let groups = RelationGraph::read_savefiles(&[dir.join("group.dat")])
+        .await
+        .unwrap();
+    assert!(groups.check(&bob, &eng_member, None).await);
+    assert!(!groups.check(&alice, &foo_read, None).await);
+
+    let merged =
+        RelationGraph::read_savefiles(&[dir.join("application.dat"), dir.join("group.dat")])
+            .await
+            .unwrap();
+    assert!(merged.check(&bob, &foo_read, None).await);
+    assert_eq!(merged.current_revision(), graph.current_revision());
+
+    let (mut expected, mut actual) = (vec![], vec![]);
+    graph.write_savefile(&mut expected).await.unwrap();
+    merged.write_savefile(&mut actual).await.unwrap();
+    assert_eq!(expected, actual);
+
+    // namespaces that would be written outside of `dir` are rejected
+    for namespace in ["../escaped", ".hidden", "a/b"] {
+        let graph = RelationGraph::default();
+        graph
+            .insert(&alice, &(namespace, "foo", "read").into())
+            .await;
+        let err = graph.write_savefiles_by_namespace(&dir).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput, "{namespace}");
+    }
+    assert!(!dir.join("../escaped.dat").exists());
+    let mut after = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|x| x.unwrap().file_name().into_string().unwrap())
+        .collect::<Vec<_>>();
+    after.sort();
+    assert_eq!(after, files);
+
+    tokio::fs::remove_dir_all(&dir).await.unwrap();
+}
+
+#[tokio::test]
+async fn namespace_alias() {
+    let graph = RelationGraph::default().with_namespace_alias("product_a", "shared");
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+
+    let shared_eng: Set = ("shared", "eng", "member").into();
+    let product_eng: Set = ("product_a", "eng", "member").into();
+    let product_ops: Set = ("product_a", "ops", "member").into();
+    let other_eng: Set = ("product_b", "eng", "member").into();
+
+    graph.insert(&alice, &shared_eng).await;
+    graph
+        .insert(&bob, &("shared", WILDCARD_ID, "member").into())
+        .await;
+
+    assert!(graph.check(&alice, &product_eng, None).await);
+    assert!(!graph.check(&alice, &product_ops, None).await);
+    assert!(!graph.check(&alice, &other_eng, None).await);
+    assert!(graph.check(&bob, &product_ops, None).await);
+
+    graph.insert(&alice, &product_ops).await;
+    assert!(
+        !graph
+            .check(&alice, &("shared", "ops", "member").into(), None)
+            .await
+    );
+
+    graph.insert_deny(&alice, &shared_eng).await;
+    assert!(!graph.check(&alice, &product_eng, None).await);
+
+    let graph = RelationGraph::default();
+    graph.insert(&alice, &shared_eng).await;
+    assert!(!graph.check(&alice, &product_eng, None).await);
+}
+
+#[tokio::test]
+async fn wildcard_membership() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+    let user_wildcard: Object = ("user", WILDCARD_ID).into();
+
+    let everyone_member: Set = ("group", "everyone", "member").into();
+    let foo_read: Set = ("application", "foo", "read").into();
+    let bar_read: Set = ("application", "bar", "read").into();
+
+    graph.insert(&user_wildcard, &everyone_member).await;
+    graph.insert(&everyone_member, &foo_read).await;
+    graph.insert(&alice, &bar_read).await;
+    graph.insert_deny(&bob, &foo_read).await;
+
+    assert!(graph.check(&alice, &everyone_member, Some(1)).await);
+    assert!(graph.check(&alice, &foo_read, Some(2)).await);
+    assert!(!graph.check(&bob, &foo_read, None).await);
+    assert_eq!(
+        graph.reachable_sets(&alice, Some(2)).await,
+        vec![bar_read, foo_read, everyone_member]
+    );
+}
+
+#[tokio::test]
+async fn compact() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let eng_member: Set = ("group", "eng", "member").into();
+    let foo_read: Set = ("application", "foo", "read").into();
+
+    for i in 0..10 {
+        let user: Object = ("user", format!("user{i}").as_str()).into();
+        let app_read: Set = ("application", format!("app{i}").as_str(), "read").into();
+        graph.insert(&user, &app_read).await;
+        graph.insert(&user, &eng_member).await;
+    }
+    graph.insert(&eng_member, &foo_read).await;
+    graph.insert(&alice, &foo_read).await;
+
+    assert_eq!(graph.compact().await, 0);
+
+    for i in 0..10 {
+        let user: Object = ("user", format!("user{i}").as_str()).into();
+        let app_read: Set = ("application", format!("app{i}").as_str(), "read").into();
+        graph.remove(&user, &app_read).await;
+        graph.remove(&user, &eng_member).await;
+    }
+    graph.remove(&eng_member, &foo_read).await;
+
+    // `remove` already cleaned up the verticies only connected to wildcards
+    let revision = graph.current_revision();
+    assert_eq!(graph.compact().await, 0);
+    assert_eq!(graph.current_revision(), revision);
+    assert!(graph.check(&alice, &foo_read, None).await);
+    assert!(!graph.contains(&eng_member).await);
+    assert!(
+        !graph
+            .contains(&Set::from(("group", WILDCARD_ID, "member")))
+            .await
+    );
+    assert_eq!(graph.vertex_count().await, 4);
+
+    graph.remove(&alice, &foo_read).await;
+    assert_eq!(graph.compact().await, 0);
+    assert!(!graph.contains(&Object::from(("user", WILDCARD_ID))).await);
+}
+
+#[tokio::test]
+async fn remove_wildcard_cleanup() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+    let eng_member: Set = ("group", "eng", "member").into();
+    let foo_read: Set = ("application", "foo", "read").into();
+    let eng_admin_member = Intersection::new(
+        "group".to_string(),
+        "eng".to_string(),
+        vec!["admin".to_string(), "member".to_string()],
+    );
+
+    graph.insert(&alice, &foo_read).await;
+    assert!(graph.remove(&alice, &foo_read).await);
+    assert_eq!(graph.vertex_count().await, 0);
+
+    graph.insert(&alice, &eng_member).await;
+    graph.insert(&bob, &eng_member).await;
+    graph.insert(&eng_admin_member, &foo_read).await;
+    graph.insert_deny(&bob, &foo_read).await;
+
+    graph.remove(&alice, &eng_member).await;
+    assert!(!graph.contains(&alice).await);
+    assert!(graph.contains(&Object::from(("user", WILDCARD_ID))).await);
+
+    graph.remove(&bob, &eng_member).await;
+    graph.remove(&eng_admin_member, &foo_read).await;
+    graph.remove_deny(&bob, &foo_read).await;
+    assert_eq!(graph.vertex_count().await, 0);
+    assert_eq!(graph.edge_count(true).await, 0);
+}
+
+#[tokio::test]
+async fn direct_members() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+
+    let eng_member: Set = ("group", "eng", "member").into();
+    let admin_member: Set = ("group", "admin", "member").into();
+    let foo_read: Set = ("application", "foo", "read").into();
+
+    graph.insert(&alice, &eng_member).await;
+    graph.insert(&bob, &admin_member).await;
+    graph.insert(&admin_member, &eng_member).await;
+    graph.insert(&eng_member, &foo_read).await;
+
+    assert_eq!(
+        graph.direct_members(&eng_member).await,
+        vec![ObjectOrSet::from(admin_member), alice.into()]
+    );
+    assert_eq!(
+        graph.direct_members(&foo_read).await,
+        vec![ObjectOrSet::from(eng_member)]
+    );
+    assert!(graph
+        .direct_members(&("application", "bar", "read").into())
+        .await
+        .is_empty());
+}
+
+#[tokio::test]
+async fn direct_relations() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+
+    let eng_member: Set = ("group", "eng", "member").into();
+    let eng_admin: Set = ("group", "eng", "admin").into();
+    let foo_read: Set = ("application", "foo", "read").into();
+    let app_read: Set = ("application", WILDCARD_ID, "read").into();
+    let eng_admin_member = Intersection::new(
+        "group".to_string(),
+        "eng".to_string(),
+        vec!["admin".to_string(), "member".to_string()],
+    );
+
+    graph.insert(&alice, &eng_member).await;
+    graph.insert(&alice, &eng_admin).await;
+    graph.insert(&alice, &app_read).await;
+    graph.insert(&eng_member, &foo_read).await;
+    graph.insert(&eng_admin_member, &foo_read).await;
+
+    assert_eq!(
+        graph.direct_relations(&alice).await,
+        vec![app_read, eng_admin, eng_member.clone()]
+    );
+    assert_eq!(graph.direct_relations(&eng_member).await, vec![foo_read]);
+    assert!(graph.direct_relations(&bob).await.is_empty());
+}
+
+#[tokio::test]
+async fn intersection_deny() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+    let charlie: Object = ("user", "charlie").into();
+
+    let eng_member: Set = ("group", "eng", "member").into();
+    let eng_admin: Set = ("group", "eng", "admin").into();
+    let ops_member: Set = ("group", "ops", "member").into();
+    let x_read: Set = ("doc", "x", "read").into();
+    let eng_member_admin = Intersection::new(
+        "group".to_string(),
+        "eng".to_string(),
+        vec!["member".to_string(), "admin".to_string()],
+    );
+
+    graph.insert(&alice, &eng_member).await;
+    graph.insert(&bob, &eng_member).await;
+    graph.insert(&bob, &eng_admin).await;
+    graph.insert(&charlie, &eng_member).await;
+    graph.insert(&charlie, &ops_member).await;
+    graph.insert(&ops_member, &eng_admin).await;
+    graph.insert(&eng_member, &x_read).await;
+    graph.insert_deny(&eng_member_admin, &x_read).await;
+
+    // the deny only applies to subjects related to all sets of the intersection
+    assert!(graph.check(&alice, &x_read, None).await);
+    assert_eq!(
+        graph.reachable_sets(&alice, None).await,
+        vec![x_read.clone(), eng_member.clone()]
+    );
+    assert!(!graph.check(&bob, &x_read, None).await);
+    assert!(!graph.check(&charlie, &x_read, None).await);
+
+    // charlie is an admin through ops, which is out of the depth left at the intersection
+    assert!(graph.check(&charlie, &x_read, Some(2)).await);
+    assert!(!graph.check(&charlie, &x_read, Some(3)).await);
+}
+
+#[tokio::test]
+async fn intersection() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+    let charlie: Object = ("user", "charlie").into();
+
+    let eng_member: Set = ("group", "eng", "member").into();
+    let eng_admin: Set = ("group", "eng", "admin").into();
+    let ops_member: Set = ("group", "ops", "member").into();
+    let foo_write: Set = ("application", "foo", "write").into();
+    let eng_member_admin = Intersection::new(
+        "group".to_string(),
+        "eng".to_string(),
+        vec!["member".to_string(), "admin".to_string()],
+    );
+
+    graph.insert(&alice, &eng_member).await;
+    graph.insert(&alice, &eng_admin).await;
+    graph.insert(&bob, &eng_member).await;
+    graph.insert(&charlie, &ops_member).await;
+    graph.insert(&ops_member, &eng_admin).await;
+    graph.insert(&ops_member, &eng_member).await;
+    graph.insert(&eng_member_admin, &foo_write).await;
+
+    assert_eq!(
+        ObjectOrSet::try_from("group:eng#member&admin"),
+        Ok(eng_member_admin.clone().into())
+    );
+
+    assert!(graph.check(&alice, &foo_write, None).await);
+    assert!(!graph.check(&bob, &foo_write, None).await);
+    assert!(graph.check(&charlie, &foo_write, None).await);
+    assert_eq!(graph.reachable_sets(&bob, None).await, vec![eng_member]);
+
+    let mut expanded = graph
+        .expand(&foo_write)
+        .await
+        .into_iter()
+        .map(|x| x.0)
+        .collect::<Vec<_>>();
+    expanded.sort_by(|a, b| a.id().cmp(b.id()));
+    expanded.dedup();
+    assert_eq!(expanded, vec![alice.clone(), charlie]);
+
+    let mut savefile = vec![];
+    graph.write_savefile(&mut savefile).await.unwrap();
+    let read = RelationGraph::read_savefile(&mut savefile.as_slice())
+        .await
+        .unwrap();
+    assert!(read.check(&alice, &foo_write, None).await);
+    assert!(!read.check(&bob, &foo_write, None).await);
+
+    graph.remove(&eng_member_admin, &foo_write).await;
+    assert!(!graph.contains(&eng_member_admin).await);
+    assert!(!graph.check(&alice, &foo_write, None).await);
+}
+
+#[tokio::test]
+async fn try_check() {
+    let graph = RelationGraph::default().with_wildcards(false);
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+    let sets: Vec<Set> = (0..5)
+        .map(|i| ("group", format!("g{i}").as_str(), "member").into())
+        .collect();
+
+    graph.insert(&alice, &sets[0]).await;
+    for pair in sets.windows(2) {
+        graph.insert(&pair[0], &pair[1]).await;
+    }
+    graph.insert(&bob, &sets[1]).await;
+
+    assert_eq!(graph.try_check(&alice, &sets[4], Some(5)).await, Ok(true));
+    assert_eq!(
+        graph.try_check(&alice, &sets[4], Some(4)).await,
+        Err(LimitReached)
+    );
+    assert!(!graph.check(&alice, &sets[4], Some(4)).await);
+    assert_eq!(graph.try_check(&bob, &sets[0], Some(4)).await, Ok(false));
+}
+
+#[test]
+fn set_object() {
+    let foo_read: Set = ("file", "foo", "read").into();
+    let foo_write: Set = ("file", "foo", "write").into();
+    let bar_read: Set = ("file", "bar", "read").into();
+    let foo: Object = ("file", "foo").into();
+    let bar: Object = ("file", "bar").into();
+
+    let mut grouped: HashMap<Object, Vec<Set>> = HashMap::new();
+    for set in [foo_read.clone(), bar_read.clone(), foo_write.clone()] {
+        grouped.entry(set.object()).or_default().push(set);
+    }
+
+    assert_eq!(foo_read.object(), foo);
+    assert_eq!(grouped.len(), 2);
+    assert_eq!(grouped[&foo], vec![foo_read, foo_write]);
+    assert_eq!(grouped[&bar], vec![bar_read]);
+}
+
+#[tokio::test]
+async fn bidirectional_check() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+    let secret_read: Set = ("file", "secret", "read").into();
+
+    // alice is related to many sets, but none of them leads to `secret_read`
+    for i in 0..20 {
+        let group: Set = ("group", format!("g{i}").as_str(), "member").into();
+        graph.insert(&alice, &group).await;
+        for j in 0..20 {
+            let file: Set = ("file", format!("f{i}-{j}").as_str(), "read").into();
+            graph.insert(&group, &file).await;
+        }
+    }
+    graph.insert(&bob, &secret_read).await;
+
+    let (meeting, visited) = graph.meet(&(&alice).into(), &secret_read, None).await;
+    assert_eq!(meeting, Meeting::Disjoint);
+    assert!(visited < graph.reachable_sets(&alice, None).await.len() / 10);
+    assert!(!graph.check(&alice, &secret_read, None).await);
+
+    // the limit is shared by both directions
+    let chain: Vec<Set> = (0..4)
+        .map(|i| ("group", format!("c{i}").as_str(), "member").into())
+        .collect();
+    graph.insert(&bob, &chain[0]).await;
+    for i in 1..chain.len() {
+        graph.insert(&chain[i - 1], &chain[i]).await;
+    }
+    graph.insert(&chain[3], &secret_read).await;
+    for limit in 0..7 {
+        assert_eq!(
+            graph.check(&bob, &chain[3], Some(limit)).await,
+            graph
+                .check_path(&bob, &chain[3], Some(limit))
+                .await
+                .is_some()
+        );
+        assert_eq!(graph.check(&bob, &chain[3], Some(limit)).await, limit >= 4,);
+        let (meeting, _) = graph.meet(&(&bob).into(), &chain[3], Some(limit)).await;
+        let try_check = graph.try_check(&bob, &chain[3], Some(limit)).await;
+        match limit >= 4 {
+            true => assert_eq!((meeting, try_check), (Meeting::Related, Ok(true))),
+            false => assert_eq!((meeting, try_check), (Meeting::Limited, Err(LimitReached))),
+        }
+    }
+    // a missing relation is decided before the limit, unlike in the search from `src` alone
+    let other_read: Set = ("file", "other", "read").into();
+    graph.insert(&bob, &other_read).await;
+    assert_eq!(
+        graph.try_check(&alice, &other_read, Some(1)).await,
+        Ok(false)
+    );
+    assert_eq!(
+        graph.try_check_path(&alice, &other_read, Some(1)).await,
+        Err(LimitReached)
+    );
+
+    let (meeting, _) = graph.meet(&(&bob).into(), &chain[3], None).await;
+    assert_eq!(meeting, Meeting::Related);
+    graph.insert_deny(&alice, &secret_read).await;
+    let (meeting, _) = graph.meet(&(&bob).into(), &chain[3], None).await;
+    assert_eq!(meeting, Meeting::Unconfirmed);
+    assert!(graph.check(&bob, &chain[3], None).await);
+}
+
+#[tokio::test]
+async fn check_path() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+    let charlie: Object = ("user", "charlie").into();
+
+    let eng_member: Set = ("group", "eng", "member").into();
+    let staff_member: Set = ("group", "staff", "member").into();
+    let foo_read: Set = ("file", "foo", "read").into();
+    let file_read: Set = ("file", WILDCARD_ID, "read").into();
+
+    graph.insert(&alice, &foo_read).await;
+    graph.insert(&bob, &eng_member).await;
+    graph.insert(&eng_member, &staff_member).await;
+    graph.insert(&staff_member, &foo_read).await;
+    graph.insert(&charlie, &file_read).await;
+
+    assert_eq!(
+        graph.check_path(&alice, &foo_read, None).await,
+        Some(vec![])
+    );
+    assert_eq!(
+        graph.check_path(&bob, &foo_read, None).await,
+        Some(vec![eng_member.clone(), staff_member.clone()])
+    );
+    assert_eq!(
+        graph
+            .check_path(&charlie, &("file", "bar", "read").into(), None)
+            .await,
+        Some(vec![file_read.clone()])
+    );
+    assert_eq!(
+        graph.check_path(&charlie, &foo_read, None).await,
+        Some(vec![file_read])
+    );
+    assert_eq!(
+        graph.check_path(&eng_member, &foo_read, None).await,
+        Some(vec![staff_member])
+    );
+    assert_eq!(graph.check_path(&bob, &foo_read, Some(2)).await, None);
+    assert_eq!(
+        graph
+            .check_path(&alice, &("file", "bar", "read").into(), None)
+            .await,
+        None
+    );
+
+    graph.insert_deny(&bob, &foo_read).await;
+    assert_eq!(graph.check_path(&bob, &foo_read, None).await, None);
+}
+
+#[tokio::test]
+async fn check_via() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+
+    let eng_member: Set = ("group", "eng", "member").into();
+    let ops_member: Set = ("group", "ops", "member").into();
+    let foo_read: Set = ("application", "foo", "read").into();
+
+    graph.insert(&alice, &eng_member).await;
+    graph.insert(&alice, &ops_member).await;
+    graph.insert(&bob, &ops_member).await;
+    graph.insert(&eng_member, &foo_read).await;
+
+    assert!(graph.check_via(&alice, &foo_read, &eng_member, None).await);
+    assert!(!graph.check_via(&alice, &foo_read, &ops_member, None).await);
+    assert!(!graph.check_via(&bob, &foo_read, &eng_member, None).await);
+
+    graph.insert_deny(&alice, &foo_read).await;
+    assert!(!graph.check_via(&alice, &foo_read, &eng_member, None).await);
+}
+
+#[tokio::test]
+async fn audit_check() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+
+    let eng_member: Set = ("group", "eng", "member").into();
+    let foo_read: Set = ("file", "foo", "read").into();
+    let bar_read: Set = ("file", "bar", "read").into();
+    let file_read: Set = ("file", WILDCARD_ID, "read").into();
+    let file_write: Set = ("file", WILDCARD_ID, "write").into();
+    let folder_view: Set = ("folder", WILDCARD_ID, "view").into();
+
+    graph.insert(&alice, &file_read).await;
+    graph.insert(&alice, &file_write).await;
+    graph.insert(&alice, &eng_member).await;
+    graph.insert(&eng_member, &folder_view).await;
+    graph.insert(&folder_view, &foo_read).await;
+    graph.insert(&bob, &foo_read).await;
+
+    assert_eq!(
+        graph.audit_check(&alice, &foo_read, None).await,
+        vec![file_read.clone(), folder_view]
+    );
+    assert_eq!(
+        graph.audit_check(&alice, &bar_read, None).await,
+        vec![file_read]
+    );
+    assert!(graph.audit_check(&bob, &foo_read, None).await.is_empty());
+    assert!(graph.audit_check(&bob, &bar_read, None).await.is_empty());
+}
+
+#[tokio::test]
+async fn ancestor_sets() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+
+    let eng_member: Set = ("group", "eng", "member").into();
+    let ops_member: Set = ("group", "ops", "member").into();
+    let staff_member: Set = ("group", "staff", "member").into();
+    let foo_read: Set = ("file", "foo", "read").into();
+    let file_read: Set = ("file", WILDCARD_ID, "read").into();
+
+    graph.insert(&alice, &eng_member).await;
+    graph.insert(&alice, &ops_member).await;
+    graph.insert(&alice, &staff_member).await;
+    graph.insert(&eng_member, &foo_read).await;
+    graph.insert(&ops_member, &file_read).await;
+
+    assert_eq!(
+        graph.ancestor_sets(&alice, &foo_read, None).await,
+        vec![
+            file_read.clone(),
+            foo_read.clone(),
+            eng_member,
+            ops_member.clone()
+        ]
+    );
+    assert_eq!(
+        graph
+            .ancestor_sets(&alice, &("file", "bar", "read").into(), None)
+            .await,
+        vec![file_read, ("file", "bar", "read").into(), ops_member]
+    );
+    assert!(graph.ancestor_sets(&bob, &foo_read, None).await.is_empty());
+}
+
+#[tokio::test]
+async fn ancestor_sets_limit() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let a_member: Set = ("group", "a", "member").into();
+    let detour: Vec<Set> = (1..=3)
+        .map(|i| ("group", format!("x{i}").as_str(), "member").into())
+        .collect();
+    let foo_read: Set = ("file", "foo", "read").into();
+
+    // alice -> a -> foo and the longer detour a -> x1 -> x2 -> x3 -> foo
+    graph.insert(&alice, &a_member).await;
+    graph.insert(&a_member, &foo_read).await;
+    graph.insert(&a_member, &detour[0]).await;
+    graph.insert(&detour[0], &detour[1]).await;
+    graph.insert(&detour[1], &detour[2]).await;
+    graph.insert(&detour[2], &foo_read).await;
+
+    let mut all = vec![foo_read.clone(), a_member.clone()];
+    all.extend(detour.iter().cloned());
+    assert_eq!(graph.ancestor_sets(&alice, &foo_read, None).await, all);
+    assert_eq!(graph.ancestor_sets(&alice, &foo_read, Some(5)).await, all);
+
+    // x1 and x2 are within the limit from alice and to foo, but not on a path within it
+    assert!(graph.check(&alice, &foo_read, Some(3)).await);
+    assert_eq!(
+        graph.ancestor_sets(&alice, &foo_read, Some(3)).await,
+        vec![foo_read.clone(), a_member]
+    );
+}
+
+#[tokio::test]
+async fn is_member() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+    let eng: Object = ("group", "eng").into();
+    let staff: Object = ("group", "staff").into();
+
+    graph
+        .insert(&alice, &("group", "eng", "member").into())
+        .await;
+    graph
+        .insert(
+            &Set::from(("group", "eng", "member")),
+            &("group", "staff", "member").into(),
+        )
+        .await;
+    graph.insert(&bob, &("group", "eng", "admin").into()).await;
+
+    assert!(graph.is_member(&alice, &eng, "member", None).await);
+    assert!(graph.is_member(&alice, &staff, "member", None).await);
+    assert!(!graph.is_member(&alice, &eng, "admin", None).await);
+    assert!(!graph.is_member(&bob, &eng, "member", None).await);
+    assert!(graph.is_member(&bob, &eng, "admin", None).await);
+    assert!(!graph.is_member(&alice, &eng, "", None).await);
+    assert!(!graph.is_member(&alice, &eng, "member&admin", None).await);
+}
+
+#[tokio::test]
+async fn savefile_read_error() {
+    let read = |savefile: &'static str| async move {
+        match RelationGraph::read_savefile(&mut savefile.as_bytes()).await {
+            Ok(_) => panic!("malformed savefile was read: {savefile:?}"),
+            Err(err) => err,
+        }
+    };
+
+    // truncated while writing
+    let err = read("[file:foo]\nread = [ user:alice, us").await;
+    assert!(matches!(err, SavefileError::MalformedLine { line: 2 }));
+    let err = read("[file:foo]\nread = [ user:alice ]\n\n[fil").await;
+    assert!(matches!(err, SavefileError::MalformedLine { line: 4 }));
+
+    // garbage
+    let err = read("# rebacs-version=1 revision=3\nhello world\n").await;
+    assert!(matches!(err, SavefileError::MalformedLine { line: 2 }));
+    let err = read("read = [ user:alice ]\n").await;
+    assert!(matches!(err, SavefileError::MissingSection { line: 1 }));
+    let err = read("[user]\n").await;
+    assert!(matches!(
+        err,
+        SavefileError::InvalidSection {
+            line: 1,
+            source: ParseError::MissingSeparator
+        }
+    ));
+    let err = read("[:alice]\n").await;
+    assert!(matches!(
+        err,
+        SavefileError::InvalidSection {
+            line: 1,
+            source: ParseError::EmptyNamespace
+        }
+    ));
+    let err = read("[file:foo]\n = [ user:alice ]\n").await;
+    assert!(matches!(
+        err,
+        SavefileError::InvalidRelation {
+            line: 2,
+            source: ParseError::EmptyRelation
+        }
+    ));
+    let err = read("[file:foo]\nread = [ user:alice, alice ]\n").await;
+    assert!(matches!(
+        err,
+        SavefileError::InvalidSource {
+            line: 2,
+            ref src,
+            source: ParseError::MissingSeparator
+        } if src == "alice"
+    ));
+    assert_eq!(
+        err.to_string(),
+        "line 2: invalid source `alice`: missing `:` between namespace and id"
+    );
+
+    let err = RelationGraph::read_savefiles(&["/nonexistent/graph.dat"])
+        .await
+        .err()
+        .unwrap();
+    assert!(matches!(err, SavefileError::Io(_)));
+}
+
+#[tokio::test]
+async fn save_to_path() {
+    let dir = std::env::temp_dir().join(format!("rebacdb-save-{}", std::process::id()));
+    tokio::fs::create_dir_all(&dir).await.unwrap();
+    let path = dir.join("graph.dat");
+
+    let alice: Object = ("user", "alice").into();
+    let foo_read: Set = ("application", "foo", "read").into();
+    let bar_read: Set = ("application", "bar", "read").into();
+
+    let graph = RelationGraph::default();
+    graph.insert(&alice, &foo_read).await;
+    graph.save_to_path(&path).await.unwrap();
+    let saved = tokio::fs::read(&path).await.unwrap();
+    assert!(!dir.join("graph.dat.tmp").exists());
+
+    // the temporary file can't be written, e.g. because the disk is full
+    graph.insert(&alice, &bar_read).await;
+    tokio::fs::create_dir(dir.join("graph.dat.tmp"))
+        .await
+        .unwrap();
+    assert!(graph.save_to_path(&path).await.is_err());
+    assert_eq!(tokio::fs::read(&path).await.unwrap(), saved);
+
+    tokio::fs::remove_dir(dir.join("graph.dat.tmp"))
+        .await
+        .unwrap();
+    graph.save_to_path(&path).await.unwrap();
+    let mut reader = tokio::io::BufReader::new(tokio::fs::File::open(&path).await.unwrap());
+    let read = RelationGraph::read_savefile(&mut reader).await.unwrap();
+    assert!(read.check(&alice, &bar_read, None).await);
+
+    tokio::fs::remove_dir_all(&dir).await.unwrap();
+}
+
+#[tokio::test]
+async fn savefile_write_error() {
+    let graph = RelationGraph::default();
+    graph
+        .insert(
+            &Object::from(("user", "alice")),
+            &("application", "foo", "read").into(),
+        )
+        .await;
+
+    let mut buf = [0u8; 16];
+    let err = graph
+        .write_savefile(&mut std::io::Cursor::new(&mut buf[..]))
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::WriteZero);
+}
+
+#[tokio::test]
+async fn count_objects_with_relation() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+
+    graph.insert(&alice, &("file", "foo", "owner").into()).await;
+    graph.insert(&bob, &("file", "foo", "owner").into()).await;
+    graph.insert(&bob, &("file", "bar", "owner").into()).await;
+    graph.insert(&bob, &("file", "baz", "read").into()).await;
+    graph
+        .insert(&alice, &("file", WILDCARD_ID, "owner").into())
+        .await;
+    graph.insert(&alice, &("dir", "foo", "owner").into()).await;
+    graph
+        .insert(&alice, &("files", "foo", "owner").into())
+        .await;
+    graph.insert(&alice, &("fil", "foo", "owner").into()).await;
+
+    assert_eq!(graph.count_objects_with_relation("file", "owner").await, 2);
+    assert_eq!(graph.count_objects_with_relation("file", "read").await, 1);
+    assert_eq!(graph.count_objects_with_relation("dir", "owner").await, 1);
+    assert_eq!(graph.count_objects_with_relation("file", "write").await, 0);
+}
+
+#[tokio::test]
+async fn vertex_and_edge_count() {
+    let alice: Object = ("user", "alice").into();
+    let eng_member: Set = ("group", "eng", "member").into();
+    let foo_read: Set = ("file", "foo", "read").into();
+
+    let graph = RelationGraph::default();
+    assert_eq!(graph.vertex_count().await, 0);
+    assert_eq!(graph.edge_count(true).await, 0);
+
+    graph.insert(&alice, &foo_read).await;
+    graph.insert(&eng_member, &foo_read).await;
+    assert_eq!(graph.vertex_count().await, 6);
+    assert_eq!(graph.edge_count(true).await, 5);
+    assert_eq!(graph.edge_count(false).await, 2);
+
+    let graph = RelationGraph::default().with_wildcards(false);
+    graph.insert(&alice, &foo_read).await;
+    graph.insert(&eng_member, &foo_read).await;
+    assert_eq!(graph.vertex_count().await, 3);
+    assert_eq!(graph.edge_count(true).await, 2);
+    assert_eq!(graph.edge_count(false).await, 2);
+}
+
+#[tokio::test]
+async fn contains_relation() {
+    let graph = RelationGraph::default();
+
+    let root: Object = ("dir", "root").into();
+    let home: Object = ("dir", "home").into();
+    let notes: Object = ("file", "notes").into();
+    let other: Object = ("file", "other").into();
+
+    assert!(graph.insert_contains(&root, &home).await);
+    assert!(graph.insert_contains(&home, &notes).await);
+    assert!(!graph.insert_contains(&home, &notes).await);
+
+    assert!(graph.is_contained(&notes, &home, None).await);
+    assert!(graph.is_contained(&notes, &root, None).await);
+    assert!(!graph.is_contained(&notes, &root, Some(1)).await);
+    assert!(!graph.is_contained(&root, &home, None).await);
+    assert!(!graph.is_contained(&other, &root, None).await);
+
+    assert!(
+        graph
+            .has(
+                &Set::from(("file", "notes", CONTAINS_RELATION)),
+                &("dir", "home", CONTAINS_RELATION).into()
+            )
+            .await
+    );
+
+    assert!(graph.remove_contains(&root, &home).await);
+    assert!(!graph.is_contained(&notes, &root, None).await);
+}
+
+#[tokio::test]
+async fn relations_on() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let foo: Object = ("file", "foo").into();
+
+    graph
+        .insert(&alice, &("file", "foo", "viewer").into())
+        .await;
+    graph.insert(&alice, &("file", "foo", "owner").into()).await;
+    graph
+        .insert(&alice, &("file", "foo2", "editor").into())
+        .await;
+    graph.insert(&alice, &("file", "fo", "editor").into()).await;
+
+    assert_eq!(graph.relations_on(&foo).await, vec!["owner", "viewer"]);
+    assert!(graph.relations_on(&alice).await.is_empty());
+    assert!(graph.relations_on(&("file", "bar").into()).await.is_empty());
+}
+
+#[tokio::test]
+async fn computed_relations() {
+    let schema = HashMap::from([
+        (
+            ("doc".to_string(), "owner".to_string()),
+            vec!["editor".to_string()],
+        ),
+        (
+            ("doc".to_string(), "editor".to_string()),
+            vec!["viewer".to_string(), "commenter".to_string()],
+        ),
+        (
+            ("folder".to_string(), "editor".to_string()),
+            vec!["viewer".to_string()],
+        ),
+        // cyclic implications terminate
+        (
+            ("doc".to_string(), "commenter".to_string()),
+            vec!["editor".to_string()],
+        ),
+    ]);
+    let graph = RelationGraph::default().with_schema(schema);
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+    let charlie: Object = ("user", "charlie").into();
+
+    let foo_viewer: Set = ("doc", "foo", "viewer").into();
+    let foo_editor: Set = ("doc", "foo", "editor").into();
+    let foo_owner: Set = ("doc", "foo", "owner").into();
+    let bar_viewer: Set = ("doc", "bar", "viewer").into();
+    let folder_editor: Set = ("folder", "f", "editor").into();
+    let folder_viewer: Set = ("folder", "f", "viewer").into();
+
+    graph.insert(&alice, &foo_editor).await;
+    graph.insert(&bob, &foo_owner).await;
+    graph.insert(&charlie, &folder_editor).await;
+    graph.insert(&folder_viewer, &bar_viewer).await;
+
+    assert!(graph.check(&alice, &foo_viewer, None).await);
+    assert!(!graph.check(&alice, &foo_owner, None).await);
+    assert!(!graph.check(&alice, &bar_viewer, None).await);
+
+    // owner -> editor -> viewer
+    assert!(graph.check(&bob, &foo_viewer, None).await);
+    assert!(graph.check(&bob, &foo_editor, None).await);
+
+    // the implied set is traversed further
+    assert!(graph.check(&charlie, &bar_viewer, None).await);
+    assert_eq!(
+        graph.check_path(&charlie, &bar_viewer, None).await,
+        Some(vec![folder_editor.clone(), folder_viewer.clone()])
+    );
+    assert!(!graph.check(&charlie, &foo_viewer, None).await);
+
+    // implications are per namespace and only apply to graphs with a schema
+    assert!(
+        !graph
+            .check(&alice, &("folder", "foo", "viewer").into(), None)
+            .await
+    );
+    let graph = RelationGraph::default();
+    graph.insert(&alice, &foo_editor).await;
+    assert!(!graph.check(&alice, &foo_viewer, None).await);
+}
+
+#[tokio::test]
+async fn membership_cycle() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+    let charlie: Object = ("user", "charlie").into();
+
+    let a: Set = ("group", "a", "member").into();
+    let b: Set = ("group", "b", "member").into();
+    let c: Set = ("group", "c", "member").into();
+    let foo_read: Set = ("file", "foo", "read").into();
+
+    // a -> b -> a and a -> b -> c -> a
+    graph.insert(&a, &b).await;
+    graph.insert(&b, &a).await;
+    graph.insert(&b, &c).await;
+    graph.insert(&c, &a).await;
+    graph.insert(&alice, &a).await;
+    graph.insert(&bob, &c).await;
+    graph.insert(&alice, &b).await;
+    graph.insert(&b, &foo_read).await;
+
+    for group in [&a, &b, &c, &foo_read] {
+        assert!(graph.check(&alice, group, None).await);
+        assert!(graph.check(&bob, group, None).await);
+        assert!(!graph.check(&charlie, group, None).await);
+        assert!(graph.check_path(&charlie, group, None).await.is_none());
+    }
+    assert!(graph.check(&a, &c, None).await);
+    assert_eq!(
+        graph.check_path(&bob, &foo_read, None).await,
+        Some(vec![c.clone(), a.clone(), b.clone()])
+    );
+
+    // every member is expanded once, with the shortest path
+    let expanded = graph.expand(&foo_read).await;
+    assert_eq!(expanded.len(), 2);
+    assert!(expanded.contains(&(alice.clone(), vec![foo_read.clone(), b.clone()])));
+    assert!(expanded.contains(&(
+        bob.clone(),
+        vec![foo_read.clone(), b.clone(), a.clone(), c.clone()]
+    )));
+    assert_eq!(graph.expand(&a).await.len(), 2);
+    assert_eq!(
+        graph.reachable_sets(&alice, None).await,
+        vec![foo_read, a, b, c]
+    );
+}
+
+#[tokio::test]
+async fn check_cache() {
+    let graph = RelationGraph::default().with_check_cache(2);
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+    let foo_read: Set = ("file", "foo", "read").into();
+
+    graph.insert(&alice, &foo_read).await;
+    assert!(graph.check(&alice, &foo_read, None).await);
+    assert!(graph.check(&alice, &foo_read, None).await);
+    assert!(!graph.check(&bob, &foo_read, None).await);
+    assert_eq!(
+        graph.check_cache_stats(),
+        Some(CheckCacheStats { hits: 1, misses: 2 })
+    );
+
+    // changes invalidate the cached results
+    graph.insert(&bob, &foo_read).await;
+    assert!(graph.check(&bob, &foo_read, None).await);
+    graph.remove(&alice, &foo_read).await;
+    assert!(!graph.check(&alice, &foo_read, None).await);
+    assert_eq!(
+        graph.check_cache_stats(),
+        Some(CheckCacheStats { hits: 1, misses: 4 })
+    );
+
+    // the limit is part of the key
+    assert!(graph.check(&bob, &foo_read, Some(1)).await);
+    assert!(graph.check(&bob, &foo_read, Some(1)).await);
+    assert_eq!(graph.check_cache_stats().unwrap().hits, 2);
+
+    // the least recently used result of bob without a limit was evicted
+    assert!(!graph.check(&alice, &foo_read, None).await);
+    assert!(graph.check(&bob, &foo_read, None).await);
+    assert_eq!(
+        graph.check_cache_stats(),
+        Some(CheckCacheStats { hits: 3, misses: 6 })
+    );
+
+    assert_eq!(RelationGraph::default().check_cache_stats(), None);
+    assert_eq!(
+        RelationGraph::default()
+            .with_check_cache(0)
+            .check_cache_stats(),
+        None
+    );
+}
+
+#[tokio::test]
+async fn expand_tree() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+
+    let admins: Set = ("group", "admins", "member").into();
+    let eng: Set = ("group", "eng", "member").into();
+    let foo_read: Set = ("file", "foo", "read").into();
+    let bar_read: Set = ("file", "bar", "read").into();
+
+    graph.insert(&alice, &eng).await;
+    graph.insert(&bob, &admins).await;
+    graph.insert(&admins, &eng).await;
+    graph.insert(&eng, &admins).await;
+    graph.insert(&eng, &foo_read).await;
+    graph.insert(&admins, &foo_read).await;
+
+    let node = |value: ObjectOrSet<'static>, children| ExpandNode {
+        value,
+        children,
+        reference: false,
+    };
+    let leaf = |value: ObjectOrSet<'static>| ExpandNode {
+        value,
+        children: vec![],
+        reference: false,
+    };
+    let reference = |value: ObjectOrSet<'static>| ExpandNode {
+        value,
+        children: vec![],
+        reference: true,
+    };
+
+    // each group is only expanded at its first node, which also cuts the cycle
+    assert_eq!(
+        graph.expand_tree(&foo_read).await,
+        node(
+            foo_read.clone().into(),
+            vec![
+                node(
+                    admins.clone().into(),
+                    vec![
+                        node(
+                            eng.clone().into(),
+                            vec![reference(admins.clone().into()), leaf(alice.clone().into())]
+                        ),
+                        leaf(bob.clone().into()),
+                    ]
+                ),
+                reference(eng.clone().into()),
+            ]
+        )
+    );
+
+    assert_eq!(
+        graph.expand_tree(&bar_read).await,
+        leaf(bar_read.clone().into())
+    );
+}
+
+#[tokio::test]
+async fn expand_tree_diamonds() {
+    let graph = RelationGraph::default();
+
+    // every group of a layer is a member of both groups of the next one
+    let group = |layer: usize, x: &str| -> Set {
+        ("group", format!("{layer}{x}").as_str(), "member").into()
+    };
+    for layer in 0..20 {
+        for x in ["a", "b"] {
+            for y in ["a", "b"] {
+                graph.insert(&group(layer, x), &group(layer + 1, y)).await;
+            }
+        }
+    }
+
+    fn count(node: &ExpandNode) -> usize {
+        1 + node.children.iter().map(count).sum::<usize>()
+    }
+    fn expanded(node: &ExpandNode) -> usize {
+        let own = (!node.reference && !node.children.is_empty()) as usize;
+        own + node.children.iter().map(expanded).sum::<usize>()
+    }
+
+    // every group with members is expanded once instead of on each of the 2^20 paths
+    let tree = graph.expand_tree(&group(20, "a")).await;
+    assert_eq!(expanded(&tree), 1 + 2 * 19);
+    assert!(count(&tree) < 100, "{}", count(&tree));
+}
+
+#[tokio::test]
+async fn expand_tree_wildcard() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let foo_read: Set = ("file", "foo", "read").into();
+    let bar_read: Set = ("file", "bar", "read").into();
+    let any_read: Set = ("file", WILDCARD_ID, "read").into();
+
+    graph.insert(&alice, &foo_read).await;
+    assert_eq!(
+        graph.expand_tree(&bar_read).await.children,
+        Vec::<ExpandNode>::new()
+    );
+
+    // the wildcard is only part of the tree once something is related to it
+    graph.insert(&alice, &any_read).await;
+    let any_read_node = ExpandNode {
+        value: any_read.clone().into(),
+        children: vec![ExpandNode {
+            value: alice.clone().into(),
+            children: vec![],
+            reference: false,
+        }],
+        reference: false,
+    };
+    assert_eq!(
+        graph.expand_tree(&bar_read).await,
+        ExpandNode {
+            value: bar_read.clone().into(),
+            children: vec![any_read_node.clone()],
+            reference: false,
+        }
+    );
+    assert_eq!(
+        graph.expand_tree(&foo_read).await.children,
+        vec![
+            any_read_node,
+            ExpandNode {
+                value: alice.clone().into(),
+                children: vec![],
+                reference: false,
+            }
+        ]
+    );
+}
+
+#[tokio::test]
+async fn json_roundtrip() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+    let user_wildcard: Object = ("user", WILDCARD_ID).into();
+
+    let foo_read: Set = ("application", "foo", "read").into();
+    let app_read: Set = ("application", WILDCARD_ID, "read").into();
+    let eng_member: Set = ("group", "eng", "member").into();
+    let eng_admin_member = Intersection::new(
+        "group".to_string(),
+        "eng".to_string(),
+        vec!["admin".to_string(), "member".to_string()],
+    );
+
+    graph.insert(&alice, &eng_member).await;
+    graph.insert(&bob, &eng_member).await;
+    graph.insert(&eng_admin_member, &foo_read).await;
+    graph.insert(&user_wildcard, &app_read).await;
+    graph.insert_deny(&bob, &foo_read).await;
+
+    let json = graph.export_json().await;
+    assert_eq!(json["revision"], graph.current_revision());
+    assert!(json["relations"]
+        .as_array()
+        .unwrap()
+        .contains(&serde_json::json!({
+            "src": { "namespace": "group", "id": "eng", "relations": ["admin", "member"] },
+            "dst": { "namespace": "application", "id": "foo", "relation": "read" },
+        })));
+    assert!(json["relations"]
+        .as_array()
+        .unwrap()
+        .contains(&serde_json::json!({
+            "src": { "namespace": "user", "id": "bob" },
+            "dst": { "namespace": "application", "id": "foo", "relation": "read" },
+            "deny": true,
+        })));
+
+    // the imported graph is equivalent to the one the savefile loader reads
+    let imported = RelationGraph::import_json(&json).await.unwrap();
+    assert_eq!(imported.export_json().await, json);
+
+    let mut savefile = vec![];
+    graph.write_savefile(&mut savefile).await.unwrap();
+    let read = RelationGraph::read_savefile(&mut savefile.as_slice())
+        .await
+        .unwrap();
+    let mut imported_savefile = vec![];
+    imported
+        .write_savefile(&mut imported_savefile)
+        .await
+        .unwrap();
+    assert_eq!(savefile, imported_savefile);
+    assert_eq!(read.export_json().await, json);
+
+    assert!(imported.check(&alice, &app_read, None).await);
+    assert!(!imported.check(&bob, &foo_read, None).await);
+}
+
+#[tokio::test]
+async fn json_import_error() {
+    let import = |value: serde_json::Value| async move {
+        match RelationGraph::import_json(&value).await {
+            Ok(_) => panic!("malformed graph was imported: {value}"),
+            Err(err) => err,
+        }
+    };
+
+    let alice = serde_json::json!({ "namespace": "user", "id": "alice" });
+    let foo_read = serde_json::json!({ "namespace": "file", "id": "foo", "relation": "read" });
+
+    assert_eq!(
+        import(serde_json::json!([])).await,
+        JsonError::MalformedGraph
+    );
+    assert_eq!(
+        import(serde_json::json!({ "relations": [{ "src": alice }] })).await,
+        JsonError::MalformedRelation { index: 0 }
+    );
+    assert_eq!(
+        import(serde_json::json!({ "relations": [
+            { "src": alice, "dst": foo_read },
+            { "src": alice, "dst": foo_read, "deny": "yes" },
+        ] }))
+        .await,
+        JsonError::MalformedRelation { index: 1 }
+    );
+    assert_eq!(
+        import(serde_json::json!({ "relations": [
+            { "src": { "namespace": "user", "id": "" }, "dst": foo_read },
+        ] }))
+        .await,
+        JsonError::InvalidSource {
+            index: 0,
+            source: ParseError::EmptyId
+        }
+    );
+    assert_eq!(
+        import(serde_json::json!({ "relations": [{ "src": alice, "dst": alice }] })).await,
+        JsonError::InvalidDestination {
+            index: 0,
+            source: ParseError::MissingRelation
+        }
+    );
+}
+
+#[tokio::test]
+async fn diamond_relations() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let a: Set = ("group", "a", "member").into();
+    let b: Set = ("group", "b", "member").into();
+    let c: Set = ("group", "c", "member").into();
+    let x: Set = ("group", "x", "member").into();
+    let foo_read: Set = ("file", "foo", "read").into();
+
+    // alice reaches c through a, b and the longer detour a -> x -> c
+    graph.insert(&alice, &a).await;
+    graph.insert(&alice, &b).await;
+    graph.insert(&a, &x).await;
+    graph.insert(&x, &c).await;
+    graph.insert(&a, &c).await;
+    graph.insert(&b, &c).await;
+    graph.insert(&c, &foo_read).await;
+
+    // the target is reached through every branch of the diamond, the shortest path is returned
+    assert!(graph.check(&alice, &foo_read, None).await);
+    let path = graph.check_path(&alice, &foo_read, None).await.unwrap();
+    assert!(path == vec![a.clone(), c.clone()] || path == vec![b.clone(), c.clone()]);
+    // the longer detour doesn't hide the short path from a depth limit
+    assert_eq!(
+        graph.try_check(&alice, &foo_read, Some(2)).await,
+        Err(LimitReached)
+    );
+    assert_eq!(graph.try_check(&alice, &foo_read, Some(3)).await, Ok(true));
+    assert_eq!(
+        graph
+            .check_path(&alice, &foo_read, Some(3))
+            .await
+            .map(|x| x.len()),
+        Some(2)
+    );
+
+    // removing one branch keeps the other
+    graph.remove(&a, &c).await;
+    graph.remove(&b, &c).await;
+    assert!(graph.check(&alice, &foo_read, None).await);
+    assert_eq!(
+        graph.check_path(&alice, &foo_read, None).await,
+        Some(vec![a.clone(), x.clone(), c.clone()])
+    );
+}
+
+#[tokio::test]
+async fn remove_namespace() {
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+    let user_wildcard: Object = ("user", WILDCARD_ID).into();
+    let eng_member: Set = ("group", "eng", "member").into();
+    let eng_admin_member = Intersection::new(
+        "group".to_string(),
+        "eng".to_string(),
+        vec!["admin".to_string(), "member".to_string()],
+    );
+
+    let a_foo_read: Set = ("a_file", "foo", "read").into();
+    let a_bar_read: Set = ("a_file", "bar", "read").into();
+    let a_any_read: Set = ("a_file", WILDCARD_ID, "read").into();
+    let b_foo_read: Set = ("b_file", "foo", "read").into();
+
+    let populate = |graph: RelationGraph, with_a: bool| {
+        let (alice, bob, user_wildcard) = (&alice, &bob, &user_wildcard);
+        let (eng_member, eng_admin_member) = (&eng_member, &eng_admin_member);
+        let (a_foo_read, a_bar_read, a_any_read, b_foo_read) =
+            (&a_foo_read, &a_bar_read, &a_any_read, &b_foo_read);
+        async move {
+            graph.insert(bob, eng_member).await;
+            graph.insert(eng_member, b_foo_read).await;
+            graph.insert(alice, b_foo_read).await;
+            if with_a {
+                graph.insert(alice, a_foo_read).await;
+                graph.insert(eng_member, a_foo_read).await;
+                graph.insert(eng_admin_member, a_bar_read).await;
+                graph.insert(user_wildcard, a_any_read).await;
+                graph.insert(a_bar_read, a_foo_read).await;
+                graph.insert_deny(bob, a_foo_read).await;
+            }
+            graph
+        }
+    };
+
+    let graph = populate(RelationGraph::default(), true).await;
+    let revision = graph.current_revision();
+    assert_eq!(graph.remove_namespace("a_file").await, 6);
+    assert_eq!(graph.current_revision(), revision + 6);
+    assert_eq!(graph.remove_namespace("a_file").await, 0);
+
+    // the graph is the same as one that never had the namespace
+    let expected = populate(RelationGraph::default(), false).await;
+    for namespace in ["a_file", "b_file", "group", "user"] {
+        assert_eq!(
+            graph.list_relations(namespace).await,
+            expected.list_relations(namespace).await,
+            "{namespace}"
+        );
+    }
+    assert_eq!(graph.vertex_count().await, expected.vertex_count().await);
+    assert_eq!(
+        graph.edge_count(true).await,
+        expected.edge_count(true).await
+    );
+    assert_eq!(graph.compact().await, 0);
+
+    assert!(!graph.check(&alice, &a_foo_read, None).await);
+    assert!(!graph.check(&alice, &a_any_read, None).await);
+    assert!(graph.check(&alice, &b_foo_read, None).await);
+    assert!(graph.check(&bob, &b_foo_read, None).await);
+
+    // the deny relation was removed with the namespace
+    graph.insert(&eng_member, &a_foo_read).await;
+    assert!(graph.check(&bob, &a_foo_read, None).await);
+}
+
+#[tokio::test]
+async fn rename_object() {
+    let graph = RelationGraph::default();
+
+    let old: Object = ("user", "alice@example.com").into();
+    let new: Object = ("user", "3f2a").into();
+    let bob: Object = ("user", "bob").into();
+    let charlie: Object = ("user", "charlie").into();
+    let friends = |user: &Object| -> Set { (user.namespace(), user.id(), "friend").into() };
+    let colleagues = |user: &Object| -> Set { (user.namespace(), user.id(), "colleague").into() };
+    let friend_colleagues = |user: &Object| {
+        Intersection::new(
+            user.namespace().to_string(),
+            user.id().to_string(),
+            vec!["friend".to_string(), "colleague".to_string()],
+        )
+    };
+
+    let eng_member: Set = ("group", "eng", "member").into();
+    let foo_read: Set = ("file", "foo", "read").into();
+    let bar_read: Set = ("file", "bar", "read").into();
+    let baz_read: Set = ("file", "baz", "read").into();
+
+    graph.insert(&old, &eng_member).await;
+    graph.insert(&eng_member, &foo_read).await;
+    graph.insert_deny(&old, &bar_read).await;
+    graph.insert(&eng_member, &bar_read).await;
+    graph.insert(&bob, &friends(&old)).await;
+    graph.insert(&bob, &colleagues(&old)).await;
+    graph.insert(&charlie, &friends(&old)).await;
+    graph.insert(&friends(&old), &baz_read).await;
+    graph.insert(&friend_colleagues(&old), &foo_read).await;
+
+    let checks = |user: &Object| -> Vec<(ObjectOrSet<'static>, Set)> {
+        vec![
+            (user.clone().into(), eng_member.clone()),
+            (user.clone().into(), foo_read.clone()),
+            (user.clone().into(), bar_read.clone()),
+            (user.clone().into(), baz_read.clone()),
+            (bob.clone().into(), friends(user)),
+            (bob.clone().into(), foo_read.clone()),
+            (charlie.clone().into(), baz_read.clone()),
+            (charlie.clone().into(), foo_read.clone()),
+            (friends(user).into(), baz_read.clone()),
+        ]
+    };
+    let mut before = vec![];
+    for (src, dst) in checks(&old) {
+        before.push(graph.check(&src, &dst, None).await);
+    }
+    assert_eq!(
+        before,
+        vec![true, true, false, false, true, true, true, false, true]
+    );
+
+    assert_eq!(graph.rename_object("user", old.id(), new.id()).await, 7);
+
+    // the new id has the relations of the old one, the old one has none left
+    let mut after = vec![];
+    for (src, dst) in checks(&new) {
+        after.push(graph.check(&src, &dst, None).await);
+    }
+    assert_eq!(after, before);
+    for (src, dst) in checks(&old) {
+        if src.id() == old.id() {
+            assert!(!graph.check(&src, &dst, None).await);
+        }
+    }
+    assert!(graph.direct_relations(&old).await.is_empty());
+    assert!(graph.direct_members(&friends(&old)).await.is_empty());
+    assert_eq!(graph.compact().await, 0);
+
+    assert_eq!(graph.rename_object("user", old.id(), new.id()).await, 0);
+    assert_eq!(graph.rename_object("user", new.id(), new.id()).await, 0);
+}
+
+#[tokio::test]
+async fn savefile_gz_roundtrip() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+    let foo_read: Set = ("application", "foo", "read").into();
+    let eng_member: Set = ("group", "eng", "member").into();
+
+    graph.insert(&alice, &foo_read).await;
+    graph.insert(&bob, &eng_member).await;
+    graph.insert(&eng_member, &foo_read).await;
+    graph.insert_deny(&bob, &foo_read).await;
+
+    let path = std::env::temp_dir().join(format!("rebacs-graph-{}.dat.gz", std::process::id()));
+    let mut file = tokio::fs::File::create(&path).await.unwrap();
+    graph.write_savefile_gz(&mut file).await.unwrap();
+    drop(file);
+
+    // the file is a gzip member and decompresses to the plain savefile
+    let compressed = tokio::fs::read(&path).await.unwrap();
+    assert_eq!(compressed[..2], [0x1f, 0x8b]);
+    let mut file = tokio::fs::File::open(&path).await.unwrap();
+    let read = RelationGraph::read_savefile_gz(&mut file).await.unwrap();
+    tokio::fs::remove_file(&path).await.unwrap();
+
+    let mut expected = vec![];
+    graph.write_savefile(&mut expected).await.unwrap();
+    let mut savefile = vec![];
+    read.write_savefile(&mut savefile).await.unwrap();
+    assert_eq!(savefile, expected);
+    assert!(read.check(&alice, &foo_read, None).await);
+    assert!(!read.check(&bob, &foo_read, None).await);
+
+    // a plain savefile is not a valid gzip stream
+    assert!(matches!(
+        RelationGraph::read_savefile_gz(&mut expected.as_slice()).await,
+        Err(SavefileError::Io(_))
+    ));
+}
+
+#[tokio::test]
+async fn validate() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+    let eng_member: Set = ("group", "eng", "member").into();
+    let foo_read: Set = ("file", "foo", "read").into();
+    let bar_read: Set = ("file", "bar", "read").into();
+    let baz_read: Set = ("file", "baz", "read").into();
+    let file_read: Set = ("file", WILDCARD_ID, "read").into();
+    let eng_admin_member = Intersection::new(
+        "group".to_string(),
+        "eng".to_string(),
+        vec!["admin".to_string(), "member".to_string()],
+    );
+
+    graph.insert(&alice, &eng_member).await;
+    graph.insert(&bob, &eng_member).await;
+    graph.insert(&eng_member, &foo_read).await;
+    graph.insert(&eng_admin_member, &bar_read).await;
+    graph
+        .insert(&Object::from(("user", WILDCARD_ID)), &bar_read)
+        .await;
+    graph.insert_deny(&bob, &foo_read).await;
+    graph.remove(&alice, &eng_member).await;
+    assert_eq!(graph.validate().await, Ok(()));
+
+    let mut savefile = vec![];
+    graph.write_savefile(&mut savefile).await.unwrap();
+    let read = RelationGraph::read_savefile(&mut savefile.as_slice())
+        .await
+        .unwrap();
+    assert_eq!(read.validate().await, Ok(()));
+
+    // corrupt the edges of the graph directly
+    let vertex = |id: &Set| {
+        let graph = &graph;
+        let id = id.clone();
+        async move {
+            graph
+                .verticies
+                .read()
+                .await
+                .get(id.vertex_id())
+                .unwrap()
+                .clone()
+        }
+    };
+    let eng_vertex = vertex(&eng_member).await;
+    let foo_vertex = vertex(&foo_read).await;
+    foo_vertex.edges_in.write().await.remove(&eng_vertex);
+    let bob_vertex = graph
+        .verticies
+        .read()
+        .await
+        .get(bob.vertex_id())
+        .unwrap()
+        .clone();
+    bob_vertex.denies_out.write().await.remove(&foo_vertex);
+
+    let wildcard_vertex = vertex(&file_read).await;
+    let bar_vertex = vertex(&bar_read).await;
+    bar_vertex.edges_in.write().await.remove(&wildcard_vertex);
+    wildcard_vertex.edges_out.write().await.remove(&bar_vertex);
+
+    let unknown = Arc::new(super::Vertex::new(baz_read.vertex_id().clone()));
+    bar_vertex.edges_out.write().await.insert(unknown.clone());
+    let orphan: Set = ("file", "qux", "read").into();
+    graph
+        .verticies
+        .write()
+        .await
+        .insert(Arc::new(super::Vertex::new(orphan.vertex_id().clone())));
+
+    let inconsistencies = graph.validate().await.unwrap_err();
+    for inconsistency in [
+        GraphInconsistency::MissingEdgeIn {
+            src: eng_member.clone().into(),
+            dst: foo_read.clone().into(),
+            deny: false,
+        },
+        GraphInconsistency::MissingEdgeOut {
+            src: bob.clone().into(),
+            dst: foo_read.clone().into(),
+            deny: true,
+        },
+        GraphInconsistency::MissingWildcardEdge {
+            vertex: bar_read.clone().into(),
+            wildcard: file_read.clone().into(),
+        },
+        GraphInconsistency::UnknownVertex {
+            vertex: bar_read.clone().into(),
+            unknown: baz_read.clone().into(),
+        },
+        GraphInconsistency::MissingEdgeIn {
+            src: bar_read.clone().into(),
+            dst: baz_read.clone().into(),
+            deny: false,
+        },
+        GraphInconsistency::Orphaned {
+            vertex: orphan.clone().into(),
+        },
+    ] {
+        assert!(
+            inconsistencies.contains(&inconsistency),
+            "{inconsistency:?} not in {inconsistencies:?}"
+        );
+    }
+    assert_eq!(inconsistencies.len(), 6, "{inconsistencies:?}");
+}
@@ -1,6 +1,10 @@
+use std::{env, path::PathBuf};
+
 fn main() {
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
     tonic_build::configure()
         .build_server(true)
+        .file_descriptor_set_path(out_dir.join("rebacs_descriptor.bin"))
         .compile(&["proto/rebacs.proto"], &["proto"])
         .unwrap();
 }
@@ -1,44 +1,195 @@
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    pin::Pin,
+    sync::{Arc, RwLock},
+    time::{Instant, UNIX_EPOCH},
+};
 
-use jsonwebtoken::{decode, DecodingKey, TokenData, Validation};
-use log::info;
-use rebacdb::{Object as DbObject, ObjectOrSet, RelationGraph, Set as DbSet};
+use futures::{future::join_all, stream, Stream};
+use jsonwebtoken::{decode, errors::ErrorKind, DecodingKey, Validation};
+use metrics::counter;
+use opentelemetry::{
+    propagation::{Extractor, TextMapPropagator},
+    trace::TraceContextExt,
+};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use rebacdb::{
+    Intersection as DbIntersection, Object as DbObject, ObjectOrSet, RelationGraph, Set as DbSet,
+};
 use serde::Deserialize;
-use tokio::sync::mpsc::Sender;
-use tonic::metadata::MetadataMap;
-use tonic::{Request, Response, Status};
+use sha2::{Digest, Sha256};
+use tokio::sync::{
+    broadcast::{self, error::RecvError},
+    mpsc::{error::TrySendError, Sender},
+};
+use tonic::metadata::{KeyRef, MetadataMap};
+use tonic::service::Interceptor;
+use tonic::{Code, Request, Response, Status};
+use tonic_types::{ErrorDetails as StatusDetails, StatusExt};
+use tracing::{debug, error, field::display, info, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 use crate::rebacs_proto::{
-    exists_req, grant_req, is_permitted_req, rebac_service_server, revoke_req, ExistsReq,
-    ExistsRes, ExpandReq, ExpandRes, ExpandResItem, GrantReq, GrantRes, IsPermittedReq,
-    IsPermittedRes, Object, RevokeReq, RevokeRes, Set,
+    error_details::Reason, exists_req, grant_req, is_permitted_req, list_permissions_req,
+    rebac_service_server, revoke_req, watch_res, BatchGrantReq, BatchGrantRes, BatchIsPermittedReq,
+    BatchIsPermittedRes, CountObjectsReq, CountObjectsRes, ExistsReq, ExistsRes, ExpandReq,
+    ExpandRes, ExpandResItem, GrantReq, GrantRes, IsMemberReq, IsMemberRes, IsPermittedReq,
+    IsPermittedRes, ListPermissionsReq, ListPermissionsRes, Object, RevokeReq, RevokeRes,
+    SaveStatusReq, SaveStatusRes, Set, WatchReq, WatchRes,
 };
+use crate::{SaveStatus, GRANT_RELATION, NAMESPACE_NS};
 
 #[derive(Clone)]
 pub struct RebacService {
     pub graph: Arc<RelationGraph>,
-    pub oidc_pubkey: DecodingKey,
-    pub oidc_validation: Validation,
     pub save_trigger: Sender<()>,
+    /// announces the revision of the graph after every successful save
+    ///
+    /// The saved file contains at least all changes up to the announced revision.
+    pub save_completed: broadcast::Sender<u64>,
+    /// state of the savefile, see [`SaveStatus`]
+    pub save_status: Arc<SaveStatus>,
+    /// maximum depth of the searches, unlimited if `None`
+    pub max_depth: Option<u32>,
+    /// namespace of the object that represents the subject of a token
+    pub subject_namespace: String,
+    /// rejects all requests that change the graph
+    pub read_only: bool,
+    /// announces every change of the graph made by `grant` and `revoke` to the `watch` streams
+    pub changes: broadcast::Sender<WatchRes>,
+}
+
+/// validates the token of every request and stores its [`Claims`] in the request extensions
+#[derive(Clone, Default)]
+pub struct AuthInterceptor {
+    /// key and validation of every trusted issuer, a token has to be valid for one of them
+    ///
+    /// The keys are replaced when the issuers rotate them.
+    pub issuers: Vec<(Arc<RwLock<DecodingKey>>, Validation)>,
+    /// subjects of the long-lived api keys by the hex encoded SHA-256 hash of the key, see
+    /// [`hash_api_key`]
+    ///
+    /// A request with an api key as token is not validated by the issuers.
+    pub api_keys: Arc<HashMap<String, String>>,
 }
 
-const USER_NS: &str = "user";
+/// domain of the `ErrorInfo` details of a failed request, see [`error_status`]
+pub const ERROR_DOMAIN: &str = "rebacs.zettoit.eu";
+/// issuer of the [`Claims`] of requests authenticated with an api key
+pub const API_KEY_ISSUER: &str = "api-key";
+/// default of [`RebacService::subject_namespace`]
+pub const DEFAULT_SUBJECT_NS: &str = "user";
+/// relation of a group that is checked by `is_member` if the request doesn't name one
+pub const DEFAULT_MEMBER_RELATION: &str = "member";
+const DEFAULT_PAGE_SIZE: usize = 100;
+const MAX_PAGE_SIZE: u32 = 1000;
+
+impl RebacService {
+    /// returns the object that represents the subject of the token
+    fn subject(&self, claims: &Claims) -> DbObject {
+        (self.subject_namespace.as_str(), claims.sub.as_str()).into()
+    }
+
+    /// fails with `FAILED_PRECONDITION` if the server doesn't accept changes of the graph
+    fn ensure_writable(&self) -> Result<(), Status> {
+        if self.read_only {
+            return Err(Status::failed_precondition("server is read-only"));
+        }
+        Ok(())
+    }
+
+    /// announces a change of the graph to the subscribed `watch` streams
+    ///
+    /// `revision` is the one returned by the change, the current revision may already include
+    /// concurrent changes.
+    fn publish_change(
+        &self,
+        operation: watch_res::Operation,
+        src: &ObjectOrSet<'_>,
+        dst: &DbSet,
+        revision: u64,
+    ) {
+        let src = match src.relation() {
+            Some(relation) => watch_res::Src::SrcSet(Set {
+                namespace: src.namespace().to_string(),
+                id: src.id().to_string(),
+                relation: relation.to_string(),
+            }),
+            None => watch_res::Src::SrcObj(Object {
+                namespace: src.namespace().to_string(),
+                id: src.id().to_string(),
+            }),
+        };
+        // no subscribers is not an error
+        _ = self.changes.send(WatchRes {
+            operation: operation.into(),
+            src: Some(src),
+            dst: Some(Set {
+                namespace: dst.namespace().to_string(),
+                id: dst.id().to_string(),
+                relation: dst.relation().to_string(),
+            }),
+            revision,
+        });
+    }
+
+    /// fails with `FAILED_PRECONDITION` if the graph doesn't contain the changes up to
+    /// `min_revision` yet, e.g. after a restart that lost unsaved changes
+    fn ensure_revision(&self, min_revision: u64) -> Result<(), Status> {
+        let revision = self.graph.current_revision();
+        if revision < min_revision {
+            return Err(Status::failed_precondition(format!(
+                "graph revision {revision} is older than the requested revision {min_revision}"
+            )));
+        }
+        Ok(())
+    }
+
+    /// requests a save of the graph without waiting for it
+    ///
+    /// A full channel means that a save is already pending, which will include the latest change.
+    fn trigger_save(&self) {
+        if let Err(TrySendError::Closed(_)) = self.save_trigger.try_send(()) {
+            error!("save task is not running, changes are not persisted");
+        }
+    }
+}
 
 #[tonic::async_trait]
 impl rebac_service_server::RebacService for RebacService {
+    type WatchStream = Pin<Box<dyn Stream<Item = Result<WatchRes, Status>> + Send>>;
+
+    #[tracing::instrument(skip_all, fields(sub, src, dst, trace_id, parent_id, traversal_us))]
     async fn grant(&self, request: Request<GrantReq>) -> Result<Response<GrantRes>, Status> {
-        let token =
-            extract_token(request.metadata(), &self.oidc_pubkey, &self.oidc_validation).await?;
-        let user: DbObject = (USER_NS, token.claims.sub.as_str()).into();
+        self.ensure_writable()?;
+
+        let claims = claims(&request)?;
+        let user = self.subject(claims);
 
         let src = extract_src(request.get_ref().src.clone(), &user)?;
         let dst = extract_dst(request.get_ref().dst.clone())?;
+        record_src(&src);
+        record_dst(&dst);
 
-        if !crate::can_write(&self.graph, &user, &dst, None).await {
-            return Err(Status::permission_denied(
+        let started = Instant::now();
+        let permitted = crate::can_write(&self.graph, &user, &dst, self.max_depth).await;
+        record_traversal(started);
+        if !permitted {
+            return Err(not_permitted(
                 "token not permitted to grant permissions on dst",
+                "dst",
             ));
         }
+
+        if request.get_ref().dry_run {
+            let created = !self.graph.has(src, &dst).await;
+            return Ok(Response::new(GrantRes {
+                created,
+                revision: self.graph.current_revision(),
+            }));
+        }
+
         info!(
             "created relation {}:{}#{}@{}:{}#{} for {}",
             dst.namespace(),
@@ -47,91 +198,475 @@ impl rebac_service_server::RebacService for RebacService {
             src.namespace(),
             src.id(),
             src.relation().map(|x| x.to_string()).unwrap_or_default(),
-            token.claims.sub
+            claims.sub
         );
 
-        self.graph.insert(src, &dst).await;
+        let revision = self.graph.insert_revision(&src, &dst).await;
+        if let Some(revision) = revision {
+            self.publish_change(watch_res::Operation::Grant, &src, &dst, revision);
+        }
 
-        self.save_trigger.send(()).await.unwrap();
+        self.trigger_save();
 
-        Ok(Response::new(GrantRes {}))
+        Ok(Response::new(GrantRes {
+            created: revision.is_some(),
+            revision: revision.unwrap_or_else(|| self.graph.current_revision()),
+        }))
     }
+    #[tracing::instrument(skip_all, fields(sub, trace_id, parent_id, traversal_us))]
+    async fn batch_grant(
+        &self,
+        request: Request<BatchGrantReq>,
+    ) -> Result<Response<BatchGrantRes>, Status> {
+        self.ensure_writable()?;
+
+        let claims = claims(&request)?;
+        let user = self.subject(claims);
+
+        let grants = request
+            .get_ref()
+            .grants
+            .iter()
+            .enumerate()
+            .map(|(i, grant)| {
+                extract_src(grant.src.clone(), &user)
+                    .and_then(|src| Ok((src, extract_dst(grant.dst.clone())?)))
+                    .map_err(|err| prefix_status(err, &format!("grants[{i}]")))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // every dst is authorized before the first relation is created
+        let started = Instant::now();
+        let permitted = join_all(
+            grants
+                .iter()
+                .map(|(_, dst)| crate::can_write(&self.graph, &user, dst, self.max_depth)),
+        )
+        .await;
+        record_traversal(started);
+        if let Some(i) = permitted.iter().position(|permitted| !permitted) {
+            let dst = &grants[i].1;
+            return Err(not_permitted(
+                format!(
+                    "token not permitted to grant permissions on grants[{i}].dst {}:{}#{}",
+                    dst.namespace(),
+                    dst.id(),
+                    dst.relation()
+                ),
+                &format!("grants[{i}].dst"),
+            ));
+        }
+
+        if request.get_ref().dry_run {
+            let mut created = Vec::with_capacity(grants.len());
+            for (i, (src, dst)) in grants.iter().enumerate() {
+                // a relation repeated in the batch is only created once
+                let repeated = grants[..i].iter().any(|(x, y)| x == src && y == dst);
+                created.push(!repeated && !self.graph.has(src, dst).await);
+            }
+            return Ok(Response::new(BatchGrantRes {
+                created,
+                revision: self.graph.current_revision(),
+            }));
+        }
+
+        let relations: Vec<(&ObjectOrSet<'_>, &DbSet)> =
+            grants.iter().map(|(src, dst)| (src, dst)).collect();
+        let revisions = self.graph.insert_each_revision(relations).await;
+        for ((src, dst), revision) in grants.iter().zip(&revisions) {
+            if let Some(revision) = revision {
+                self.publish_change(watch_res::Operation::Grant, src, dst, *revision);
+            }
+        }
+        let created = revisions.iter().map(Option::is_some).collect::<Vec<_>>();
+
+        info!(
+            "created {} of {} relations for {}",
+            created.iter().filter(|x| **x).count(),
+            grants.len(),
+            claims.sub
+        );
+
+        self.trigger_save();
+
+        let revision = revisions.iter().flatten().max().copied();
+        Ok(Response::new(BatchGrantRes {
+            created,
+            revision: revision.unwrap_or_else(|| self.graph.current_revision()),
+        }))
+    }
+
+    #[tracing::instrument(skip_all, fields(sub, src, dst, trace_id, parent_id, traversal_us))]
     async fn revoke(&self, request: Request<RevokeReq>) -> Result<Response<RevokeRes>, Status> {
-        let token =
-            extract_token(request.metadata(), &self.oidc_pubkey, &self.oidc_validation).await?;
-        let user: DbObject = (USER_NS, token.claims.sub.as_str()).into();
+        self.ensure_writable()?;
+
+        let claims = claims(&request)?;
+        let user = self.subject(claims);
 
         let src = extract_src(request.get_ref().src.clone(), &user)?;
         let dst = extract_dst(request.get_ref().dst.clone())?;
+        record_src(&src);
+        record_dst(&dst);
 
-        if !crate::can_write(&self.graph, &user, &dst, None).await {
-            return Err(Status::permission_denied(
+        let started = Instant::now();
+        let permitted = crate::can_write(&self.graph, &user, &dst, self.max_depth).await;
+        record_traversal(started);
+        if !permitted {
+            return Err(not_permitted(
                 "token not permitted to revoke permissions on dst",
+                "dst",
             ));
         }
 
-        self.graph.remove(&src, &dst).await;
+        if request.get_ref().dry_run {
+            let removed = self.graph.has(src, &dst).await;
+            return Ok(Response::new(RevokeRes {
+                removed,
+                revision: self.graph.current_revision(),
+            }));
+        }
 
-        info!(
-            "delted relation {}:{}#{}@{}:{}#{} for {}",
+        let revision = self.graph.remove_revision(&src, &dst).await;
+        let relation = format!(
+            "{}:{}#{}@{}:{}#{}",
             dst.namespace(),
             dst.id(),
             dst.relation(),
             src.namespace(),
             src.id(),
             src.relation().map(|x| x.to_string()).unwrap_or_default(),
-            token.claims.sub
         );
+        match revision {
+            Some(revision) => {
+                self.publish_change(watch_res::Operation::Revoke, &src, &dst, revision);
+                info!("deleted relation {relation} for {}", claims.sub);
+            }
+            None => info!(
+                "relation {relation} to delete for {} does not exist",
+                claims.sub
+            ),
+        }
 
-        self.save_trigger.send(()).await.unwrap();
+        self.trigger_save();
 
-        Ok(Response::new(RevokeRes {}))
+        Ok(Response::new(RevokeRes {
+            removed: revision.is_some(),
+            revision: revision.unwrap_or_else(|| self.graph.current_revision()),
+        }))
     }
+    #[tracing::instrument(skip_all, fields(sub, src, dst, trace_id, parent_id))]
     async fn exists(&self, request: Request<ExistsReq>) -> Result<Response<ExistsRes>, Status> {
-        let token =
-            extract_token(request.metadata(), &self.oidc_pubkey, &self.oidc_validation).await?;
-        let user: DbObject = (USER_NS, token.claims.sub.as_str()).into();
+        let claims = claims(&request)?;
+        let user = self.subject(claims);
 
         let src = extract_src(request.get_ref().src.clone(), &user)?;
         let dst = extract_dst(request.get_ref().dst.clone())?;
+        record_src(&src);
+        record_dst(&dst);
 
         let exists = self.graph.has(src, &dst).await;
 
         Ok(Response::new(ExistsRes { exists }))
     }
 
+    #[tracing::instrument(skip_all, fields(sub, src, dst, trace_id, parent_id, traversal_us))]
     async fn is_permitted(
         &self,
         request: Request<IsPermittedReq>,
     ) -> Result<Response<IsPermittedRes>, Status> {
-        let token =
-            extract_token(request.metadata(), &self.oidc_pubkey, &self.oidc_validation).await?;
-        let user: DbObject = (USER_NS, token.claims.sub.as_str()).into();
+        let claims = claims(&request)?;
+        let user = self.subject(claims);
 
         let src = extract_src(request.get_ref().src.clone(), &user)?;
         let dst = extract_dst(request.get_ref().dst.clone())?;
+        record_src(&src);
+        record_dst(&dst);
+        self.ensure_revision(request.get_ref().min_revision)?;
 
-        let permitted = self.graph.check(src, &dst, None).await;
+        let started = Instant::now();
+        let permitted = crate::check(&self.graph, src, &dst, self.max_depth).await;
+        record_traversal(started);
 
         Ok(Response::new(IsPermittedRes { permitted }))
     }
 
+    #[tracing::instrument(skip_all, fields(sub, trace_id, parent_id, traversal_us))]
+    async fn batch_is_permitted(
+        &self,
+        request: Request<BatchIsPermittedReq>,
+    ) -> Result<Response<BatchIsPermittedRes>, Status> {
+        let claims = claims(&request)?;
+        let user = self.subject(claims);
+
+        let checks = request
+            .get_ref()
+            .checks
+            .iter()
+            .enumerate()
+            .map(|(i, check)| {
+                extract_src(check.src.clone(), &user)
+                    .and_then(|src| Ok((src, extract_dst(check.dst.clone())?)))
+                    .map_err(|err| prefix_status(err, &format!("checks[{i}]")))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let min_revision = request
+            .get_ref()
+            .checks
+            .iter()
+            .map(|x| x.min_revision)
+            .max();
+        self.ensure_revision(min_revision.unwrap_or_default())?;
+
+        let started = Instant::now();
+        let permitted = join_all(
+            checks
+                .iter()
+                .map(|(src, dst)| crate::check(&self.graph, src, dst, self.max_depth)),
+        )
+        .await;
+        record_traversal(started);
+
+        Ok(Response::new(BatchIsPermittedRes { permitted }))
+    }
+
+    #[tracing::instrument(skip_all, fields(sub, src, dst, trace_id, parent_id, traversal_us))]
+    async fn is_member(
+        &self,
+        request: Request<IsMemberReq>,
+    ) -> Result<Response<IsMemberRes>, Status> {
+        let claims = claims(&request)?;
+        let user = self.subject(claims);
+
+        let subject = extract_src(
+            request
+                .get_ref()
+                .subject
+                .clone()
+                .map(|obj| ObjectOrSet::from((obj.namespace, obj.id, None))),
+            &user,
+        )?;
+        let group = request
+            .get_ref()
+            .group
+            .clone()
+            .ok_or_else(|| missing_field("group"))?;
+        let member_relation = match request.get_ref().member_relation.as_str() {
+            "" => DEFAULT_MEMBER_RELATION,
+            relation => relation,
+        };
+        if group.namespace.is_empty() {
+            return Err(missing_field("group.namespace"));
+        }
+        if group.id.is_empty() {
+            return Err(missing_field("group.id"));
+        }
+        let members = DbSet::try_new(group.namespace, group.id, member_relation.to_string())
+            .map_err(|err| invalid_field("group", err))?;
+
+        record_src(&subject);
+        record_dst(&members);
+
+        let started = Instant::now();
+        let member = crate::check(&self.graph, subject, &members, self.max_depth).await;
+        record_traversal(started);
+
+        Ok(Response::new(IsMemberRes { member }))
+    }
+
+    #[tracing::instrument(skip_all, fields(sub, src, trace_id, parent_id, traversal_us))]
+    async fn list_permissions(
+        &self,
+        request: Request<ListPermissionsReq>,
+    ) -> Result<Response<ListPermissionsRes>, Status> {
+        let claims = claims(&request)?;
+        let user = self.subject(claims);
+
+        let src = extract_src(request.get_ref().src.clone(), &user)?;
+        record_src(&src);
+
+        let started = Instant::now();
+        if src != ObjectOrSet::from(&user)
+            && !crate::can_write(
+                &self.graph,
+                &user,
+                &(src.namespace(), src.id(), GRANT_RELATION).into(),
+                self.max_depth,
+            )
+            .await
+        {
+            return Err(not_permitted(
+                "token not permitted to list permissions of src",
+                "src",
+            ));
+        }
+
+        let permissions = self.graph.reachable_sets(src, self.max_depth).await;
+        record_traversal(started);
+        let (permissions, next_page_token) = paginate(
+            permissions,
+            request.get_ref().page_size,
+            &request.get_ref().page_token,
+        )?;
+
+        Ok(Response::new(ListPermissionsRes {
+            permissions: permissions
+                .into_iter()
+                .map(|w| Set {
+                    namespace: w.namespace().to_string(),
+                    id: w.id().to_string(),
+                    relation: w.relation().to_string(),
+                })
+                .collect(),
+            next_page_token,
+        }))
+    }
+
+    #[tracing::instrument(skip_all, fields(sub, dst, trace_id, parent_id, traversal_us))]
+    async fn count_objects(
+        &self,
+        request: Request<CountObjectsReq>,
+    ) -> Result<Response<CountObjectsRes>, Status> {
+        let claims = claims(&request)?;
+        let user = self.subject(claims);
+
+        let CountObjectsReq {
+            namespace,
+            relation,
+        } = request.get_ref();
+        if namespace.is_empty() {
+            return Err(missing_field("namespace"));
+        }
+        if relation.is_empty() {
+            return Err(missing_field("relation"));
+        }
+
+        let namespace_grant: DbSet = (NAMESPACE_NS, namespace.as_str(), GRANT_RELATION).into();
+        record_dst(&namespace_grant);
+
+        let started = Instant::now();
+        let permitted = crate::check(&self.graph, &user, &namespace_grant, self.max_depth).await;
+        record_traversal(started);
+        if !permitted {
+            return Err(not_permitted(
+                "token not permitted to count objects of namespace",
+                "namespace",
+            ));
+        }
+
+        let count = self
+            .graph
+            .count_objects_with_relation(namespace, relation)
+            .await as u64;
+
+        Ok(Response::new(CountObjectsRes { count }))
+    }
+
+    #[tracing::instrument(skip_all, fields(sub, trace_id, parent_id))]
+    async fn save_status(
+        &self,
+        request: Request<SaveStatusReq>,
+    ) -> Result<Response<SaveStatusRes>, Status> {
+        claims(&request)?;
+
+        let last_saved = self
+            .save_status
+            .last_saved()
+            .and_then(|x| x.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |x| x.as_secs());
+
+        Ok(Response::new(SaveStatusRes {
+            dirty: self.save_status.is_dirty(&self.graph),
+            last_saved,
+            revision: self.graph.current_revision(),
+            saved_revision: self.save_status.revision(),
+        }))
+    }
+
+    /// streams the changes of relations to sets of the namespace made after the subscription
+    ///
+    /// Requires the grant relation of the namespace, see [`crate::NAMESPACE_NS`]. A subscriber
+    /// that falls too far behind receives a `DATA_LOSS` error and has to resubscribe, since it
+    /// missed changes.
+    #[tracing::instrument(skip_all, fields(sub, dst, trace_id, parent_id, traversal_us))]
+    async fn watch(
+        &self,
+        request: Request<WatchReq>,
+    ) -> Result<Response<Self::WatchStream>, Status> {
+        let claims = claims(&request)?;
+        let user = self.subject(claims);
+
+        let namespace = request.get_ref().namespace.clone();
+        if namespace.is_empty() {
+            return Err(missing_field("namespace"));
+        }
+
+        let namespace_grant: DbSet = (NAMESPACE_NS, namespace.as_str(), GRANT_RELATION).into();
+        record_dst(&namespace_grant);
+
+        let started = Instant::now();
+        let permitted = crate::check(&self.graph, &user, &namespace_grant, self.max_depth).await;
+        record_traversal(started);
+        if !permitted {
+            return Err(not_permitted(
+                "token not permitted to watch the namespace",
+                "namespace",
+            ));
+        }
+
+        let receiver = self.changes.subscribe();
+        let changes = stream::unfold(Some(receiver), move |receiver| {
+            let namespace = namespace.clone();
+            async move {
+                let mut receiver = receiver?;
+                loop {
+                    match receiver.recv().await {
+                        Ok(change)
+                            if change.dst.as_ref().map(|x| x.namespace.as_str())
+                                != Some(namespace.as_str()) =>
+                        {
+                            continue
+                        }
+                        Ok(change) => return Some((Ok(change), Some(receiver))),
+                        Err(RecvError::Lagged(missed)) => {
+                            return Some((
+                                Err(Status::data_loss(format!("missed {missed} changes"))),
+                                None,
+                            ))
+                        }
+                        Err(RecvError::Closed) => return None,
+                    }
+                }
+            }
+        });
+
+        Ok(Response::new(Box::pin(changes)))
+    }
+
+    #[tracing::instrument(skip_all, fields(sub, dst, trace_id, parent_id, traversal_us))]
     async fn expand(&self, request: Request<ExpandReq>) -> Result<Response<ExpandRes>, Status> {
-        let token =
-            extract_token(request.metadata(), &self.oidc_pubkey, &self.oidc_validation).await?;
+        let claims = claims(&request)?;
         let dst = extract_dst(request.get_ref().dst.clone())?;
+        record_dst(&dst);
 
-        let user: DbObject = (USER_NS, token.claims.sub.as_str()).into();
-        if !crate::can_write(&self.graph, &user, &dst, None).await {
-            return Err(Status::permission_denied(
+        let user = self.subject(claims);
+        let started = Instant::now();
+        if !crate::can_write(&self.graph, &user, &dst, self.max_depth).await {
+            return Err(not_permitted(
                 "token not permitted to expand permissions on dst",
+                "dst",
             ));
         }
 
-        let expanded = self
+        let max_results = match request.get_ref().max_results {
+            0 => None,
+            max => Some(max as usize),
+        };
+        let (expanded, truncated) = self
             .graph
-            .expand(&dst)
-            .await
+            .expand_capped(&dst, max_results, self.max_depth)
+            .await;
+        record_traversal(started);
+        let expanded = expanded
             .into_iter()
             .map(|(v, path)| ExpandResItem {
                 src: Some(Object {
@@ -149,11 +684,14 @@ impl rebac_service_server::RebacService for RebacService {
             })
             .collect();
 
-        Ok(Response::new(ExpandRes { expanded }))
+        Ok(Response::new(ExpandRes {
+            expanded,
+            truncated,
+        }))
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize)]
 pub struct Claims {
     pub aud: Vec<String>,
     pub exp: usize,
@@ -169,52 +707,348 @@ pub struct Claims {
     pub email: Option<String>,
 }
 
-async fn extract_token(
+impl Interceptor for AuthInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        let claims = extract_token(request.metadata(), &self.issuers, &self.api_keys)?;
+        request.extensions_mut().insert(claims);
+        Ok(request)
+    }
+}
+
+/// returns the [`Claims`] stored by the [`AuthInterceptor`]
+///
+/// The subject is recorded in the span of the request, which becomes a child of the span of the
+/// caller (see [`parent_context`]).
+fn claims<T>(request: &Request<T>) -> Result<&Claims, Status> {
+    let span = Span::current();
+    let parent = parent_context(request.metadata());
+    let caller = parent.span().span_context().clone();
+    if caller.is_valid() {
+        span.record("trace_id", display(caller.trace_id()));
+        span.record("parent_id", display(caller.span_id()));
+        span.set_parent(parent);
+    }
+
+    let claims = request
+        .extensions()
+        .get::<Claims>()
+        .ok_or_else(token_missing)?;
+    span.record("sub", claims.sub.as_str());
+    Ok(claims)
+}
+
+/// returns the trace context of the caller from the W3C `traceparent` header of a request (e.g.
+/// `00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01`)
+///
+/// The span context of the returned context is invalid if the header is missing or malformed.
+pub fn parent_context(metadata: &MetadataMap) -> opentelemetry::Context {
+    TraceContextPropagator::new().extract(&MetadataExtractor(metadata))
+}
+
+/// reads the propagated trace context from the metadata of a request
+struct MetadataExtractor<'a>(&'a MetadataMap);
+
+impl Extractor for MetadataExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|x| x.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0
+            .keys()
+            .map(|key| match key {
+                KeyRef::Ascii(key) => key.as_str(),
+                KeyRef::Binary(key) => key.as_str(),
+            })
+            .collect()
+    }
+}
+
+/// records the src of the request in its span
+fn record_src(src: &ObjectOrSet<'_>) {
+    let span = Span::current();
+    match src.relation() {
+        Some(relation) => span.record(
+            "src",
+            display(format_args!(
+                "{}:{}#{}",
+                src.namespace(),
+                src.id(),
+                relation
+            )),
+        ),
+        None => span.record(
+            "src",
+            display(format_args!("{}:{}", src.namespace(), src.id())),
+        ),
+    };
+}
+
+/// records the dst of the request in its span
+fn record_dst(dst: &DbSet) {
+    Span::current().record(
+        "dst",
+        display(format_args!(
+            "{}:{}#{}",
+            dst.namespace(),
+            dst.id(),
+            dst.relation()
+        )),
+    );
+}
+
+/// records the time since `started` as duration of the graph traversals in the span of the
+/// request
+fn record_traversal(started: Instant) {
+    Span::current().record("traversal_us", started.elapsed().as_micros() as u64);
+}
+
+#[tracing::instrument(skip_all)]
+fn extract_token(
     metadata: &MetadataMap,
-    pubkey: &DecodingKey,
-    validation: &Validation,
-) -> Result<TokenData<Claims>, Status> {
-    let token = metadata
-        .get("authorization")
-        .map(|x| x.to_str().unwrap())
-        .ok_or(Status::unauthenticated("authorization header required"))?;
+    issuers: &[(Arc<RwLock<DecodingKey>>, Validation)],
+    api_keys: &HashMap<String, String>,
+) -> Result<Claims, Status> {
+    let token = metadata.get("authorization").ok_or_else(token_missing)?;
+    // neither a token nor an api key contains other than visible ASCII characters
+    let Ok(token) = token.to_str() else {
+        return Err(token_invalid("malformed"));
+    };
+
+    if !api_keys.is_empty() {
+        if let Some(sub) = api_keys.get(&hash_api_key(token)) {
+            return Ok(Claims {
+                iss: API_KEY_ISSUER.to_string(),
+                sub: sub.clone(),
+                ..Default::default()
+            });
+        }
+    }
+
+    // only the reason of the last issuer is reported if the token is invalid for all of them
+    let mut reason = "no_issuer";
+    for (pubkey, validation) in issuers {
+        match decode::<Claims>(token, &pubkey.read().unwrap(), validation) {
+            Ok(token) => return Ok(token.claims),
+            Err(err) => reason = decode_failure_reason(err.kind()),
+        }
+    }
+    Err(token_invalid(reason))
+}
+
+/// `UNAUTHENTICATED` status of a request with a token that is invalid for `reason`, see
+/// [`decode_failure_reason`]
+fn token_invalid(reason: &'static str) -> Status {
+    debug!(reason, "token decode failed");
+    counter!("rebacs_token_decode_failures_total", "reason" => reason).increment(1);
+    let reason = match reason {
+        "expired" => Reason::TokenExpired,
+        _ => Reason::TokenInvalid,
+    };
+    error_status(
+        Code::Unauthenticated,
+        "authorization header invalid",
+        reason,
+        "",
+    )
+}
 
-    let token = decode::<Claims>(token, pubkey, validation)
-        .map_err(|_| Status::unauthenticated("authorization header invalid"))?;
+/// returns the hex encoded SHA-256 hash of an api key, which is stored instead of the key
+pub fn hash_api_key(key: &str) -> String {
+    Sha256::digest(key.as_bytes())
+        .iter()
+        .map(|x| format!("{x:02x}"))
+        .collect()
+}
 
-    Ok(token)
+/// parses the api keys of [`AuthInterceptor::api_keys`] from lines of `<hash> <subject>`
+///
+/// The hash is the one of [`hash_api_key`]. Empty lines and lines starting with `#` are
+/// ignored, a malformed line fails with its line number starting at 1.
+pub fn parse_api_keys(content: &str) -> Result<HashMap<String, String>, usize> {
+    let mut api_keys = HashMap::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((hash, sub)) = line.split_once(char::is_whitespace) else {
+            return Err(i + 1);
+        };
+        let sub = sub.trim();
+        let is_hash =
+            hash.len() == 64 && hash.bytes().all(|c| matches!(c, b'0'..=b'9' | b'a'..=b'f'));
+        if !is_hash || sub.is_empty() {
+            return Err(i + 1);
+        }
+        api_keys.insert(hash.to_string(), sub.to_string());
+    }
+    Ok(api_keys)
+}
+
+/// `UNAUTHENTICATED` status of a request without authorization header
+fn token_missing() -> Status {
+    error_status(
+        Code::Unauthenticated,
+        "authorization header required",
+        Reason::TokenMissing,
+        "",
+    )
+}
+
+/// maps a token decode error to a short reason used as metrics label
+pub fn decode_failure_reason(kind: &ErrorKind) -> &'static str {
+    match kind {
+        ErrorKind::ExpiredSignature => "expired",
+        ErrorKind::ImmatureSignature => "immature",
+        ErrorKind::InvalidSignature => "bad_signature",
+        ErrorKind::InvalidAudience => "wrong_audience",
+        ErrorKind::InvalidIssuer => "wrong_issuer",
+        ErrorKind::InvalidAlgorithm | ErrorKind::InvalidAlgorithmName => "wrong_algorithm",
+        ErrorKind::MissingRequiredClaim(_) => "missing_claim",
+        _ => "malformed",
+    }
 }
 
-fn extract_src<'a>(
+pub fn extract_src<'a>(
     src: Option<impl Into<ObjectOrSet<'a>>>,
     fallback_user: &'a DbObject,
 ) -> Result<ObjectOrSet<'a>, Status> {
     if let Some(src) = src {
         let src: ObjectOrSet<'_> = src.into();
         if src.namespace().is_empty() {
-            Err(Status::invalid_argument("src.namespace must be set"))
+            Err(missing_field("src.namespace"))
         } else if src.id().is_empty() {
-            Err(Status::invalid_argument("src.id must be set"))
+            Err(missing_field("src.id"))
         } else {
-            Ok(src)
+            match src {
+                ObjectOrSet::Object(obj) => {
+                    DbObject::try_new(obj.namespace().to_string(), obj.id().to_string())
+                        .map(Into::into)
+                }
+                ObjectOrSet::Set(set) => DbSet::try_new(
+                    set.namespace().to_string(),
+                    set.id().to_string(),
+                    set.relation().to_string(),
+                )
+                .map(Into::into),
+                ObjectOrSet::Intersection(intersection) => DbIntersection::try_new(
+                    intersection.namespace().to_string(),
+                    intersection.id().to_string(),
+                    intersection.relations().map(str::to_string).collect(),
+                )
+                .map(Into::into),
+            }
+            .map_err(|err| invalid_field("src", err))
         }
     } else {
         Ok(fallback_user.into())
     }
 }
 
-fn extract_dst(dst: Option<Set>) -> Result<DbSet, Status> {
-    let dst = dst.ok_or(Status::invalid_argument("dst must be set"))?;
-    let dst: DbSet = (dst.namespace, dst.id, dst.relation).into();
+/// returns the page of `items` selected by `page_token` and the token of the next page
+///
+/// The page token is the offset of the page in `items`, the next page token is empty if there
+/// are no more items.
+pub fn paginate<T>(
+    items: Vec<T>,
+    page_size: u32,
+    page_token: &str,
+) -> Result<(Vec<T>, String), Status> {
+    let page_size = match page_size {
+        0 => DEFAULT_PAGE_SIZE,
+        size => size.min(MAX_PAGE_SIZE) as usize,
+    };
+    let offset = match page_token {
+        "" => 0,
+        token => token.parse::<usize>().map_err(|_| {
+            error_status(
+                Code::InvalidArgument,
+                "page_token invalid",
+                Reason::FieldInvalid,
+                "page_token",
+            )
+        })?,
+    };
 
-    if dst.namespace().is_empty() {
-        return Err(Status::invalid_argument("dst.namespace must be set"));
+    let next_page_token = if offset + page_size < items.len() {
+        (offset + page_size).to_string()
+    } else {
+        String::new()
+    };
+    let page = items.into_iter().skip(offset).take(page_size).collect();
+
+    Ok((page, next_page_token))
+}
+
+pub fn extract_dst(dst: Option<Set>) -> Result<DbSet, Status> {
+    let dst = dst.ok_or_else(|| missing_field("dst"))?;
+
+    if dst.namespace.is_empty() {
+        return Err(missing_field("dst.namespace"));
+    }
+    if dst.id.is_empty() {
+        return Err(missing_field("dst.id"));
     }
-    if dst.id().is_empty() {
-        return Err(Status::invalid_argument("dst.id must be set"));
+
+    DbSet::try_new(dst.namespace, dst.id, dst.relation).map_err(|err| invalid_field("dst", err))
+}
+
+/// creates a status with the details of the failure as standard `google.rpc.Status` details
+///
+/// The `reason` is the `ErrorInfo` reason with the domain [`ERROR_DOMAIN`], a non-empty `field`
+/// is the field of the `BadRequest` violation.
+fn error_status(code: Code, message: impl Into<String>, reason: Reason, field: &str) -> Status {
+    let message = message.into();
+    let mut details =
+        StatusDetails::with_error_info(reason.as_str_name(), ERROR_DOMAIN, HashMap::new());
+    if !field.is_empty() {
+        details.add_bad_request_violation(field, message.clone());
     }
+    Status::with_error_details(code, message, details)
+}
+
+/// `INVALID_ARGUMENT` status of a `field` of the request that is not set
+fn missing_field(field: &str) -> Status {
+    error_status(
+        Code::InvalidArgument,
+        format!("{field} must be set"),
+        Reason::FieldMissing,
+        field,
+    )
+}
 
-    Ok(dst)
+/// `INVALID_ARGUMENT` status of a malformed `field` of the request
+fn invalid_field(field: &str, err: impl Display) -> Status {
+    error_status(
+        Code::InvalidArgument,
+        format!("{field} invalid: {err}"),
+        Reason::FieldInvalid,
+        field,
+    )
+}
+
+/// `PERMISSION_DENIED` status of a `field` of the request the token is not permitted to access
+fn not_permitted(message: impl Into<String>, field: &str) -> Status {
+    error_status(Code::PermissionDenied, message, Reason::NotPermitted, field)
+}
+
+/// prefixes the message and the field of a status with the entry of a batch, e.g. `grants[1]`
+fn prefix_status(status: Status, prefix: &str) -> Status {
+    let message = format!("{prefix}: {}", status.message());
+    let details = status.get_error_details();
+    let reason = details
+        .error_info()
+        .and_then(|info| Reason::from_str_name(&info.reason));
+    let field = details
+        .bad_request()
+        .and_then(|x| x.field_violations.first())
+        .map(|x| format!("{prefix}.{}", x.field))
+        .unwrap_or_else(|| prefix.to_string());
+    match reason {
+        Some(reason) => error_status(status.code(), message, reason, &field),
+        None => Status::new(status.code(), message),
+    }
 }
 
 macro_rules! from_src {
@@ -234,3 +1068,4 @@ from_src!(grant_req::Src);
 from_src!(revoke_req::Src);
 from_src!(exists_req::Src);
 from_src!(is_permitted_req::Src);
+from_src!(list_permissions_req::Src);
@@ -1,22 +1,57 @@
-use std::{env, sync::Arc, time::Duration};
+// `tonic::Status` is large, but it is the error type of every handler anyway
+#![allow(clippy::result_large_err)]
+use std::{
+    collections::HashMap,
+    env,
+    future::Future,
+    io,
+    net::SocketAddr,
+    num::NonZeroU64,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::{Duration, SystemTime},
+};
 
-use grpc_service::RebacService;
+use grpc_service::{parse_api_keys, AuthInterceptor, RebacService, DEFAULT_SUBJECT_NS};
 use jsonwebtoken::{Algorithm, DecodingKey, Validation};
-use log::info;
-use rebacdb::{ObjectOrSet, RelationGraph, Set};
+use log::{error, info, warn};
+use metrics_exporter_prometheus::PrometheusBuilder;
+use opentelemetry::KeyValue;
+use opentelemetry_sdk::{runtime, trace, Resource};
+use rebacdb::{LimitReached, ObjectOrSet, RelationGraph, SavefileError, Set};
 use serde::Deserialize;
+use thiserror::Error;
 use tokio::{
     fs::{self, File},
     io::BufReader,
-    select,
-    sync::mpsc::channel,
+    select, signal,
+    sync::{
+        broadcast,
+        mpsc::{channel, Receiver},
+        oneshot,
+    },
+};
+use tonic::{service::interceptor::InterceptedService, transport::Server};
+use tonic_health::{
+    pb::health_server::{Health, HealthServer},
+    server::{health_reporter, HealthReporter},
+    ServingStatus,
 };
-use tonic::transport::Server;
+use tonic_reflection::server::{ServerReflection, ServerReflectionServer};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
 pub mod grpc_service;
+#[cfg(test)]
+mod tests;
 pub mod rebacs_proto {
 
     tonic::include_proto!("eu.zettoit.rebacs");
+
+    /// encoded descriptors of the proto files, served by the reflection service
+    pub const FILE_DESCRIPTOR_SET: &[u8] = tonic::include_file_descriptor_set!("rebacs_descriptor");
 }
 
 use crate::rebacs_proto::rebac_service_server;
@@ -29,79 +64,648 @@ struct IssuerDiscovery {
 #[tokio::main]
 async fn main() {
     dotenvy::dotenv().ok();
-    env_logger::init();
+    init_tracing();
 
-    info!("loading graph from graph.dat");
-    let graph = if let Ok(file) = File::open("graph.dat").await {
-        let mut reader = BufReader::new(file);
-        RelationGraph::read_savefile(&mut reader).await
-    } else {
-        RelationGraph::default()
+    // `METRICS_LISTEN=[::]:9000` serves the metrics in the Prometheus format at that address
+    if let Ok(metrics_listen) = env::var("METRICS_LISTEN") {
+        PrometheusBuilder::new()
+            .with_http_listener(
+                metrics_listen
+                    .parse::<SocketAddr>()
+                    .expect("METRICS_LISTEN must be a socket address"),
+            )
+            .install()
+            .expect("METRICS_LISTEN must be available");
+    }
+
+    let graph_path = PathBuf::from(
+        env::var("REBACS_GRAPH_PATH").unwrap_or_else(|_| DEFAULT_GRAPH_PATH.to_string()),
+    );
+    let save_config = SaveConfig {
+        backup_path: env::var("REBACS_BACKUP_PATH")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| default_backup_path(&graph_path)),
+        path: graph_path,
+        backup_count: env::var("BACKUP_COUNT")
+            .map(|x| x.parse().expect("BACKUP_COUNT must be a number"))
+            .unwrap_or(1),
+        interval: env::var("REBACS_SAVE_INTERVAL_SECS")
+            .map(|x| {
+                Duration::from_secs(
+                    x.parse::<NonZeroU64>()
+                        .expect("REBACS_SAVE_INTERVAL_SECS must be a positive number of seconds")
+                        .get(),
+                )
+            })
+            .unwrap_or(DEFAULT_SAVE_INTERVAL),
     };
 
+    info!("loading graph from {}", save_config.path.display());
+    let graph = match load(&save_config.path, &save_config.backup_path).await {
+        Ok(graph) => graph,
+        Err(err)
+            if env::var("START_EMPTY_ON_LOAD_FAILURE")
+                .map(|x| {
+                    x.parse()
+                        .expect("START_EMPTY_ON_LOAD_FAILURE must be true or false")
+                })
+                .unwrap_or(false) =>
+        {
+            error!("failed to load graph, starting with an empty graph: {err}");
+            RelationGraph::default()
+        }
+        Err(err) => {
+            error!("failed to load graph: {err}");
+            std::process::exit(1);
+        }
+    };
+    if let Err(inconsistencies) = graph.validate().await {
+        for inconsistency in inconsistencies {
+            warn!("loaded graph is inconsistent: {inconsistency:?}");
+        }
+    }
+
+    // `NAMESPACE_ALIASES=product_a=shared,product_b=shared` lets the sets of `shared` satisfy
+    // checks for the sets of `product_a` and `product_b`
+    let graph = env::var("NAMESPACE_ALIASES")
+        .unwrap_or_default()
+        .split(',')
+        .filter(|x| !x.is_empty())
+        .map(|x| {
+            x.split_once('=')
+                .expect("NAMESPACE_ALIASES must be a list of namespace=alias")
+        })
+        .fold(graph, |graph, (namespace, alias)| {
+            graph.with_namespace_alias(namespace, alias)
+        });
+
     let graph = Arc::new(graph);
+    let save_status = Arc::new(SaveStatus::new(graph.current_revision()));
 
     let (save_tx, mut save_rx) = channel::<()>(32);
+    let (save_completed_tx, _) = broadcast::channel::<u64>(16);
     let save_thread_graph = graph.clone();
+    let save_thread_completed = save_completed_tx.clone();
+    let save_thread_status = save_status.clone();
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+    let save_task = tokio::spawn(async move {
+        save_loop(
+            &save_thread_graph,
+            &save_thread_status,
+            &save_thread_completed,
+            &mut save_rx,
+            &save_config,
+            async {
+                // a dropped sender shuts down the loop as well
+                _ = shutdown_rx.await;
+            },
+        )
+        .await
+    });
+
+    // without a pause the compaction would hold the lock of the graph all the time
+    let compact_interval = env::var("COMPACT_INTERVAL")
+        .map(|x| {
+            x.parse::<NonZeroU64>()
+                .expect("COMPACT_INTERVAL must be a positive number of seconds")
+                .get()
+        })
+        .unwrap_or(3600);
+    let compact_thread_graph = graph.clone();
     tokio::spawn(async move {
         loop {
-            select! {
-                _ = tokio::time::sleep(Duration::from_secs(30)) => {}
-                _ = save_rx.recv() => {}
-            };
-            info!("saving graph");
-            let _ = fs::copy("graph.dat", "graph.dat.bak").await;
-            let mut file = File::create("graph.dat").await.unwrap();
-            save_thread_graph.write_savefile(&mut file).await;
+            tokio::time::sleep(Duration::from_secs(compact_interval)).await;
+            let reclaimed = compact_thread_graph.compact().await;
+            info!("compacted graph, reclaimed {reclaimed} verticies");
         }
     });
 
-    let issuer = env::var("OIDC_ISSUER").expect("OIDC_ISSUER env var");
-    info!("loading public key from {issuer}");
-    let issuer_key = reqwest::get(&issuer)
-        .await
-        .unwrap()
-        .json::<IssuerDiscovery>()
-        .await
-        .unwrap()
-        .public_key;
-
-    let pem = format!(
-        "-----BEGIN PUBLIC KEY-----\n{}\n-----END PUBLIC KEY-----",
-        issuer_key
+    // `OIDC_ISSUER=https://a.example,https://b.example` accepts tokens of both issuers
+    let issuers = env::var("OIDC_ISSUER").expect("OIDC_ISSUER env var");
+    let issuers = issuers
+        .split(',')
+        .filter(|x| !x.is_empty())
+        .collect::<Vec<_>>();
+    let oidc_pubkey = env::var("OIDC_PUBLIC_KEY").ok();
+    let oidc_pubkey_path = env::var("OIDC_PUBLIC_KEY_PATH").ok();
+    assert!(
+        issuers.len() == 1 || (oidc_pubkey.is_none() && oidc_pubkey_path.is_none()),
+        "OIDC_PUBLIC_KEY and OIDC_PUBLIC_KEY_PATH require a single OIDC_ISSUER"
     );
+    // `OIDC_AUDIENCE=app-a,app-b` accepts tokens minted for either of the clients
+    let audiences = env::var("OIDC_AUDIENCE").expect("OIDC_AUDIENCE env var");
+    let audiences = audiences
+        .split(',')
+        .filter(|x| !x.is_empty())
+        .collect::<Vec<_>>();
+    assert!(!audiences.is_empty(), "OIDC_AUDIENCE must not be empty");
+    // keys that are configured locally are never refreshed
+    let key_refresh_interval = (oidc_pubkey.is_none() && oidc_pubkey_path.is_none()).then(|| {
+        env::var("OIDC_KEY_REFRESH_INTERVAL")
+            .map(|x| {
+                Duration::from_secs(
+                    x.parse::<NonZeroU64>()
+                        .expect("OIDC_KEY_REFRESH_INTERVAL must be a positive number of seconds")
+                        .get(),
+                )
+            })
+            .unwrap_or(DEFAULT_KEY_REFRESH_INTERVAL)
+    });
 
-    let oidc_pubkey = DecodingKey::from_rsa_pem(pem.as_bytes()).unwrap();
+    let mut oidc_issuers = Vec::with_capacity(issuers.len());
+    for issuer in issuers {
+        let oidc_pubkey = Arc::new(RwLock::new(
+            load_oidc_pubkey(issuer, oidc_pubkey.clone(), oidc_pubkey_path.clone()).await,
+        ));
+
+        if let Some(interval) = key_refresh_interval {
+            let issuer = issuer.to_string();
+            let key = oidc_pubkey.clone();
+            tokio::spawn(async move {
+                loop {
+                    tokio::time::sleep(interval).await;
+                    refresh_oidc_pubkey(&issuer, &key).await;
+                }
+            });
+        }
 
-    let mut oidc_validation = Validation::new(Algorithm::RS256);
-    oidc_validation.set_issuer(&[&issuer]);
-    oidc_validation.set_audience(&[env::var("OIDC_AUDIENCE").expect("OIDC_AUDIENCE env var")]);
+        oidc_issuers.push((
+            oidc_pubkey,
+            token_validation(Algorithm::RS256, issuer, &audiences),
+        ));
+    }
 
     let rebac_service = RebacService {
         graph: graph.clone(),
         save_trigger: save_tx.clone(),
-        oidc_pubkey,
-        oidc_validation,
+        save_completed: save_completed_tx,
+        save_status,
+        max_depth: env::var("MAX_DEPTH")
+            .ok()
+            .map(|x| x.parse().expect("MAX_DEPTH must be a number")),
+        subject_namespace: env::var("SUBJECT_NAMESPACE")
+            .unwrap_or_else(|_| DEFAULT_SUBJECT_NS.to_string()),
+        read_only: env::var("READ_ONLY")
+            .map(|x| x.parse().expect("READ_ONLY must be true or false"))
+            .unwrap_or(false),
+        changes: broadcast::channel(WATCH_CAPACITY).0,
+    };
+    // `API_KEYS_PATH=api_keys.dat` additionally accepts the api keys listed in the file
+    let api_keys = match env::var("API_KEYS_PATH") {
+        Ok(path) => {
+            let content = std::fs::read_to_string(&path).expect("API_KEYS_PATH must be readable");
+            parse_api_keys(&content).unwrap_or_else(|line| {
+                panic!("API_KEYS_PATH line {line}: expected `<sha256 hex> <subject>`")
+            })
+        }
+        Err(_) => HashMap::new(),
+    };
+    let auth_interceptor = AuthInterceptor {
+        issuers: oidc_issuers,
+        api_keys: Arc::new(api_keys),
     };
 
+    let rebac_server = rebac_service_server::RebacServiceServer::new(rebac_service.clone())
+        .max_decoding_message_size(
+            env::var("MAX_DECODING_MESSAGE_SIZE")
+                .map(|x| {
+                    x.parse()
+                        .expect("MAX_DECODING_MESSAGE_SIZE must be a number of bytes")
+                })
+                .unwrap_or(DEFAULT_MAX_DECODING_MESSAGE_SIZE),
+        )
+        .max_encoding_message_size(
+            env::var("MAX_ENCODING_MESSAGE_SIZE")
+                .map(|x| {
+                    x.parse()
+                        .expect("MAX_ENCODING_MESSAGE_SIZE must be a number of bytes")
+                })
+                .unwrap_or(DEFAULT_MAX_ENCODING_MESSAGE_SIZE),
+        );
+
+    // reflection only describes the API, it doesn't require a token
+    let reflection_server = env::var("REBACS_ENABLE_REFLECTION")
+        .map(|x| {
+            x.parse()
+                .expect("REBACS_ENABLE_REFLECTION must be true or false")
+        })
+        .unwrap_or(false)
+        .then(reflection_server);
+
+    // the health service doesn't require a token, it is used by probes
+    let (mut health_reporter, health_server) = health_server().await;
+
     let listen = "[::]:50051";
     info!("starting grpc server on {listen}");
     Server::builder()
-        .add_service(rebac_service_server::RebacServiceServer::new(
-            rebac_service.clone(),
-        ))
-        .serve(listen.parse().unwrap())
+        .add_service(InterceptedService::new(rebac_server, auth_interceptor))
+        .add_service(health_server)
+        .add_optional_service(reflection_server)
+        .serve_with_shutdown(listen.parse().unwrap(), async move {
+            shutdown_signal().await;
+            set_not_serving(&mut health_reporter).await;
+        })
         .await
-        .unwrap()
+        .unwrap();
+
+    info!("shutting down, saving graph");
+    _ = shutdown_tx.send(());
+    save_task.await.unwrap();
+    // exports the spans that are still batched
+    opentelemetry::global::shutdown_tracer_provider();
+}
+
+/// returns the reflection service that describes the `RebacService`
+pub fn reflection_server() -> ServerReflectionServer<impl ServerReflection> {
+    tonic_reflection::server::Builder::configure()
+        .register_encoded_file_descriptor_set(rebacs_proto::FILE_DESCRIPTOR_SET)
+        .build()
+        .expect("file descriptor set is valid")
+}
+
+/// returns the `grpc.health.v1.Health` service that reports the `RebacService` as serving
+pub async fn health_server() -> (HealthReporter, HealthServer<impl Health>) {
+    let (mut reporter, server) = health_reporter();
+    reporter
+        .set_serving::<rebac_service_server::RebacServiceServer<RebacService>>()
+        .await;
+    (reporter, server)
+}
+
+/// reports the server and the `RebacService` as not serving, e.g. while shutting down
+pub async fn set_not_serving(reporter: &mut HealthReporter) {
+    reporter
+        .set_service_status("", ServingStatus::NotServing)
+        .await;
+    reporter
+        .set_not_serving::<rebac_service_server::RebacServiceServer<RebacService>>()
+        .await;
+}
+
+/// completes when the process receives ctrl-c or SIGTERM
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        signal::ctrl_c()
+            .await
+            .expect("ctrl-c handler can be installed");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("SIGTERM handler can be installed")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+/// where and how often the graph is saved
+pub struct SaveConfig {
+    /// path of the savefile
+    pub path: PathBuf,
+    /// path of the newest backup, older ones get the suffix `.1`, `.2`, ...
+    pub backup_path: PathBuf,
+    /// number of backups that are kept
+    pub backup_count: usize,
+    /// time between two saves
+    pub interval: Duration,
+}
+
+/// saves `graph` every `config.interval` and whenever `trigger` receives a message, if it has
+/// unsaved changes
+///
+/// Once `shutdown` completes, the graph is saved a last time if it has unsaved changes and the
+/// loop returns.
+pub async fn save_loop(
+    graph: &RelationGraph,
+    status: &SaveStatus,
+    completed: &broadcast::Sender<u64>,
+    trigger: &mut Receiver<()>,
+    config: &SaveConfig,
+    shutdown: impl Future<Output = ()>,
+) {
+    tokio::pin!(shutdown);
+    loop {
+        select! {
+            _ = tokio::time::sleep(config.interval) => {}
+            _ = trigger.recv() => {}
+            _ = &mut shutdown => {
+                if status.is_dirty(graph) {
+                    save(graph, status, completed, config).await;
+                }
+                return;
+            }
+        };
+        // saving an unchanged graph would rotate the backups until all of them are the same
+        if status.is_dirty(graph) {
+            save(graph, status, completed, config).await;
+        }
+    }
+}
+
+async fn save(
+    graph: &RelationGraph,
+    status: &SaveStatus,
+    completed: &broadcast::Sender<u64>,
+    config: &SaveConfig,
+) {
+    info!("saving graph");
+    if let Err(err) = rotate_backups(&config.path, &config.backup_path, config.backup_count).await {
+        warn!("failed to rotate backups: {err}");
+    }
+    let snapshot = graph.snapshot().await;
+    let revision = snapshot.revision();
+    // a failed save is retried on the next tick
+    match snapshot.save_to_path(&config.path).await {
+        Ok(()) => {
+            status.saved(revision);
+            // no subscribers is not an error
+            _ = completed.send(revision);
+        }
+        Err(err) => error!("failed to save graph: {err}"),
+    }
+}
+
+/// namespace of the objects that represent a namespace (e.g. (`namespace`, `file`))
+pub const NAMESPACE_NS: &str = "namespace";
+/// relation that permits modifying the relations of an object or a whole namespace
+pub const GRANT_RELATION: &str = "grant";
+/// default size limit of received messages in bytes (4MiB, the default of tonic)
+pub const DEFAULT_MAX_DECODING_MESSAGE_SIZE: usize = 4 * 1024 * 1024;
+/// default size limit of sent messages in bytes (64MiB), large enough for big `expand` responses
+pub const DEFAULT_MAX_ENCODING_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+/// path of the savefile if `REBACS_GRAPH_PATH` is not set
+pub const DEFAULT_GRAPH_PATH: &str = "graph.dat";
+/// time between two saves if `REBACS_SAVE_INTERVAL_SECS` is not set
+pub const DEFAULT_SAVE_INTERVAL: Duration = Duration::from_secs(30);
+/// time between two fetches of the key of an issuer if `OIDC_KEY_REFRESH_INTERVAL` is not set
+pub const DEFAULT_KEY_REFRESH_INTERVAL: Duration = Duration::from_secs(3600);
+/// number of changes buffered for a `watch` stream before it misses changes
+const WATCH_CAPACITY: usize = 1024;
+
+/// installs the subscriber that logs the spans and events filtered by `RUST_LOG`
+///
+/// The records of the `log` crate are logged as events. If `OTEL_EXPORTER_OTLP_ENDPOINT` is set
+/// (e.g. `http://localhost:4317`), the spans are also exported to the OpenTelemetry collector,
+/// linked to the spans of the callers by [`grpc_service::parent_context`].
+fn init_tracing() {
+    let otel = env::var("OTEL_EXPORTER_OTLP_ENDPOINT").is_ok().then(|| {
+        let tracer = opentelemetry_otlp::new_pipeline()
+            .tracing()
+            .with_exporter(opentelemetry_otlp::new_exporter().tonic())
+            .with_trace_config(
+                trace::config()
+                    .with_resource(Resource::new([KeyValue::new("service.name", "rebacs")])),
+            )
+            .install_batch(runtime::Tokio)
+            .expect("OTEL_EXPORTER_OTLP_ENDPOINT must be a valid collector endpoint");
+        tracing_opentelemetry::layer().with_tracer(tracer)
+    });
+
+    tracing_subscriber::registry()
+        .with(EnvFilter::from_default_env())
+        .with(tracing_subscriber::fmt::layer())
+        .with(otel)
+        .init();
+}
+
+/// validation of the tokens of `issuer`
+///
+/// A token is accepted if its `aud` claim contains at least one of the `audiences`.
+pub fn token_validation(algorithm: Algorithm, issuer: &str, audiences: &[&str]) -> Validation {
+    let mut validation = Validation::new(algorithm);
+    validation.set_issuer(&[issuer]);
+    validation.set_audience(audiences);
+    validation
+}
+
+/// loads the key that signs the tokens
+///
+/// The key is taken from the PEM encoded `pem`, the PEM file at `pem_path` or fetched from the
+/// `issuer`, in this order.
+pub async fn load_oidc_pubkey(
+    issuer: &str,
+    pem: Option<String>,
+    pem_path: Option<String>,
+) -> DecodingKey {
+    let pem = if let Some(pem) = pem {
+        info!("loading public key from OIDC_PUBLIC_KEY");
+        pem
+    } else if let Some(pem_path) = pem_path {
+        info!("loading public key from {pem_path}");
+        fs::read_to_string(&pem_path)
+            .await
+            .expect("OIDC_PUBLIC_KEY_PATH readable")
+    } else {
+        info!("loading public key from {issuer}");
+        return fetch_oidc_pubkey(issuer)
+            .await
+            .unwrap_or_else(|err| panic!("failed to load public key from {issuer}: {err}"));
+    };
+
+    DecodingKey::from_rsa_pem(pem.as_bytes()).unwrap()
+}
+
+#[derive(Debug, Error)]
+pub enum KeyFetchError {
+    #[error("request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("invalid key: {0}")]
+    Key(#[from] jsonwebtoken::errors::Error),
+}
+
+/// fetches the key that signs the tokens from the discovery document of the `issuer`
+pub async fn fetch_oidc_pubkey(issuer: &str) -> Result<DecodingKey, KeyFetchError> {
+    let issuer_key = reqwest::get(issuer)
+        .await?
+        .error_for_status()?
+        .json::<IssuerDiscovery>()
+        .await?
+        .public_key;
+
+    let pem = format!(
+        "-----BEGIN PUBLIC KEY-----\n{}\n-----END PUBLIC KEY-----",
+        issuer_key
+    );
+    Ok(DecodingKey::from_rsa_pem(pem.as_bytes())?)
+}
+
+/// replaces `key` with the current key of the `issuer`, e.g. after the issuer rotated its key
+///
+/// The previous key is kept if the key can't be fetched.
+pub async fn refresh_oidc_pubkey(issuer: &str, key: &RwLock<DecodingKey>) {
+    match fetch_oidc_pubkey(issuer).await {
+        Ok(new_key) => {
+            info!("refreshed public key from {issuer}");
+            *key.write().unwrap() = new_key;
+        }
+        Err(err) => {
+            warn!("failed to refresh public key from {issuer}, keeping the previous one: {err}")
+        }
+    }
+}
+
+/// reads the graph from the savefile at `path`, falling back to the newest backup at `backup`
+///
+/// A missing savefile results in an empty graph. The backup is only used if the savefile can't
+/// be read, e.g. because it was truncated while it was written.
+pub async fn load(path: &Path, backup: &Path) -> Result<RelationGraph, SavefileError> {
+    let err = match read_graph(path).await {
+        Ok(graph) => return Ok(graph),
+        Err(SavefileError::Io(err)) if err.kind() == io::ErrorKind::NotFound => {
+            return Ok(RelationGraph::default())
+        }
+        Err(err) => err,
+    };
+
+    warn!(
+        "failed to read {}, falling back to {}: {err}",
+        path.display(),
+        backup.display()
+    );
+    read_graph(backup).await
+}
+
+async fn read_graph(path: &Path) -> Result<RelationGraph, SavefileError> {
+    let mut reader = BufReader::new(File::open(path).await?);
+    RelationGraph::read_savefile(&mut reader).await
+}
+
+/// state of the savefile, updated by the save task
+pub struct SaveStatus {
+    /// revision of the graph in the savefile
+    revision: AtomicU64,
+    /// time of the last successful save since the start of the server
+    last_saved: Mutex<Option<SystemTime>>,
 }
 
+impl SaveStatus {
+    /// creates the status of a savefile that contains `revision` of the graph
+    pub fn new(revision: u64) -> Self {
+        Self {
+            revision: AtomicU64::new(revision),
+            last_saved: Mutex::new(None),
+        }
+    }
+
+    /// records a successful save of `revision` of the graph
+    pub fn saved(&self, revision: u64) {
+        self.revision.store(revision, Ordering::Relaxed);
+        *self.last_saved.lock().unwrap() = Some(SystemTime::now());
+    }
+
+    /// returns the revision of the graph in the savefile
+    pub fn revision(&self) -> u64 {
+        self.revision.load(Ordering::Relaxed)
+    }
+
+    /// returns the time of the last successful save, `None` if there was none since the start
+    pub fn last_saved(&self) -> Option<SystemTime> {
+        *self.last_saved.lock().unwrap()
+    }
+
+    /// checks if `graph` has changes that are not in the savefile
+    pub fn is_dirty(&self, graph: &RelationGraph) -> bool {
+        graph.current_revision() > self.revision()
+    }
+}
+
+/// copies `path` to `backup` and keeps up to `count` backups in total
+///
+/// Older backups are shifted to `<backup>.1`, `<backup>.2`, ... before the copy, the oldest
+/// backup beyond `count` is overwritten.
+pub async fn rotate_backups(path: &Path, backup: &Path, count: usize) -> io::Result<()> {
+    if count == 0 {
+        return Ok(());
+    }
+
+    let backup = |i| backup_path(backup, i);
+
+    for i in (1..count).rev() {
+        match fs::rename(backup(i - 1), backup(i)).await {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+    }
+    match fs::copy(path, backup(0)).await {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+/// returns the path of the newest backup of the savefile at `path` if none is configured
+pub fn default_backup_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".bak");
+    PathBuf::from(name)
+}
+
+/// returns the path of the `i`-th backup, `backup` itself for the newest one
+fn backup_path(backup: &Path, i: usize) -> PathBuf {
+    let mut name = backup.as_os_str().to_owned();
+    if i > 0 {
+        name.push(format!(".{i}"));
+    }
+    PathBuf::from(name)
+}
+
+/// checks if `src` can modify the relations of `dst`
+///
+/// This is the case if `src` has the [`GRANT_RELATION`] on the object of `dst` or on the
+/// [`NAMESPACE_NS`] object of the namespace of `dst`.
 pub async fn can_write(
     graph: &RelationGraph,
     src: impl Into<ObjectOrSet<'_>>,
     dst: &Set,
     limit: Option<u32>,
 ) -> bool {
-    graph
-        .check(src, &(dst.namespace(), dst.id(), "grant").into(), limit)
+    let src: ObjectOrSet<'_> = src.into();
+    check(
+        graph,
+        &src,
+        &(dst.namespace(), dst.id(), GRANT_RELATION).into(),
+        limit,
+    )
+    .await
+        || check(
+            graph,
+            &src,
+            &(NAMESPACE_NS, dst.namespace(), GRANT_RELATION).into(),
+            limit,
+        )
         .await
 }
+
+/// [`RelationGraph::check`] that logs a warning if the search reached `limit`
+///
+/// Reaching the limit denies access, a relation chain that deep is most likely abuse.
+pub async fn check(
+    graph: &RelationGraph,
+    src: impl Into<ObjectOrSet<'_>>,
+    dst: &Set,
+    limit: Option<u32>,
+) -> bool {
+    let src: ObjectOrSet<'_> = src.into();
+    match graph.try_check(&src, dst, limit).await {
+        Ok(related) => related,
+        Err(LimitReached) => {
+            warn!(
+                "depth limit reached checking {}:{}#{}@{}:{}#{}",
+                dst.namespace(),
+                dst.id(),
+                dst.relation(),
+                src.namespace(),
+                src.id(),
+                src.relation().unwrap_or_default(),
+            );
+            false
+        }
+    }
+}
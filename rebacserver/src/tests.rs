@@ -0,0 +1,1415 @@
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use futures::{stream, StreamExt};
+use jsonwebtoken::{
+    encode, errors::ErrorKind, Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use opentelemetry::trace::{TraceContextExt, TracerProvider as _};
+use opentelemetry_sdk::testing::trace::InMemorySpanExporter;
+use rebacclient::RebacClient;
+use rebacdb::{Intersection, Object, RelationGraph, SavefileError, Set};
+use serde::Serialize;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+    sync::{broadcast, mpsc::channel},
+};
+use tonic::metadata::{MetadataMap, MetadataValue};
+use tonic::{
+    service::Interceptor,
+    transport::{server::Router, Channel, Endpoint, Server},
+    Code, Request,
+};
+use tonic_health::pb::{
+    health_check_response::ServingStatus, health_client::HealthClient, HealthCheckRequest,
+};
+use tonic_reflection::pb::{
+    server_reflection_client::ServerReflectionClient, server_reflection_request::MessageRequest,
+    server_reflection_response::MessageResponse, ServerReflectionRequest,
+};
+use tonic_types::StatusExt;
+use tracing_subscriber::layer::SubscriberExt;
+
+use crate::{
+    can_write, default_backup_path,
+    grpc_service::{
+        decode_failure_reason, extract_dst, extract_src, hash_api_key, paginate, parent_context,
+        parse_api_keys, AuthInterceptor, Claims, RebacService, API_KEY_ISSUER, DEFAULT_SUBJECT_NS,
+        ERROR_DOMAIN,
+    },
+    health_server, load, load_oidc_pubkey,
+    rebacs_proto::{
+        self,
+        error_details::Reason,
+        grant_req, is_permitted_req,
+        rebac_service_server::{RebacService as _, RebacServiceServer},
+        revoke_req, watch_res, BatchGrantReq, BatchIsPermittedReq, GrantReq, IsMemberReq,
+        IsPermittedReq, RevokeReq, SaveStatusReq, WatchReq,
+    },
+    reflection_server, refresh_oidc_pubkey, rotate_backups, save_loop, set_not_serving,
+    token_validation, SaveConfig, SaveStatus, GRANT_RELATION, NAMESPACE_NS,
+};
+
+#[tokio::test]
+async fn object_write_permission() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+
+    let foo_read: Set = ("file", "foo", "read").into();
+    let bar_read: Set = ("file", "bar", "read").into();
+
+    graph
+        .insert(&alice, &("file", "foo", GRANT_RELATION).into())
+        .await;
+
+    assert!(can_write(&graph, &alice, &foo_read, None).await);
+    assert!(!can_write(&graph, &alice, &bar_read, None).await);
+    assert!(!can_write(&graph, &bob, &foo_read, None).await);
+}
+
+#[tokio::test]
+async fn namespace_write_permission() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+
+    let foo_read: Set = ("file", "foo", "read").into();
+    let bar_write: Set = ("file", "bar", "write").into();
+    let app_read: Set = ("application", "foo", "read").into();
+
+    graph
+        .insert(&alice, &(NAMESPACE_NS, "file", GRANT_RELATION).into())
+        .await;
+
+    assert!(can_write(&graph, &alice, &foo_read, None).await);
+    assert!(can_write(&graph, &alice, &bar_write, None).await);
+    assert!(!can_write(&graph, &alice, &app_read, None).await);
+    assert!(!can_write(&graph, &bob, &foo_read, None).await);
+}
+
+#[derive(Serialize)]
+struct TestClaims<'a> {
+    aud: Vec<&'a str>,
+    exp: usize,
+    iat: usize,
+    iss: &'a str,
+    sub: &'a str,
+    azp: &'a str,
+}
+
+fn test_token(key: &[u8], sub: &str) -> String {
+    issuer_token(key, "https://issuer.test", sub)
+}
+
+fn issuer_token(key: &[u8], iss: &str, sub: &str) -> String {
+    let claims = TestClaims {
+        aud: vec!["rebacs"],
+        exp: 4_000_000_000,
+        iat: 0,
+        iss,
+        sub,
+        azp: "test",
+    };
+    encode(&Header::default(), &claims, &EncodingKey::from_secret(key)).unwrap()
+}
+
+fn test_issuer(key: &[u8], iss: &str) -> (Arc<RwLock<DecodingKey>>, Validation) {
+    (
+        Arc::new(RwLock::new(DecodingKey::from_secret(key))),
+        token_validation(Algorithm::HS256, iss, &["rebacs"]),
+    )
+}
+
+fn test_interceptor(key: &[u8]) -> AuthInterceptor {
+    AuthInterceptor {
+        issuers: vec![test_issuer(key, "https://issuer.test")],
+        ..Default::default()
+    }
+}
+
+fn authorized_request(token: &str) -> Request<()> {
+    let mut request = Request::new(());
+    request
+        .metadata_mut()
+        .insert("authorization", token.parse().unwrap());
+    request
+}
+
+fn test_service(graph: RelationGraph) -> RebacService {
+    RebacService {
+        graph: Arc::new(graph),
+        save_trigger: channel(1).0,
+        save_completed: broadcast::channel(1).0,
+        save_status: Arc::new(SaveStatus::new(0)),
+        max_depth: None,
+        subject_namespace: DEFAULT_SUBJECT_NS.to_string(),
+        read_only: false,
+        changes: broadcast::channel(16).0,
+    }
+}
+
+/// builds a request with `message` that passed the [`test_interceptor`] for the subject `sub`
+fn test_request<T>(message: T, sub: &str) -> Request<T> {
+    test_interceptor(b"secret")
+        .call(authorized_request(&test_token(b"secret", sub)))
+        .unwrap()
+        .map(|_| message)
+}
+
+#[test]
+fn interceptor_injects_claims() {
+    let mut interceptor = test_interceptor(b"secret");
+
+    let request = interceptor
+        .call(authorized_request(&test_token(b"secret", "alice")))
+        .unwrap();
+    assert_eq!(request.extensions().get::<Claims>().unwrap().sub, "alice");
+
+    let err = interceptor.call(Request::new(())).unwrap_err();
+    assert_eq!(err.code(), Code::Unauthenticated);
+
+    let err = interceptor
+        .call(authorized_request(&test_token(b"other", "alice")))
+        .unwrap_err();
+    assert_eq!(err.code(), Code::Unauthenticated);
+}
+
+#[test]
+fn api_keys() {
+    let mut interceptor = AuthInterceptor {
+        api_keys: Arc::new(
+            parse_api_keys(&format!(
+                "# service accounts\n\n{} backup-service\n",
+                hash_api_key("key-1")
+            ))
+            .unwrap(),
+        ),
+        ..test_interceptor(b"secret")
+    };
+    let claims = |request: Request<()>| request.extensions().get::<Claims>().unwrap().clone();
+
+    let request = interceptor.call(authorized_request("key-1")).unwrap();
+    let api_key_claims = claims(request);
+    assert_eq!(api_key_claims.sub, "backup-service");
+    assert_eq!(api_key_claims.iss, API_KEY_ISSUER);
+
+    // tokens of the issuers are still accepted next to the api keys
+    let request = interceptor
+        .call(authorized_request(&test_token(b"secret", "alice")))
+        .unwrap();
+    assert_eq!(claims(request).sub, "alice");
+
+    // an unknown key is validated as token and rejected
+    let err = interceptor.call(authorized_request("key-2")).unwrap_err();
+    assert_eq!(err.code(), Code::Unauthenticated);
+
+    // the hash of the key isn't accepted as key
+    let err = interceptor
+        .call(authorized_request(&hash_api_key("key-1")))
+        .unwrap_err();
+    assert_eq!(err.code(), Code::Unauthenticated);
+
+    assert_eq!(
+        hash_api_key("abc"),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+    for (content, line) in [
+        ("user", 1),
+        ("\nabc service", 2),
+        (&format!("{} ", hash_api_key("a")), 1),
+        (&format!("{} service", hash_api_key("a").to_uppercase()), 1),
+    ] {
+        assert_eq!(parse_api_keys(content), Err(line), "{content}");
+    }
+}
+
+#[test]
+fn invalid_tokens() {
+    let mut interceptor = test_interceptor(b"secret");
+
+    // header values with other bytes than visible ASCII are rejected, not a panic
+    let mut request = Request::new(());
+    request.metadata_mut().insert(
+        "authorization",
+        MetadataValue::try_from(&b"token\xff"[..]).unwrap(),
+    );
+    let err = interceptor.call(request).unwrap_err();
+    assert_eq!(err.code(), Code::Unauthenticated);
+
+    for (kind, reason) in [
+        (ErrorKind::ExpiredSignature, "expired"),
+        (ErrorKind::ImmatureSignature, "immature"),
+        (ErrorKind::InvalidSignature, "bad_signature"),
+        (ErrorKind::InvalidAudience, "wrong_audience"),
+        (ErrorKind::InvalidIssuer, "wrong_issuer"),
+        (ErrorKind::InvalidAlgorithm, "wrong_algorithm"),
+        (ErrorKind::InvalidAlgorithmName, "wrong_algorithm"),
+        (
+            ErrorKind::MissingRequiredClaim("exp".to_string()),
+            "missing_claim",
+        ),
+        (ErrorKind::InvalidToken, "malformed"),
+    ] {
+        assert_eq!(decode_failure_reason(&kind), reason, "{kind:?}");
+    }
+}
+
+#[test]
+fn multiple_issuers() {
+    let mut interceptor = AuthInterceptor {
+        issuers: vec![
+            test_issuer(b"a", "https://a.test"),
+            test_issuer(b"b", "https://b.test"),
+        ],
+        ..Default::default()
+    };
+    let sub = |request: Request<()>| request.extensions().get::<Claims>().unwrap().sub.clone();
+
+    let request = interceptor
+        .call(authorized_request(&issuer_token(
+            b"a",
+            "https://a.test",
+            "alice",
+        )))
+        .unwrap();
+    assert_eq!(sub(request), "alice");
+    let request = interceptor
+        .call(authorized_request(&issuer_token(
+            b"b",
+            "https://b.test",
+            "bob",
+        )))
+        .unwrap();
+    assert_eq!(sub(request), "bob");
+
+    // the key of one issuer doesn't validate tokens of the other
+    for token in [
+        issuer_token(b"a", "https://b.test", "alice"),
+        issuer_token(b"c", "https://c.test", "alice"),
+    ] {
+        let err = interceptor.call(authorized_request(&token)).unwrap_err();
+        assert_eq!(err.code(), Code::Unauthenticated);
+    }
+}
+
+#[test]
+fn pagination() {
+    let items: Vec<u32> = (0..250).collect();
+
+    let (page, token) = paginate(items.clone(), 0, "").unwrap();
+    assert_eq!(page, (0..100).collect::<Vec<_>>());
+    assert_eq!(token, "100");
+
+    let (page, token) = paginate(items.clone(), 100, &token).unwrap();
+    assert_eq!(page, (100..200).collect::<Vec<_>>());
+
+    let (page, token) = paginate(items.clone(), 100, &token).unwrap();
+    assert_eq!(page, (200..250).collect::<Vec<_>>());
+    assert_eq!(token, "");
+
+    let (page, token) = paginate(items.clone(), 5000, "").unwrap();
+    assert_eq!(page.len(), 250);
+    assert_eq!(token, "");
+
+    assert_eq!(
+        paginate(items, 10, "abc").unwrap_err().code(),
+        Code::InvalidArgument
+    );
+}
+
+#[test]
+fn reject_invalid_characters() {
+    let alice: Object = ("user", "alice").into();
+    let set = |id: &str| rebacs_proto::Set {
+        namespace: "file".to_string(),
+        id: id.to_string(),
+        relation: "read".to_string(),
+    };
+
+    assert!(extract_dst(Some(set("foo"))).is_ok());
+    assert_eq!(
+        extract_dst(Some(set("foo]\n[file:bar")))
+            .unwrap_err()
+            .code(),
+        Code::InvalidArgument
+    );
+
+    let src = grant_req::Src::SrcObj(rebacs_proto::Object {
+        namespace: "user".to_string(),
+        id: "bob#admin".to_string(),
+    });
+    assert_eq!(
+        extract_src(Some(src), &alice).unwrap_err().code(),
+        Code::InvalidArgument
+    );
+    assert!(extract_src(None::<grant_req::Src>, &alice).is_ok());
+}
+
+#[tokio::test]
+async fn subject_namespace() {
+    let graph = RelationGraph::default();
+    let foo_read = rebacs_proto::Set {
+        namespace: "file".to_string(),
+        id: "foo".to_string(),
+        relation: "read".to_string(),
+    };
+    graph
+        .insert(
+            &Object::from(("principal", "alice")),
+            &("file", "foo", "read").into(),
+        )
+        .await;
+
+    let mut service = test_service(graph);
+    let mut interceptor = test_interceptor(b"secret");
+    let mut is_permitted = |service: &RebacService| {
+        let request = interceptor
+            .call(authorized_request(&test_token(b"secret", "alice")))
+            .unwrap()
+            .map(|_| IsPermittedReq {
+                src: None,
+                dst: Some(foo_read.clone()),
+                min_revision: 0,
+            });
+        let service = service.clone();
+        async move {
+            service
+                .is_permitted(request)
+                .await
+                .unwrap()
+                .into_inner()
+                .permitted
+        }
+    };
+
+    assert!(!is_permitted(&service).await);
+    service.subject_namespace = "principal".to_string();
+    assert!(is_permitted(&service).await);
+}
+
+#[tokio::test]
+async fn dry_run() {
+    let graph = RelationGraph::default();
+    let alice: Object = ("user", "alice").into();
+    let bob = rebacs_proto::Object {
+        namespace: "user".to_string(),
+        id: "bob".to_string(),
+    };
+    let foo_read = rebacs_proto::Set {
+        namespace: "file".to_string(),
+        id: "foo".to_string(),
+        relation: "read".to_string(),
+    };
+    graph
+        .insert(&alice, &("file", "foo", GRANT_RELATION).into())
+        .await;
+    let service = test_service(graph);
+
+    let grant = |dry_run| GrantReq {
+        src: Some(grant_req::Src::SrcObj(bob.clone())),
+        dst: Some(foo_read.clone()),
+        dry_run,
+    };
+    let revoke = |dry_run| RevokeReq {
+        src: Some(revoke_req::Src::SrcObj(bob.clone())),
+        dst: Some(foo_read.clone()),
+        dry_run,
+    };
+    let bob_foo_read = |service: &RebacService| {
+        let graph = service.graph.clone();
+        async move {
+            graph
+                .has(
+                    &Object::from(("user", "bob")),
+                    &("file", "foo", "read").into(),
+                )
+                .await
+        }
+    };
+
+    let res = service.grant(test_request(grant(true), "alice")).await;
+    assert!(res.unwrap().into_inner().created);
+    assert!(!bob_foo_read(&service).await);
+
+    let res = service.grant(test_request(grant(true), "bob")).await;
+    assert_eq!(res.unwrap_err().code(), Code::PermissionDenied);
+
+    service
+        .grant(test_request(grant(false), "alice"))
+        .await
+        .unwrap();
+    let res = service.grant(test_request(grant(true), "alice")).await;
+    assert!(!res.unwrap().into_inner().created);
+
+    let res = service.revoke(test_request(revoke(true), "alice")).await;
+    assert!(res.unwrap().into_inner().removed);
+    assert!(bob_foo_read(&service).await);
+}
+
+#[tokio::test]
+async fn batch_grant() {
+    let graph = RelationGraph::default();
+    let alice: Object = ("user", "alice").into();
+    graph
+        .insert(&alice, &("file", "foo", GRANT_RELATION).into())
+        .await;
+    let service = test_service(graph);
+
+    let grant = |user: &str, file: &str| GrantReq {
+        src: Some(grant_req::Src::SrcObj(rebacs_proto::Object {
+            namespace: "user".to_string(),
+            id: user.to_string(),
+        })),
+        dst: Some(rebacs_proto::Set {
+            namespace: "file".to_string(),
+            id: file.to_string(),
+            relation: "read".to_string(),
+        }),
+        dry_run: false,
+    };
+    let batch = |grants, dry_run| test_request(BatchGrantReq { grants, dry_run }, "alice");
+    let bob_foo_read: Set = ("file", "foo", "read").into();
+    let bob = Object::from(("user", "bob"));
+
+    // one unauthorized dst rejects the whole batch
+    let err = service
+        .batch_grant(batch(
+            vec![
+                grant("bob", "foo"),
+                grant("charlie", "foo"),
+                grant("bob", "bar"),
+            ],
+            false,
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::PermissionDenied);
+    assert!(err.message().contains("grants[2].dst file:bar#read"));
+    assert!(!service.graph.has(&bob, &bob_foo_read).await);
+
+    let err = service
+        .batch_grant(batch(vec![grant("bob", "foo"), grant("bob", "")], false))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+    assert!(err.message().starts_with("grants[1]: "));
+
+    let grants = vec![
+        grant("bob", "foo"),
+        grant("charlie", "foo"),
+        grant("bob", "foo"),
+    ];
+    let res = service
+        .batch_grant(batch(grants.clone(), true))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(res.created, vec![true, true, false]);
+    assert!(!service.graph.has(&bob, &bob_foo_read).await);
+
+    let res = service
+        .batch_grant(batch(grants, false))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(res.created, vec![true, true, false]);
+    assert_eq!(res.revision, service.graph.current_revision());
+    assert!(service.graph.has(&bob, &bob_foo_read).await);
+}
+
+#[tokio::test]
+async fn watch() {
+    let graph = RelationGraph::default();
+    graph
+        .insert(
+            &Object::from(("user", "alice")),
+            &(NAMESPACE_NS, "file", GRANT_RELATION).into(),
+        )
+        .await;
+    graph
+        .insert(
+            &Object::from(("user", "alice")),
+            &(NAMESPACE_NS, "folder", GRANT_RELATION).into(),
+        )
+        .await;
+    let service = test_service(graph);
+    let bob = rebacs_proto::Object {
+        namespace: "user".to_string(),
+        id: "bob".to_string(),
+    };
+    let foo_read = rebacs_proto::Set {
+        namespace: "file".to_string(),
+        id: "foo".to_string(),
+        relation: "read".to_string(),
+    };
+    let grant = GrantReq {
+        src: Some(grant_req::Src::SrcObj(bob.clone())),
+        dst: Some(foo_read.clone()),
+        dry_run: false,
+    };
+    let revoke = RevokeReq {
+        src: Some(revoke_req::Src::SrcObj(bob.clone())),
+        dst: Some(foo_read.clone()),
+        dry_run: false,
+    };
+
+    // changes before the subscription are not replayed
+    service
+        .grant(test_request(grant.clone(), "alice"))
+        .await
+        .unwrap();
+    let watch_req = |namespace: &str| WatchReq {
+        namespace: namespace.to_string(),
+    };
+    let mut changes = service
+        .watch(test_request(watch_req("file"), "alice"))
+        .await
+        .unwrap()
+        .into_inner();
+
+    // the changes of a namespace are only streamed to subjects that can grant in it
+    let err = service
+        .watch(test_request(watch_req("file"), "bob"))
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(err.code(), Code::PermissionDenied);
+    let err = service
+        .watch(test_request(watch_req(""), "alice"))
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(err.code(), Code::InvalidArgument);
+
+    // changes of other namespaces are not streamed
+    let folder_grant = GrantReq {
+        src: Some(grant_req::Src::SrcObj(bob.clone())),
+        dst: Some(rebacs_proto::Set {
+            namespace: "folder".to_string(),
+            id: "bar".to_string(),
+            relation: "read".to_string(),
+        }),
+        dry_run: false,
+    };
+    service
+        .grant(test_request(folder_grant, "alice"))
+        .await
+        .unwrap();
+
+    // unchanged graphs are not announced
+    service
+        .grant(test_request(grant.clone(), "alice"))
+        .await
+        .unwrap();
+    service
+        .revoke(test_request(revoke.clone(), "alice"))
+        .await
+        .unwrap();
+    service
+        .revoke(test_request(revoke.clone(), "alice"))
+        .await
+        .unwrap();
+    service.grant(test_request(grant, "alice")).await.unwrap();
+
+    let change = changes.next().await.unwrap().unwrap();
+    assert_eq!(change.operation(), watch_res::Operation::Revoke);
+    assert_eq!(change.src, Some(watch_res::Src::SrcObj(bob.clone())));
+    assert_eq!(change.dst, Some(foo_read.clone()));
+    assert_eq!(change.revision, 5);
+
+    let change = changes.next().await.unwrap().unwrap();
+    assert_eq!(change.operation(), watch_res::Operation::Grant);
+    assert_eq!(change.src, Some(watch_res::Src::SrcObj(bob)));
+    assert_eq!(change.revision, 6);
+}
+
+#[tokio::test]
+async fn backup_rotation() {
+    let dir = std::env::temp_dir().join(format!("rebacs-backups-{}", std::process::id()));
+    tokio::fs::create_dir_all(&dir).await.unwrap();
+    let path = dir.join("graph.dat");
+    let read = |name: &str| std::fs::read_to_string(dir.join(name)).ok();
+
+    for revision in 1..=4 {
+        tokio::fs::write(&path, revision.to_string()).await.unwrap();
+        rotate_backups(&path, &default_backup_path(&path), 3)
+            .await
+            .unwrap();
+    }
+
+    assert_eq!(read("graph.dat.bak").as_deref(), Some("4"));
+    assert_eq!(read("graph.dat.bak.1").as_deref(), Some("3"));
+    assert_eq!(read("graph.dat.bak.2").as_deref(), Some("2"));
+    assert_eq!(read("graph.dat.bak.3"), None);
+
+    tokio::fs::remove_dir_all(&dir).await.unwrap();
+}
+
+const TEST_PUBLIC_KEY: &str = "-----BEGIN PUBLIC KEY-----\n\
+MIIBIjANBgkqhkiG9w0BAQEFAAOCAQ8AMIIBCgKCAQEAwirqtzURL8j/VmX8JjVv\n\
+mVFa4cz86e/QMth8nSNiN0405Flu+WEyWECbwI9oC4UEAHWcnSnqXIlFlHDeu6IQ\n\
+m3Zo6CGpU+K38WtOdJQw0f0rIR320fWWqU7Rdl6mGVsTVnjQO/p546FptyMBfSxw\n\
+huwuczitdAEBQce3BXfhkZ0mj01fets/af5Ig7R5z6y0Jh6KaF/rLhsDx3pzL94R\n\
+Hr9sFapJ6o/loMFjYoi7XWvzqVhIRJjDk63/5pCeEJMrwodpYaI6opG8UxJgQVLE\n\
+C83ib8q9pPzfL208Qwr1+yGYymV/SxnfM+5MSbKhsd8+vSqIm/0XN0UAkOKnMQZS\n\
+IwIDAQAB\n\
+-----END PUBLIC KEY-----";
+
+#[tokio::test]
+async fn local_oidc_pubkey() {
+    // the issuer is never contacted if a key is configured
+    let issuer = "http://unreachable.invalid";
+
+    load_oidc_pubkey(issuer, Some(TEST_PUBLIC_KEY.to_string()), None).await;
+
+    let path = std::env::temp_dir().join(format!("rebacs-key-{}.pem", std::process::id()));
+    tokio::fs::write(&path, TEST_PUBLIC_KEY).await.unwrap();
+    load_oidc_pubkey(issuer, None, Some(path.to_string_lossy().to_string())).await;
+    tokio::fs::remove_file(&path).await.unwrap();
+}
+
+/// serves the discovery document with `public_key` to a single request
+async fn serve_discovery(public_key: &str) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let body = format!("{{\"public_key\":\"{public_key}\"}}");
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut request = [0; 1024];
+        _ = stream.read(&mut request).await.unwrap();
+        let response = format!(
+            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        stream.write_all(response.as_bytes()).await.unwrap();
+    });
+    format!("http://{addr}")
+}
+
+#[tokio::test]
+async fn oidc_pubkey_refresh() {
+    let mut interceptor = test_interceptor(b"secret");
+    let token = test_token(b"secret", "alice");
+    let key = interceptor.issuers[0].0.clone();
+
+    // the previous key is kept if the issuer is unreachable
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let unreachable = format!("http://{}", listener.local_addr().unwrap());
+    drop(listener);
+    refresh_oidc_pubkey(&unreachable, &key).await;
+    assert!(interceptor.call(authorized_request(&token)).is_ok());
+
+    // the rotated key doesn't validate tokens signed with the previous one
+    let public_key = TEST_PUBLIC_KEY
+        .lines()
+        .filter(|x| !x.starts_with("-----"))
+        .collect::<String>();
+    refresh_oidc_pubkey(&serve_discovery(&public_key).await, &key).await;
+    let err = interceptor.call(authorized_request(&token)).unwrap_err();
+    assert_eq!(err.code(), Code::Unauthenticated);
+}
+
+#[tokio::test]
+async fn read_only() {
+    let graph = RelationGraph::default();
+    let alice: Object = ("user", "alice").into();
+    let foo_read = rebacs_proto::Set {
+        namespace: "file".to_string(),
+        id: "foo".to_string(),
+        relation: "read".to_string(),
+    };
+    graph
+        .insert(&alice, &("file", "foo", GRANT_RELATION).into())
+        .await;
+    graph.insert(&alice, &("file", "foo", "read").into()).await;
+    let mut service = test_service(graph);
+    service.read_only = true;
+
+    let grant = GrantReq {
+        src: None,
+        dst: Some(foo_read.clone()),
+        dry_run: false,
+    };
+    let res = service.grant(test_request(grant, "alice")).await;
+    assert_eq!(res.unwrap_err().code(), Code::FailedPrecondition);
+
+    let revoke = RevokeReq {
+        src: None,
+        dst: Some(foo_read.clone()),
+        dry_run: true,
+    };
+    let res = service.revoke(test_request(revoke, "alice")).await;
+    assert_eq!(res.unwrap_err().code(), Code::FailedPrecondition);
+
+    let is_permitted = IsPermittedReq {
+        src: None,
+        dst: Some(foo_read),
+        min_revision: 0,
+    };
+    let res = service
+        .is_permitted(test_request(is_permitted, "alice"))
+        .await;
+    assert!(res.unwrap().into_inner().permitted);
+}
+
+#[tokio::test]
+async fn min_revision() {
+    let graph = RelationGraph::default();
+    graph
+        .insert(
+            &Object::from(("user", "alice")),
+            &("file", "foo", GRANT_RELATION).into(),
+        )
+        .await;
+    let service = test_service(graph);
+    let bob = rebacs_proto::Object {
+        namespace: "user".to_string(),
+        id: "bob".to_string(),
+    };
+    let foo_read = rebacs_proto::Set {
+        namespace: "file".to_string(),
+        id: "foo".to_string(),
+        relation: "read".to_string(),
+    };
+    let is_permitted = |min_revision| IsPermittedReq {
+        src: Some(is_permitted_req::Src::SrcObj(bob.clone())),
+        dst: Some(foo_read.clone()),
+        min_revision,
+    };
+
+    let grant = GrantReq {
+        src: Some(grant_req::Src::SrcObj(bob.clone())),
+        dst: Some(foo_read.clone()),
+        dry_run: false,
+    };
+    let revision = service
+        .grant(test_request(grant, "alice"))
+        .await
+        .unwrap()
+        .into_inner()
+        .revision;
+    assert_eq!(revision, 2);
+
+    let res = service
+        .is_permitted(test_request(is_permitted(revision), "alice"))
+        .await;
+    assert!(res.unwrap().into_inner().permitted);
+
+    let res = service
+        .is_permitted(test_request(is_permitted(revision + 1), "alice"))
+        .await;
+    assert_eq!(res.unwrap_err().code(), Code::FailedPrecondition);
+
+    let batch = BatchIsPermittedReq {
+        checks: vec![is_permitted(0), is_permitted(revision + 1)],
+    };
+    let res = service
+        .batch_is_permitted(test_request(batch, "alice"))
+        .await;
+    assert_eq!(res.unwrap_err().code(), Code::FailedPrecondition);
+
+    let revoke = RevokeReq {
+        src: Some(revoke_req::Src::SrcObj(bob)),
+        dst: Some(foo_read),
+        dry_run: false,
+    };
+    let res = service.revoke(test_request(revoke, "alice")).await;
+    assert_eq!(res.unwrap().into_inner().revision, 3);
+}
+
+#[tokio::test]
+async fn batch_is_permitted() {
+    let graph = RelationGraph::default();
+    graph
+        .insert(
+            &Object::from(("user", "alice")),
+            &("file", "foo", "read").into(),
+        )
+        .await;
+    let service = test_service(graph);
+    let check = |src: Option<&str>, id: &str| IsPermittedReq {
+        src: src.map(|id| {
+            is_permitted_req::Src::SrcObj(rebacs_proto::Object {
+                namespace: "user".to_string(),
+                id: id.to_string(),
+            })
+        }),
+        dst: Some(rebacs_proto::Set {
+            namespace: "file".to_string(),
+            id: id.to_string(),
+            relation: "read".to_string(),
+        }),
+        min_revision: 0,
+    };
+
+    let request = test_request(
+        BatchIsPermittedReq {
+            checks: vec![
+                check(None, "foo"),
+                check(None, "bar"),
+                check(Some("bob"), "foo"),
+            ],
+        },
+        "alice",
+    );
+    let permitted = service
+        .batch_is_permitted(request)
+        .await
+        .unwrap()
+        .into_inner()
+        .permitted;
+    assert_eq!(permitted, vec![true, false, false]);
+
+    let request = test_request(
+        BatchIsPermittedReq {
+            checks: vec![check(None, "foo"), check(None, "")],
+        },
+        "alice",
+    );
+    let err = service.batch_is_permitted(request).await.unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+    assert!(err.message().starts_with("checks[1]: "));
+}
+
+#[tokio::test]
+async fn is_member() {
+    let graph = RelationGraph::default();
+    graph
+        .insert(
+            &Object::from(("user", "alice")),
+            &("group", "eng", "member").into(),
+        )
+        .await;
+    let service = test_service(graph);
+    let eng = rebacs_proto::Object {
+        namespace: "group".to_string(),
+        id: "eng".to_string(),
+    };
+    let is_member = |subject: Option<&str>, member_relation: &str, sub: &str| {
+        let request = test_request(
+            IsMemberReq {
+                subject: subject.map(|id| rebacs_proto::Object {
+                    namespace: "user".to_string(),
+                    id: id.to_string(),
+                }),
+                group: Some(eng.clone()),
+                member_relation: member_relation.to_string(),
+            },
+            sub,
+        );
+        let service = service.clone();
+        async move { service.is_member(request).await }
+    };
+
+    assert!(
+        is_member(None, "", "alice")
+            .await
+            .unwrap()
+            .into_inner()
+            .member
+    );
+    assert!(
+        !is_member(None, "", "bob")
+            .await
+            .unwrap()
+            .into_inner()
+            .member
+    );
+    assert!(
+        is_member(Some("alice"), "member", "bob")
+            .await
+            .unwrap()
+            .into_inner()
+            .member
+    );
+    assert!(
+        !is_member(Some("alice"), "admin", "alice")
+            .await
+            .unwrap()
+            .into_inner()
+            .member
+    );
+    assert_eq!(
+        is_member(None, "member&admin", "alice")
+            .await
+            .unwrap_err()
+            .code(),
+        Code::InvalidArgument
+    );
+}
+
+#[tokio::test]
+async fn load_backup_fallback() {
+    let dir = std::env::temp_dir().join(format!("rebacs-load-{}", std::process::id()));
+    tokio::fs::create_dir_all(&dir).await.unwrap();
+    let path = dir.join("graph.dat");
+    let alice: Object = ("user", "alice").into();
+    let foo_read: Set = ("file", "foo", "read").into();
+
+    assert!(
+        !load(&path, &default_backup_path(&path))
+            .await
+            .unwrap()
+            .contains(&foo_read)
+            .await
+    );
+
+    tokio::fs::write(&path, "[file:foo]\nread = [ user:alice ]\n")
+        .await
+        .unwrap();
+    tokio::fs::copy(&path, dir.join("graph.dat.bak"))
+        .await
+        .unwrap();
+    tokio::fs::write(&path, "[file:foo]\nread = [ user ]\n")
+        .await
+        .unwrap();
+    assert!(
+        load(&path, &default_backup_path(&path))
+            .await
+            .unwrap()
+            .has(&alice, &foo_read)
+            .await
+    );
+
+    tokio::fs::write(dir.join("graph.dat.bak"), "[file]\n")
+        .await
+        .unwrap();
+    let Err(err) = load(&path, &default_backup_path(&path)).await else {
+        panic!("corrupt savefile and backup were loaded");
+    };
+    assert!(matches!(err, SavefileError::InvalidSection { line: 1, .. }));
+
+    tokio::fs::remove_dir_all(&dir).await.unwrap();
+}
+
+#[tokio::test]
+async fn save_status() {
+    let service = test_service(RelationGraph::default());
+    let status = |service: &RebacService| {
+        let service = service.clone();
+        async move {
+            service
+                .save_status(test_request(SaveStatusReq {}, "alice"))
+                .await
+                .unwrap()
+                .into_inner()
+        }
+    };
+
+    let res = status(&service).await;
+    assert!(!res.dirty);
+    assert_eq!(res.last_saved, 0);
+
+    service
+        .graph
+        .insert(
+            &Object::from(("user", "alice")),
+            &("file", "foo", "read").into(),
+        )
+        .await;
+    let res = status(&service).await;
+    assert!(res.dirty);
+    assert_eq!(res.saved_revision, 0);
+
+    service.save_status.saved(service.graph.current_revision());
+    let res = status(&service).await;
+    assert!(!res.dirty);
+    assert_eq!(res.saved_revision, res.revision);
+    assert!(res.last_saved > 0);
+}
+
+#[tokio::test]
+async fn save_only_changes() {
+    let dir = std::env::temp_dir().join(format!("rebacs-unchanged-{}", std::process::id()));
+    tokio::fs::create_dir_all(&dir).await.unwrap();
+    let config = SaveConfig {
+        path: dir.join("graph.dat"),
+        backup_path: dir.join("backups"),
+        backup_count: 3,
+        interval: Duration::from_millis(10),
+    };
+
+    let graph = RelationGraph::default();
+    graph
+        .insert(
+            &Object::from(("user", "alice")),
+            &("file", "foo", "read").into(),
+        )
+        .await;
+    let status = SaveStatus::new(0);
+    let (completed, _) = broadcast::channel(1);
+    let (_trigger_tx, mut trigger) = channel(1);
+
+    // only the first tick saves, the following ones don't rotate the backups
+    let shutdown = tokio::time::sleep(Duration::from_millis(200));
+    save_loop(&graph, &status, &completed, &mut trigger, &config, shutdown).await;
+    assert!(!status.is_dirty(&graph));
+    assert!(config.path.exists());
+    assert!(!config.backup_path.exists());
+
+    tokio::fs::remove_dir_all(&dir).await.unwrap();
+}
+
+#[tokio::test]
+async fn save_on_shutdown() {
+    let dir = std::env::temp_dir().join(format!("rebacs-shutdown-{}", std::process::id()));
+    tokio::fs::create_dir_all(&dir).await.unwrap();
+    let config = SaveConfig {
+        path: dir.join("graph.dat"),
+        backup_path: dir.join("backups"),
+        backup_count: 1,
+        interval: Duration::from_secs(3600),
+    };
+
+    let graph = RelationGraph::default();
+    let status = SaveStatus::new(graph.current_revision());
+    let (completed, _) = broadcast::channel(1);
+    let (_trigger_tx, mut trigger) = channel(1);
+    let alice: Object = ("user", "alice").into();
+    let foo_read: Set = ("file", "foo", "read").into();
+
+    // nothing to save
+    save_loop(&graph, &status, &completed, &mut trigger, &config, async {}).await;
+    assert!(!config.path.exists());
+
+    graph.insert(&alice, &foo_read).await;
+    save_loop(&graph, &status, &completed, &mut trigger, &config, async {}).await;
+    assert!(!status.is_dirty(&graph));
+    assert!(
+        load(&config.path, &config.backup_path)
+            .await
+            .unwrap()
+            .has(&alice, &foo_read)
+            .await
+    );
+
+    // the second save backs up the first one to the configured backup path
+    graph.remove(&alice, &foo_read).await;
+    save_loop(&graph, &status, &completed, &mut trigger, &config, async {}).await;
+    tokio::fs::write(&config.path, "[file]\n").await.unwrap();
+    assert!(
+        load(&config.path, &config.backup_path)
+            .await
+            .unwrap()
+            .has(&alice, &foo_read)
+            .await
+    );
+
+    tokio::fs::remove_dir_all(&dir).await.unwrap();
+}
+
+/// serves `router` on a local port and returns a channel to it
+async fn serve_local(router: Router) -> Channel {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let incoming = stream::unfold(listener, |listener| async {
+        let stream = listener.accept().await.map(|(stream, _)| stream);
+        Some((stream, listener))
+    });
+    tokio::spawn(router.serve_with_incoming(incoming));
+
+    Endpoint::from_shared(format!("http://{addr}"))
+        .unwrap()
+        .connect()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn reflection() {
+    let channel = serve_local(Server::builder().add_service(reflection_server())).await;
+    let mut client = ServerReflectionClient::new(channel);
+    let request = ServerReflectionRequest {
+        host: String::new(),
+        message_request: Some(MessageRequest::ListServices(String::new())),
+    };
+    let mut responses = client
+        .server_reflection_info(stream::iter([request]))
+        .await
+        .unwrap()
+        .into_inner();
+    let Some(MessageResponse::ListServicesResponse(services)) =
+        responses.next().await.unwrap().unwrap().message_response
+    else {
+        panic!("services weren't listed");
+    };
+    assert!(services
+        .service
+        .iter()
+        .any(|x| x.name == "eu.zettoit.rebacs.RebacService"));
+}
+
+#[tokio::test]
+async fn health() {
+    let (mut reporter, server) = health_server().await;
+    let client = HealthClient::new(serve_local(Server::builder().add_service(server)).await);
+    let status = |service: &str| {
+        let mut client = client.clone();
+        let request = HealthCheckRequest {
+            service: service.to_string(),
+        };
+        async move { client.check(request).await.unwrap().into_inner().status() }
+    };
+
+    assert_eq!(status("").await, ServingStatus::Serving);
+    assert_eq!(
+        status("eu.zettoit.rebacs.RebacService").await,
+        ServingStatus::Serving
+    );
+
+    set_not_serving(&mut reporter).await;
+    assert_eq!(status("").await, ServingStatus::NotServing);
+    assert_eq!(
+        status("eu.zettoit.rebacs.RebacService").await,
+        ServingStatus::NotServing
+    );
+}
+
+#[tokio::test]
+async fn client_intersection() {
+    let graph = RelationGraph::default();
+
+    let alice: Object = ("user", "alice").into();
+    let bob: Object = ("user", "bob").into();
+    let charlie: Object = ("user", "charlie").into();
+
+    let eng_admin: Set = ("group", "eng", "admin").into();
+    let eng_member: Set = ("group", "eng", "member").into();
+    let foo_read: Set = ("file", "foo", "read").into();
+    let eng_admin_member = Intersection::new(
+        "group".to_string(),
+        "eng".to_string(),
+        vec!["admin".to_string(), "member".to_string()],
+    );
+
+    graph
+        .insert(&alice, &(NAMESPACE_NS, "file", GRANT_RELATION).into())
+        .await;
+    graph.insert(&bob, &eng_admin).await;
+    graph.insert(&bob, &eng_member).await;
+    graph.insert(&charlie, &eng_member).await;
+
+    let server =
+        RebacServiceServer::with_interceptor(test_service(graph), test_interceptor(b"secret"));
+    let channel = serve_local(Server::builder().add_service(server)).await;
+    let mut client = RebacClient::new(channel, || test_token(b"secret", "alice"));
+
+    assert!(client.grant(&eng_admin_member, &foo_read).await.unwrap());
+    assert!(client.exists(&eng_admin_member, &foo_read).await.unwrap());
+    assert!(client.is_permitted(&bob, &foo_read).await.unwrap());
+    assert!(!client.is_permitted(&charlie, &foo_read).await.unwrap());
+    assert!(client.revoke(&eng_admin_member, &foo_read).await.unwrap());
+    assert!(!client.is_permitted(&bob, &foo_read).await.unwrap());
+}
+
+#[tokio::test]
+async fn error_details() {
+    let details = |status: &tonic::Status| {
+        let details = status.get_error_details();
+        let info = details.error_info().unwrap();
+        assert_eq!(info.domain, ERROR_DOMAIN);
+        let field = details
+            .bad_request()
+            .map(|x| x.field_violations[0].field.clone())
+            .unwrap_or_default();
+        (Reason::from_str_name(&info.reason).unwrap(), field)
+    };
+    let set = |namespace: &str, id: &str| rebacs_proto::Set {
+        namespace: namespace.to_string(),
+        id: id.to_string(),
+        relation: "read".to_string(),
+    };
+
+    let err = extract_dst(None).unwrap_err();
+    assert_eq!(details(&err), (Reason::FieldMissing, "dst".to_string()));
+    let err = extract_dst(Some(set("", "foo"))).unwrap_err();
+    assert_eq!(
+        details(&err),
+        (Reason::FieldMissing, "dst.namespace".to_string())
+    );
+    let err = extract_dst(Some(set("file", ""))).unwrap_err();
+    assert_eq!(details(&err), (Reason::FieldMissing, "dst.id".to_string()));
+    let err = extract_dst(Some(set("file", "foo]"))).unwrap_err();
+    assert_eq!(details(&err), (Reason::FieldInvalid, "dst".to_string()));
+
+    let mut interceptor = test_interceptor(b"secret");
+    let err = interceptor.call(Request::new(())).unwrap_err();
+    assert_eq!(details(&err), (Reason::TokenMissing, String::new()));
+    let expired = TestClaims {
+        aud: vec!["rebacs"],
+        exp: 1,
+        iat: 0,
+        iss: "https://issuer.test",
+        sub: "alice",
+        azp: "test",
+    };
+    let expired = encode(
+        &Header::default(),
+        &expired,
+        &EncodingKey::from_secret(b"secret"),
+    )
+    .unwrap();
+    let err = interceptor.call(authorized_request(&expired)).unwrap_err();
+    assert_eq!(details(&err), (Reason::TokenExpired, String::new()));
+    let err = interceptor
+        .call(authorized_request(&test_token(b"other", "alice")))
+        .unwrap_err();
+    assert_eq!(details(&err), (Reason::TokenInvalid, String::new()));
+
+    let service = test_service(RelationGraph::default());
+    let grant = |dst| GrantReq {
+        src: None,
+        dst: Some(dst),
+        dry_run: false,
+    };
+    let err = service
+        .grant(test_request(grant(set("file", "foo")), "alice"))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::PermissionDenied);
+    assert_eq!(details(&err), (Reason::NotPermitted, "dst".to_string()));
+
+    // the field of a batch entry is prefixed with its index
+    let batch = BatchGrantReq {
+        grants: vec![grant(set("file", "foo")), grant(set("file", ""))],
+        dry_run: false,
+    };
+    let err = service
+        .batch_grant(test_request(batch, "alice"))
+        .await
+        .unwrap_err();
+    assert_eq!(err.message(), "grants[1]: dst.id must be set");
+    assert_eq!(
+        details(&err),
+        (Reason::FieldMissing, "grants[1].dst.id".to_string())
+    );
+    let batch = BatchGrantReq {
+        grants: vec![grant(set("file", "foo"))],
+        dry_run: false,
+    };
+    let err = service
+        .batch_grant(test_request(batch, "alice"))
+        .await
+        .unwrap_err();
+    assert_eq!(
+        details(&err),
+        (Reason::NotPermitted, "grants[0].dst".to_string())
+    );
+}
+
+#[test]
+fn traceparent() {
+    let trace_id = "4bf92f3577b34da6a3ce929d0e0e4736";
+    let parent_id = "00f067aa0ba902b7";
+    let parent = |traceparent: &str| {
+        let mut metadata = MetadataMap::new();
+        metadata.insert("traceparent", traceparent.parse().unwrap());
+        parent_context(&metadata).span().span_context().clone()
+    };
+
+    let caller = parent(&format!("00-{trace_id}-{parent_id}-01"));
+    assert!(caller.is_valid() && caller.is_remote());
+    assert_eq!(caller.trace_id().to_string(), trace_id);
+    assert_eq!(caller.span_id().to_string(), parent_id);
+    // future versions may append fields
+    let caller = parent(&format!("01-{trace_id}-{parent_id}-00-extra"));
+    assert_eq!(caller.trace_id().to_string(), trace_id);
+
+    assert!(!parent_context(&MetadataMap::new())
+        .span()
+        .span_context()
+        .is_valid());
+    for invalid in [
+        String::new(),
+        format!("00-{trace_id}-{parent_id}"),
+        format!("00-{trace_id}-{parent_id}-01-extra"),
+        format!("ff-{trace_id}-{parent_id}-01"),
+        format!("00-{}-{parent_id}-01", trace_id.to_uppercase()),
+        format!("00-{}-{parent_id}-01", "0".repeat(32)),
+        format!("00-{trace_id}-{}-01", "0".repeat(16)),
+    ] {
+        assert!(!parent(&invalid).is_valid(), "{invalid}");
+    }
+}
+
+#[tokio::test]
+async fn trace_parent_span() {
+    let exporter = InMemorySpanExporter::default();
+    let provider = opentelemetry_sdk::trace::TracerProvider::builder()
+        .with_simple_exporter(exporter.clone())
+        .build();
+    let subscriber = tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let trace_id = "4bf92f3577b34da6a3ce929d0e0e4736";
+    let parent_id = "00f067aa0ba902b7";
+    let mut request = test_request(
+        IsPermittedReq {
+            src: None,
+            dst: None,
+            min_revision: 0,
+        },
+        "alice",
+    );
+    request.metadata_mut().insert(
+        "traceparent",
+        format!("00-{trace_id}-{parent_id}-01").parse().unwrap(),
+    );
+    let service = test_service(RelationGraph::default());
+    _ = service.is_permitted(request).await;
+
+    // the span of the request continues the trace of the caller
+    provider.force_flush();
+    let spans = exporter.get_finished_spans().unwrap();
+    let span = spans.iter().find(|x| x.name == "is_permitted").unwrap();
+    assert_eq!(span.span_context.trace_id().to_string(), trace_id);
+    assert_eq!(span.parent_span_id.to_string(), parent_id);
+}
+
+#[test]
+fn multiple_audiences() {
+    let mut interceptor = AuthInterceptor {
+        issuers: vec![(
+            Arc::new(RwLock::new(DecodingKey::from_secret(b"secret"))),
+            token_validation(Algorithm::HS256, "https://issuer.test", &["app-a", "app-b"]),
+        )],
+        ..Default::default()
+    };
+    let token = |aud| {
+        let claims = TestClaims {
+            aud,
+            exp: 4_000_000_000,
+            iat: 0,
+            iss: "https://issuer.test",
+            sub: "alice",
+            azp: "test",
+        };
+        encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(b"secret"),
+        )
+        .unwrap()
+    };
+
+    // the audiences of the token have to intersect the accepted ones
+    for aud in [vec!["app-a"], vec!["app-b"], vec!["other", "app-b"]] {
+        let request = interceptor
+            .call(authorized_request(&token(aud.clone())))
+            .unwrap_or_else(|err| panic!("{aud:?} rejected: {err}"));
+        assert_eq!(request.extensions().get::<Claims>().unwrap().sub, "alice");
+    }
+    for aud in [vec![], vec!["other"], vec!["app-c", "rebacs"]] {
+        let err = interceptor
+            .call(authorized_request(&token(aud)))
+            .unwrap_err();
+        assert_eq!(err.code(), Code::Unauthenticated);
+    }
+}